@@ -0,0 +1,151 @@
+#ifndef SUPER_RODIO_H
+#define SUPER_RODIO_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/*
+ * Opaque handle of a player
+ */
+typedef struct SuperRodioPlayer SuperRodioPlayer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/*
+ * Make a player, freed by `super_rodio_player_free`
+ */
+SuperRodioPlayer *super_rodio_player_new(void);
+
+/*
+ * Shut the player down and free it
+ *
+ * # Safety
+ *
+ * `player` is made by `super_rodio_player_new` and not
+ * freed yet, or null
+ */
+void super_rodio_player_free(SuperRodioPlayer *player);
+
+/*
+ * Add a song to the waiting list, returns its id, or
+ * 0 if the player or the strings are invalid
+ *
+ * # Safety
+ *
+ * `player` is a live player, `name` and `path` are
+ * NUL-terminated strings
+ */
+uint64_t super_rodio_player_add(const SuperRodioPlayer *player, const char *name, const char *path);
+
+/*
+ * Play the songs in waiting list without waiting for
+ * them to end, returns `false` if the player is invalid
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_play(const SuperRodioPlayer *player);
+
+/*
+ * Pause or resume current song
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_toggle(const SuperRodioPlayer *player);
+
+/*
+ * Pause current song
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_pause(const SuperRodioPlayer *player);
+
+/*
+ * Resume current song
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_resume(const SuperRodioPlayer *player);
+
+/*
+ * Stop playing
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_stop(const SuperRodioPlayer *player);
+
+/*
+ * Skip current song
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_skip(const SuperRodioPlayer *player);
+
+/*
+ * Set the volume, 1.0 for the original loudness
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_set_volume(const SuperRodioPlayer *player, float volume);
+
+/*
+ * Seek current song to the position in milliseconds
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_seek(const SuperRodioPlayer *player, uint64_t position_ms);
+
+/*
+ * Check whether a song is playing
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+bool super_rodio_player_is_playing(const SuperRodioPlayer *player);
+
+/*
+ * Current song as JSON, freed by `super_rodio_string_free`,
+ * or null if the player is invalid
+ *
+ * # Safety
+ *
+ * `player` is a live player
+ */
+char *super_rodio_player_current_song_json(const SuperRodioPlayer *player);
+
+/*
+ * Free a string returned by the player
+ *
+ * # Safety
+ *
+ * `s` is returned by the player and not freed yet, or null
+ */
+void super_rodio_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SUPER_RODIO_H */
@@ -0,0 +1,264 @@
+//! C ABI of the player, declared in `include/super_rodio.h`
+//!
+//! Strings are UTF-8 and NUL-terminated; strings returned
+//! by the player are freed by `super_rodio_string_free`
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr,
+    time::Duration,
+};
+
+use super_rodio::{Make, Player, SharedPlayer, Song};
+
+/// Opaque handle of a player
+pub struct SuperRodioPlayer(SharedPlayer);
+
+unsafe fn player_arg<'a>(player: *const SuperRodioPlayer) -> Option<&'a SharedPlayer> {
+    player.as_ref().map(|player| &player.0)
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+    if s.is_null() {
+        return None;
+    }
+    CStr::from_ptr(s).to_str().ok()
+}
+
+/// Make a player, freed by `super_rodio_player_free`
+#[no_mangle]
+pub extern "C" fn super_rodio_player_new() -> *mut SuperRodioPlayer {
+    Box::into_raw(Box::new(SuperRodioPlayer(SharedPlayer::make())))
+}
+
+/// Shut the player down and free it
+///
+/// # Safety
+///
+/// `player` is made by `super_rodio_player_new` and not
+/// freed yet, or null
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_free(player: *mut SuperRodioPlayer) {
+    if !player.is_null() {
+        drop(Box::from_raw(player));
+    }
+}
+
+/// Add a song to the waiting list, returns its id, or
+/// 0 if the player or the strings are invalid
+///
+/// # Safety
+///
+/// `player` is a live player, `name` and `path` are
+/// NUL-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_add(
+    player: *const SuperRodioPlayer,
+    name: *const c_char,
+    path: *const c_char,
+) -> u64 {
+    let (Some(player), Some(name), Some(path)) = (player_arg(player), str_arg(name), str_arg(path))
+    else {
+        return 0;
+    };
+    player
+        .add(Song::from(name.to_string(), path.to_string()))
+        .join()
+        .map_or(0, u64::from)
+}
+
+/// Play the songs in waiting list without waiting for
+/// them to end, returns `false` if the player is invalid
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_play(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).map(|player| player.play()).is_some()
+}
+
+/// Pause or resume current song
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_toggle(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).is_some_and(|player| player.toggle().join().is_ok())
+}
+
+/// Pause current song
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_pause(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).is_some_and(|player| player.pause().join().is_ok())
+}
+
+/// Resume current song
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_resume(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).is_some_and(|player| player.resume().join().is_ok())
+}
+
+/// Stop playing
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_stop(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).is_some_and(|player| player.stop().join().is_ok())
+}
+
+/// Skip current song
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_skip(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).is_some_and(|player| player.skip().join().is_ok())
+}
+
+/// Set the volume, 1.0 for the original loudness
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_set_volume(
+    player: *const SuperRodioPlayer,
+    volume: f32,
+) -> bool {
+    player_arg(player).is_some_and(|player| player.set_volume(volume).join().is_ok())
+}
+
+/// Seek current song to the position in milliseconds
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_seek(
+    player: *const SuperRodioPlayer,
+    position_ms: u64,
+) -> bool {
+    let pos = Duration::from_millis(position_ms);
+    player_arg(player).is_some_and(|player| player.seek(pos).join().is_ok())
+}
+
+/// Check whether a song is playing
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_is_playing(player: *const SuperRodioPlayer) -> bool {
+    player_arg(player).is_some_and(|player| player.is_playing_now())
+}
+
+/// Current song as JSON, freed by `super_rodio_string_free`,
+/// or null if the player is invalid
+///
+/// # Safety
+///
+/// `player` is a live player
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_player_current_song_json(
+    player: *const SuperRodioPlayer,
+) -> *mut c_char {
+    player_arg(player)
+        .and_then(|player| serde_json::to_string(&player.current_song_now()).ok())
+        .and_then(|json| CString::new(json).ok())
+        .map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// Free a string returned by the player
+///
+/// # Safety
+///
+/// `s` is returned by the player and not freed yet, or null
+#[no_mangle]
+pub unsafe extern "C" fn super_rodio_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{thread::sleep, time::Instant};
+    use super_rodio::NullOutput;
+
+    /// Write a WAV file of silence to the temp folder
+    fn write_silence(name: &str, length: Duration) -> CString {
+        let rate = 8000u32;
+        let data_len = (rate as f32 * length.as_secs_f32()) as u32 * 2;
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        bytes.extend_from_slice(&(rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        bytes.resize(44 + data_len as usize, 0);
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        CString::new(path.to_str().unwrap()).unwrap()
+    }
+
+    /// Read the current song through the C ABI, freeing the string
+    unsafe fn current_song(player: *const SuperRodioPlayer) -> serde_json::Value {
+        let json = super_rodio_player_current_song_json(player);
+        let song = serde_json::from_str(CStr::from_ptr(json).to_str().unwrap()).unwrap();
+        super_rodio_string_free(json);
+        song
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let path = write_silence("super_rodio_ffi.wav", Duration::from_secs(10));
+        let name = CString::new("Silence").unwrap();
+        unsafe {
+            let player = super_rodio_player_new();
+            (*player).0.use_null_output(NullOutput::new(1.0));
+            assert_eq!(
+                super_rodio_player_add(player, ptr::null(), path.as_ptr()),
+                0
+            );
+            assert_ne!(
+                super_rodio_player_add(player, name.as_ptr(), path.as_ptr()),
+                0
+            );
+
+            assert!(current_song(player)["song"].is_null());
+
+            assert!(super_rodio_player_play(player));
+            let start = Instant::now();
+            while !super_rodio_player_is_playing(player) {
+                assert!(start.elapsed() < Duration::from_secs(5));
+                sleep(Duration::from_millis(10));
+            }
+            assert_eq!(current_song(player)["song"]["name"], "Silence");
+
+            assert!(super_rodio_player_stop(player));
+            super_rodio_player_free(player);
+            assert!(super_rodio_player_current_song_json(ptr::null()).is_null());
+        }
+    }
+}
@@ -0,0 +1,973 @@
+use std::{
+    panic::{catch_unwind, AssertUnwindSafe},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Receiver},
+        Arc, Weak,
+    },
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+use rodio::{OutputStream, OutputStreamHandle};
+
+use crate::{
+    asset::{seek_sink, PlaybackMode, PlaybackState, PlayerSnapshot, PlayerStatus},
+    bus::OutputMaker,
+    config::{Buffering, RetryPolicy, SpeedMode},
+    decode::decode_song,
+    device::{find_output_device, StreamConfig},
+    effect::Effect,
+    equalizer::EqBand,
+    event::PlayerEvent,
+    focus::AudioFocus,
+    folder::{scan_folder, watch_folder},
+    limiter::Limiter,
+    logging::log,
+    loudness::Normalization,
+    metrics::PlayerMetrics,
+    mirror::Mirror,
+    mixer::{Channel, Mixer, Outlet},
+    playback,
+    playlist::Playlist,
+    poison::Recover,
+    probe,
+    reply::{Reply, Responder},
+    shared_player::{SharedState, StateLock},
+    silence::SilenceTrim,
+    sleep_timer,
+    song::{ActiveSong, Song, SongId},
+    spatial::SpatialHandle,
+    stats::PlayStats,
+    watch::watch,
+};
+
+#[cfg(feature = "test-backend")]
+use crate::null_output::NullOutput;
+#[cfg(feature = "recording")]
+use crate::recorder::Recording;
+
+/// Operations handled by the actor thread of a player,
+/// each carries the responder of its reply
+pub enum PlayerCommand {
+    Add(Song, Responder<SongId>),
+    AddFolder(String, bool, Responder<std::io::Result<Vec<SongId>>>),
+    WatchFolder(String, bool, Responder<std::io::Result<()>>),
+    UnwatchFolder(String, Responder<bool>),
+    LoadPlaylist(Playlist, Responder<()>),
+    CreatePlaylist(String, Responder<bool>),
+    AddToPlaylist(String, Song, Responder<bool>),
+    QueuePlaylist(String, Responder<bool>),
+    GetPlaylist(String, Responder<Option<Playlist>>),
+    RemovePlaylist(String, Responder<Option<Playlist>>),
+    PlaylistNames(Responder<Vec<String>>),
+    Remove(SongId, Responder<Option<Song>>),
+    MoveSong(SongId, usize, Responder<bool>),
+    WaitingList(Responder<Vec<Song>>),
+    PlayedList(Responder<Vec<Song>>),
+    FailedList(Responder<Vec<Song>>),
+    WaitingRange(usize, usize, Responder<Vec<Song>>),
+    PlayedRange(usize, usize, Responder<Vec<Song>>),
+    Find(
+        Box<dyn Fn(&Song) -> bool + Send>,
+        Responder<Vec<(usize, Song)>>,
+    ),
+    Subscribe(Responder<Receiver<PlayerEvent>>),
+    Watch(Responder<Receiver<PlayerStatus>>),
+    CurrentSong(Responder<ActiveSong>),
+    Play(Responder<()>),
+    SetMode(PlaybackMode, Responder<()>),
+    Toggle(Responder<()>),
+    Pause(Responder<()>),
+    Resume(Responder<()>),
+    Stop(Responder<()>),
+    Skip(Responder<()>),
+    Previous(Responder<()>),
+    PlaySong(SongId, Responder<bool>),
+    PlayIndex(usize, Responder<bool>),
+    SetVolume(f32, Duration, Responder<()>),
+    SetMasterVolume(f32, Responder<()>),
+    Mute(Responder<()>),
+    Unmute(Responder<()>),
+    IsMuted(Responder<bool>),
+    SetAudioFocus(AudioFocus, Responder<()>),
+    Seek(Duration, Responder<()>),
+    NextChapter(Responder<()>),
+    PrevChapter(Responder<()>),
+    GotoChapter(usize, Responder<()>),
+    SetEq(Vec<EqBand>, Responder<()>),
+    SetNormalization(Option<Normalization>, Responder<()>),
+    SetLimiter(Limiter, Responder<()>),
+    SetBuffering(Buffering, Responder<()>),
+    SetCacheBudget(usize, Responder<()>),
+    SetProgressInterval(Duration, Responder<()>),
+    SetTrackGap(Duration, Responder<()>),
+    SetDuckLevel(f32, Responder<()>),
+    SetSpeed(f32, Responder<()>),
+    SetSpeedMode(SpeedMode, Responder<()>),
+    #[cfg(feature = "time-stretch")]
+    SetPitch(f32, Responder<()>),
+    Preload(Song, Responder<std::io::Result<()>>),
+    SetSilenceTrim(Option<SilenceTrim>, Responder<()>),
+    RememberPosition(bool, Responder<()>),
+    #[cfg(feature = "recording")]
+    StartRecording(String, Responder<std::io::Result<()>>),
+    #[cfg(feature = "recording")]
+    StopRecording(Responder<std::io::Result<()>>),
+    SetRetryPolicy(RetryPolicy, Responder<()>),
+    AddEffect(Box<dyn Effect + Send + Sync>, Responder<()>),
+    ClearEffects(Responder<()>),
+    SetBalance(f32, Responder<()>),
+    SetMono(bool, Responder<()>),
+    SeekBy(f32, Responder<()>),
+    SetLoopRegion(Option<(Duration, Duration)>, Responder<()>),
+    StopAfter(Duration, Option<Duration>, Responder<()>),
+    StopAfterCurrent(Responder<()>),
+    CancelStopAfter(Responder<()>),
+    Clear(Responder<()>),
+    ClearWaiting(Responder<()>),
+    ClearHistory(Responder<()>),
+    ReplayLast(Option<usize>, Responder<()>),
+    QueueLen(Responder<usize>),
+    Spectrum(usize, Responder<Vec<f32>>),
+    Levels(Responder<(f32, f32)>),
+    ChannelLevels(Responder<Vec<(f32, f32)>>),
+    QueueDuration(Responder<Duration>),
+    RemainingDuration(Responder<Duration>),
+    IsPlaying(Responder<bool>),
+    IsPaused(Responder<bool>),
+    GetPlaybackState(Responder<PlaybackState>),
+    SetDeviceMaker(
+        Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+        Responder<()>,
+    ),
+    SetStreamConfig(StreamConfig, Responder<()>),
+    SwitchDevice(
+        Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+        Responder<()>,
+    ),
+    SelectDevice(String, Responder<bool>),
+    AddMirror(
+        String,
+        Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+        Responder<std::io::Result<()>>,
+    ),
+    RemoveMirror(String, Responder<bool>),
+    SetMirrorVolume(String, f32, Responder<bool>),
+    #[cfg(feature = "test-backend")]
+    UseNullOutput(NullOutput, Responder<()>),
+    Channel(String, Responder<std::io::Result<Channel>>),
+    PlayOnce(Song, Responder<std::io::Result<()>>),
+    PlayOverlapping(Song, Responder<std::io::Result<()>>),
+    SetDucking(
+        [String; 2],
+        f32,
+        [Duration; 2],
+        Responder<std::io::Result<()>>,
+    ),
+    PlaySpatial(
+        Song,
+        [[f32; 3]; 3],
+        Responder<std::io::Result<SpatialHandle>>,
+    ),
+    SaveState(Responder<PlayerSnapshot>),
+    LoadState(PlayerSnapshot, Responder<()>),
+    Stats(Responder<PlayStats>),
+    Metrics(Responder<PlayerMetrics>),
+    LoadStats(PlayStats, Responder<()>),
+    #[cfg(feature = "serde")]
+    SaveSession(String, Responder<std::io::Result<()>>),
+    #[cfg(feature = "serde")]
+    RestoreSession(String, Responder<std::io::Result<()>>),
+    Shutdown(Responder<()>),
+}
+
+/// Consume commands in order until every handle of the player is gone
+pub(crate) fn run(player: Weak<StateLock>, inbox: Receiver<PlayerCommand>) {
+    while let Ok(command) = inbox.recv() {
+        let Some(player) = player.upgrade() else {
+            break;
+        };
+        let exiting = matches!(command, PlayerCommand::Shutdown(_));
+        // a panicking command only fails its own reply
+        let _ = catch_unwind(AssertUnwindSafe(|| command.execute(&player)));
+        if exiting {
+            // later commands fail along with their replies
+            break;
+        }
+    }
+}
+
+/// Stop playing, wait for the play loop to exit
+/// and release the output
+fn shutdown(state: &SharedState) {
+    log!(info, "shutting down the player");
+    {
+        let mut state = state.write();
+        state.sleep_timer = None;
+        // event callbacks end along with their channels
+        state.subscribers.clear();
+        for (_, stop) in state.folder_watches.drain() {
+            stop.store(true, Ordering::Relaxed);
+        }
+    }
+    halt_loop(state);
+    let output = {
+        let mut state = state.write();
+        state.hot.write().sink = None;
+        state.mixer = None;
+        state.bus.detach()
+    };
+    // the device is closed without holding the lock
+    drop(output);
+}
+
+/// Stop the song playing and wait for the play loop to
+/// exit, no song is started until `halting` is cleared
+fn halt_loop(state: &SharedState) {
+    let play_thread = {
+        let mut state = state.write();
+        state.halting = true;
+        state.stop_after_current = true;
+        state.queue_changed.notify();
+        if let Some(sink) = &state.hot.read().sink {
+            sink.stop();
+        }
+        state.play_thread.take()
+    };
+    if let Some(play_thread) = play_thread {
+        let _ = play_thread.join();
+    }
+}
+
+/// Restore the snapshot once the song playing is stopped,
+/// so that the play loop doesn't outlive the current song
+fn restore(state: &SharedState, snapshot: PlayerSnapshot) {
+    halt_loop(state);
+    let mut state = state.write();
+    state.halting = false;
+    state.stop_after_current = false;
+    state.restore(snapshot);
+}
+
+/// Play the song at the index of waiting list right away,
+/// starting the play loop if it's not running
+fn jump_to(state: &SharedState, index: usize) -> bool {
+    let idle = {
+        let mut state = state.write();
+        if !state.jump_to(index) {
+            return false;
+        }
+        !state.loop_running
+    };
+    if idle {
+        // nobody waits for the loop to end
+        let (_, responder) = Reply::pending();
+        playback::play(state, responder);
+    }
+    true
+}
+
+impl PlayerCommand {
+    fn execute(self, state: &SharedState) {
+        match self {
+            PlayerCommand::Add(song, reply) => {
+                reply.send(state.write().enqueue(song));
+            }
+            PlayerCommand::AddFolder(path, recursive, reply) => {
+                log!(info, "add folder {:?}", path);
+                let state = Arc::clone(state);
+                // scanned and probed before taking the lock
+                off_actor(reply, move || {
+                    let songs = scan_folder(Path::new(&path), recursive)?;
+                    let mut state = state.write();
+                    Ok(songs.into_iter().map(|song| state.enqueue(song)).collect())
+                });
+            }
+            PlayerCommand::WatchFolder(path, recursive, reply) => {
+                let stop = Arc::new(AtomicBool::new(false));
+                let watched = watch_folder(state, path.clone(), recursive, Arc::clone(&stop));
+                if watched.is_ok() {
+                    log!(info, "watch folder {:?}", path);
+                    let old = state.write().folder_watches.insert(path, stop);
+                    if let Some(old) = old {
+                        old.store(true, Ordering::Relaxed);
+                    }
+                }
+                reply.send(watched);
+            }
+            PlayerCommand::UnwatchFolder(path, reply) => {
+                let stop = state.write().folder_watches.remove(&path);
+                if let Some(stop) = &stop {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                reply.send(stop.is_some());
+            }
+            PlayerCommand::LoadPlaylist(playlist, reply) => {
+                state.write().load_playlist(playlist);
+                reply.send(());
+            }
+            PlayerCommand::CreatePlaylist(name, reply) => {
+                let mut state = state.write();
+                let created = !state.playlists.contains_key(&name);
+                if created {
+                    state.playlists.insert(name.clone(), Playlist::new(name));
+                }
+                reply.send(created);
+            }
+            PlayerCommand::AddToPlaylist(name, song, reply) => {
+                let mut state = state.write();
+                let playlist = state.playlists.get_mut(&name);
+                let found = playlist.is_some();
+                if let Some(playlist) = playlist {
+                    playlist.push(song);
+                }
+                reply.send(found);
+            }
+            PlayerCommand::QueuePlaylist(name, reply) => {
+                let mut state = state.write();
+                let playlist = state.playlists.get(&name).cloned();
+                let found = playlist.is_some();
+                if let Some(playlist) = playlist {
+                    state.load_playlist(playlist);
+                }
+                reply.send(found);
+            }
+            PlayerCommand::GetPlaylist(name, reply) => {
+                reply.send(state.read().playlists.get(&name).cloned());
+            }
+            PlayerCommand::RemovePlaylist(name, reply) => {
+                reply.send(state.write().playlists.remove(&name));
+            }
+            PlayerCommand::PlaylistNames(reply) => {
+                let mut names: Vec<_> = state.read().playlists.keys().cloned().collect();
+                names.sort();
+                reply.send(names);
+            }
+            PlayerCommand::Remove(id, reply) => {
+                log!(debug, "remove {:?} from waiting list", id);
+                reply.send(state.write().waiting_q.remove(id));
+            }
+            PlayerCommand::MoveSong(id, index, reply) => {
+                log!(debug, "move {:?} to {} of waiting list", id, index);
+                reply.send(state.write().waiting_q.move_to(id, index));
+            }
+            PlayerCommand::WaitingList(reply) => {
+                reply.send(state.read().waiting_list());
+            }
+            PlayerCommand::WaitingRange(offset, len, reply) => {
+                reply.send(state.read().waiting_q.range(offset, len));
+            }
+            PlayerCommand::PlayedRange(offset, len, reply) => {
+                reply.send(state.read().played_q.range(offset, len));
+            }
+            PlayerCommand::Find(predicate, reply) => {
+                reply.send(state.read().find(predicate));
+            }
+            PlayerCommand::PlayedList(reply) => {
+                reply.send(state.read().played_list());
+            }
+            PlayerCommand::FailedList(reply) => {
+                reply.send(state.read().failed_list());
+            }
+            PlayerCommand::Subscribe(reply) => {
+                let (sender, receiver) = channel();
+                state.write().subscribers.push(sender);
+                reply.send(receiver);
+            }
+            PlayerCommand::Watch(reply) => reply.send(watch(state)),
+            PlayerCommand::CurrentSong(reply) => {
+                reply.send(state.read().hot.read().current_song());
+            }
+            PlayerCommand::Play(reply) => playback::play(state, reply),
+            PlayerCommand::SetMode(mode, reply) => {
+                let mut state = state.write();
+                state.mode = mode;
+                // a loop waiting for songs stops out of the mode
+                state.queue_changed.notify();
+                reply.send(());
+            }
+            PlayerCommand::Toggle(reply) => {
+                let state = state.read();
+                let mut hot = state.hot.write();
+                if hot.is_playing() {
+                    hot.pause();
+                } else {
+                    hot.resume();
+                }
+                reply.send(());
+            }
+            PlayerCommand::Pause(reply) => {
+                state.read().pause();
+                reply.send(());
+            }
+            PlayerCommand::Resume(reply) => {
+                state.read().resume();
+                reply.send(());
+            }
+            PlayerCommand::Stop(reply) => {
+                let mut state = state.write();
+                state.bookmark_current();
+                if state.mode == PlaybackMode::CONTINUOUS && state.loop_running || state.retrying {
+                    state.stop_after_current = true;
+                    state.queue_changed.notify();
+                }
+                // check if old sink exists and stop it
+                if let Some(sink) = &state.hot.read().sink {
+                    sink.stop();
+                };
+                reply.send(());
+            }
+            PlayerCommand::Skip(reply) => {
+                let mut state = state.write();
+                state.bookmark_current();
+                let sink = state.hot.read().active_sink().cloned();
+                if let Some(sink) = sink {
+                    sink.skip_one();
+                    state.skipping = true;
+                }
+                if state.retrying {
+                    state.skipping = true;
+                    state.queue_changed.notify();
+                }
+                reply.send(());
+            }
+            PlayerCommand::Previous(reply) => {
+                let settle = state.write().previous();
+                if let Some(settle) = settle {
+                    settle.wait();
+                }
+                reply.send(());
+            }
+            PlayerCommand::PlaySong(id, reply) => {
+                let index = state.read().waiting_q.position(id);
+                reply.send(index.is_some_and(|index| jump_to(state, index)));
+            }
+            PlayerCommand::PlayIndex(index, reply) => {
+                reply.send(jump_to(state, index));
+            }
+            PlayerCommand::SetVolume(volume, ramp, reply) => {
+                state.write().set_volume(volume, ramp);
+                reply.send(());
+            }
+            PlayerCommand::SetMasterVolume(volume, reply) => {
+                state.read().master_volume.set(volume.max(0.0));
+                reply.send(());
+            }
+            PlayerCommand::Mute(reply) => {
+                state.write().set_muted(true);
+                reply.send(());
+            }
+            PlayerCommand::Unmute(reply) => {
+                state.write().set_muted(false);
+                reply.send(());
+            }
+            PlayerCommand::IsMuted(reply) => {
+                reply.send(state.read().muted);
+            }
+            PlayerCommand::SetAudioFocus(focus, reply) => {
+                state.write().set_focus(focus);
+                reply.send(());
+            }
+            PlayerCommand::Seek(pos, reply) => {
+                let sink = state.read().hot.read().sink.clone();
+                let settle = sink.and_then(|sink| seek_sink(&sink, pos).ok());
+                if let Some(settle) = settle {
+                    settle.wait();
+                }
+                reply.send(());
+            }
+            PlayerCommand::NextChapter(reply) => {
+                let settle = state.read().next_chapter();
+                if let Some(settle) = settle {
+                    settle.wait();
+                }
+                reply.send(());
+            }
+            PlayerCommand::PrevChapter(reply) => {
+                let settle = state.read().prev_chapter();
+                if let Some(settle) = settle {
+                    settle.wait();
+                }
+                reply.send(());
+            }
+            PlayerCommand::GotoChapter(index, reply) => {
+                let settle = state.read().goto_chapter(index);
+                if let Some(settle) = settle {
+                    settle.wait();
+                }
+                reply.send(());
+            }
+            PlayerCommand::SeekBy(secs, reply) => {
+                let target = {
+                    let state = state.read();
+                    let hot = state.hot.read();
+                    // an infinite or NaN offset isn't a position
+                    hot.sink
+                        .clone()
+                        .filter(|_| !secs.is_nan())
+                        .and_then(|sink| {
+                            let pos = (hot.sink_pos(&sink).as_secs_f32() + secs).max(0.0);
+                            Some((sink, Duration::try_from_secs_f32(pos).ok()?))
+                        })
+                };
+                // seeked without holding the lock
+                let settle = target.and_then(|(sink, pos)| seek_sink(&sink, pos).ok());
+                if let Some(settle) = settle {
+                    settle.wait();
+                }
+                reply.send(());
+            }
+            PlayerCommand::SetLoopRegion(region, reply) => {
+                // an empty region means no loop
+                state.write().loop_region = region.filter(|(start, end)| start < end);
+                reply.send(());
+            }
+            PlayerCommand::StopAfter(after, fade_out, reply) => {
+                let fade_out = fade_out.unwrap_or_default().min(after);
+                state.write().cancel_sleep_timer();
+                sleep_timer::start(state, Instant::now() + after, fade_out);
+                reply.send(());
+            }
+            PlayerCommand::StopAfterCurrent(reply) => {
+                state.write().stop_after_current = true;
+                reply.send(());
+            }
+            PlayerCommand::CancelStopAfter(reply) => {
+                let mut state = state.write();
+                state.cancel_sleep_timer();
+                state.stop_after_current = false;
+                reply.send(());
+            }
+            PlayerCommand::SetEq(bands, reply) => {
+                *state.read().eq_bands.write().recover() = bands;
+                reply.send(());
+            }
+            PlayerCommand::SetNormalization(normalization, reply) => {
+                state.write().normalization = normalization;
+                reply.send(());
+            }
+            PlayerCommand::SetLimiter(limiter, reply) => {
+                *state.read().bus.taps.limiter.lock().recover() = limiter;
+                reply.send(());
+            }
+            PlayerCommand::SetBuffering(buffering, reply) => {
+                state.write().buffering = buffering;
+                reply.send(());
+            }
+            PlayerCommand::SetCacheBudget(budget, reply) => {
+                let cache = Arc::clone(&state.read().cache);
+                cache.lock().recover().set_budget(budget);
+                reply.send(());
+            }
+            PlayerCommand::SetProgressInterval(interval, reply) => {
+                state.write().progress_interval = interval;
+                reply.send(());
+            }
+            PlayerCommand::SetTrackGap(gap, reply) => {
+                state.write().track_gap = gap;
+                reply.send(());
+            }
+            PlayerCommand::SetDuckLevel(level, reply) => {
+                state.read().ducking.set_level(level);
+                reply.send(());
+            }
+            PlayerCommand::SetSpeed(speed, reply) => {
+                state.write().set_speed(speed);
+                reply.send(());
+            }
+            PlayerCommand::SetSpeedMode(mode, reply) => {
+                state.write().set_speed_mode(mode);
+                reply.send(());
+            }
+            #[cfg(feature = "time-stretch")]
+            PlayerCommand::SetPitch(semitones, reply) => {
+                state.read().hot.read().stretch.set_pitch(semitones);
+                reply.send(());
+            }
+            PlayerCommand::Preload(song, reply) => {
+                let (buffering, decoder, cache) = {
+                    let state = state.read();
+                    (state.buffering, state.decoder, Arc::clone(&state.cache))
+                };
+                // a sound fitting in the cache is kept there
+                off_actor(reply, move || {
+                    decode_song(&song, &buffering, decoder, &cache).map(drop)
+                });
+            }
+            PlayerCommand::SetSilenceTrim(silence_trim, reply) => {
+                state.write().silence_trim = silence_trim;
+                reply.send(());
+            }
+            PlayerCommand::RememberPosition(enabled, reply) => {
+                let mut state = state.write();
+                state.remember_position = enabled;
+                if !enabled {
+                    state.bookmarks.clear();
+                }
+                reply.send(());
+            }
+            #[cfg(feature = "recording")]
+            PlayerCommand::StartRecording(path, reply) => {
+                reply.send((|| {
+                    let (recording, samples) = Recording::create(&path)?;
+                    // the former recording is completed first
+                    let former = {
+                        let mut state = state.write();
+                        *state.bus.taps.recorder.lock().recover() = Some(samples);
+                        state.recording.replace(recording)
+                    };
+                    former.map_or(Ok(()), Recording::finish)
+                })());
+            }
+            #[cfg(feature = "recording")]
+            PlayerCommand::StopRecording(reply) => {
+                let former = {
+                    let mut state = state.write();
+                    *state.bus.taps.recorder.lock().recover() = None;
+                    state.recording.take()
+                };
+                reply.send(former.map_or(Ok(()), Recording::finish));
+            }
+            PlayerCommand::SetRetryPolicy(retry, reply) => {
+                state.write().retry = retry;
+                reply.send(());
+            }
+            PlayerCommand::AddEffect(effect, reply) => {
+                state.write().effects.push(effect);
+                reply.send(());
+            }
+            PlayerCommand::ClearEffects(reply) => {
+                state.write().effects.clear();
+                reply.send(());
+            }
+            PlayerCommand::SetBalance(balance, reply) => {
+                state.read().balance.set(balance.clamp(-1.0, 1.0));
+                reply.send(());
+            }
+            PlayerCommand::SetMono(mono, reply) => {
+                state.read().mono.store(mono, Ordering::Relaxed);
+                reply.send(());
+            }
+            PlayerCommand::Clear(reply) => {
+                log!(debug, "clear the queues");
+                let mut state = state.write();
+                state.waiting_q.clear();
+                state.played_q.clear();
+                state.playlist = None;
+                reply.send(());
+            }
+            PlayerCommand::ClearWaiting(reply) => {
+                log!(debug, "clear waiting list");
+                let mut state = state.write();
+                state.waiting_q.clear();
+                state.playlist = None;
+                reply.send(());
+            }
+            PlayerCommand::ClearHistory(reply) => {
+                log!(debug, "clear played history");
+                state.write().played_q.clear();
+                reply.send(());
+            }
+            PlayerCommand::ReplayLast(n, reply) => {
+                let mut state = state.write();
+                let n = n.unwrap_or(state.played_q.len());
+                // the songs played only once are dropped
+                for song in state.played_q.take_back(n) {
+                    if song.replayable() {
+                        state.waiting_q.push(song);
+                    }
+                }
+                state.queue_changed.notify();
+                reply.send(());
+            }
+            PlayerCommand::QueueLen(reply) => {
+                reply.send(state.read().waiting_q.len());
+            }
+            PlayerCommand::Spectrum(bins, reply) => {
+                let window = Arc::clone(&state.read().bus.taps.window);
+                let spectrum = window.lock().recover().spectrum(bins);
+                reply.send(spectrum);
+            }
+            PlayerCommand::Levels(reply) => {
+                let window = Arc::clone(&state.read().bus.taps.window);
+                let levels = window.lock().recover().levels();
+                reply.send(levels.into_iter().fold((0.0, 0.0), |(peak, rms), level| {
+                    (level.0.max(peak), level.1.max(rms))
+                }));
+            }
+            PlayerCommand::ChannelLevels(reply) => {
+                let window = Arc::clone(&state.read().bus.taps.window);
+                let levels = window.lock().recover().levels();
+                reply.send(levels);
+            }
+            PlayerCommand::QueueDuration(reply) => {
+                let state = Arc::clone(state);
+                off_actor(reply, move || queue_duration(&state));
+            }
+            PlayerCommand::RemainingDuration(reply) => {
+                let current = state.read().hot.read().remaining();
+                let state = Arc::clone(state);
+                off_actor(reply, move || queue_duration(&state) + current);
+            }
+            PlayerCommand::IsPlaying(reply) => {
+                reply.send(state.read().is_playing());
+            }
+            PlayerCommand::IsPaused(reply) => {
+                let state = state.read().playback_state();
+                reply.send(state == PlaybackState::PAUSED);
+            }
+            PlayerCommand::GetPlaybackState(reply) => {
+                reply.send(state.read().playback_state());
+            }
+            PlayerCommand::SetDeviceMaker(with_generator, reply) => {
+                let mut state = state.write();
+                state.output_maker = OutputMaker::Maker(with_generator.into());
+                state.device = None;
+                #[cfg(feature = "test-backend")]
+                {
+                    state.null_output = None;
+                }
+                reply.send(());
+            }
+            PlayerCommand::SetStreamConfig(config, reply) => {
+                let mut state = state.write();
+                state.output_maker = OutputMaker::Config(config);
+                state.device = None;
+                #[cfg(feature = "test-backend")]
+                {
+                    state.null_output = None;
+                }
+                reply.send(());
+            }
+            PlayerCommand::SwitchDevice(with_generator, reply) => {
+                switch_device(state, OutputMaker::Maker(with_generator.into()), None);
+                reply.send(());
+            }
+            PlayerCommand::SelectDevice(name, reply) => match find_output_device(&name) {
+                Some(device) => {
+                    log!(info, "select output device {:?}", name);
+                    // a device failing to open later is reported
+                    // by the output, not a panic of the maker
+                    switch_device(state, OutputMaker::Device(device), Some(name));
+                    reply.send(true);
+                }
+                None => {
+                    log!(warn, "no output device named {:?}", name);
+                    reply.send(false);
+                }
+            },
+            PlayerCommand::AddMirror(name, with_generator, reply) => {
+                log!(info, "add mirror output {:?}", name);
+                let mirror = Mirror::open(name.clone(), with_generator);
+                reply.send(mirror.map(|mirror| {
+                    let mut state = state.write();
+                    state.mirrors.retain(|mirror| mirror.name != name);
+                    state.mirrors.push(mirror);
+                }));
+            }
+            PlayerCommand::RemoveMirror(name, reply) => {
+                let removed = {
+                    let mut state = state.write();
+                    let index = state.mirrors.iter().position(|mirror| mirror.name == name);
+                    index.map(|index| state.mirrors.remove(index))
+                };
+                // the output is released out of the lock
+                reply.send(removed.is_some());
+            }
+            PlayerCommand::SetMirrorVolume(name, volume, reply) => {
+                let state = state.read();
+                let mirror = state.mirrors.iter().find(|mirror| mirror.name == name);
+                if let Some(mirror) = mirror {
+                    mirror.set_volume(volume);
+                }
+                reply.send(mirror.is_some());
+            }
+            #[cfg(feature = "test-backend")]
+            PlayerCommand::UseNullOutput(output, reply) => {
+                state.write().null_output = Some(output);
+                reply.send(());
+            }
+            PlayerCommand::Channel(name, reply) => {
+                let state = Arc::clone(state);
+                off_actor(reply, move || {
+                    with_mixer(&state, |mixer| mixer.channel(&name))
+                });
+            }
+            PlayerCommand::PlayOnce(song, reply) => {
+                let state = Arc::clone(state);
+                off_actor(reply, move || outlet(&state)?.play_once(&song));
+            }
+            PlayerCommand::SetDucking([source, target], amount, [attack, release], reply) => {
+                let state = Arc::clone(state);
+                off_actor(reply, move || {
+                    with_mixer(&state, |mixer| {
+                        mixer.set_ducking(&source, &target, amount, attack, release)
+                    })
+                });
+            }
+            PlayerCommand::PlaySpatial(song, [emitter, left_ear, right_ear], reply) => {
+                let state = Arc::clone(state);
+                off_actor(reply, move || {
+                    outlet(&state)?.play_spatial(&song, emitter, left_ear, right_ear)
+                });
+            }
+            PlayerCommand::PlayOverlapping(song, reply) => {
+                let state = Arc::clone(state);
+                off_actor(reply, move || playback::play_overlapping(&state, &song));
+            }
+            PlayerCommand::Stats(reply) => {
+                reply.send(state.read().stats.clone());
+            }
+            PlayerCommand::Metrics(reply) => {
+                reply.send(state.read().metrics());
+            }
+            PlayerCommand::LoadStats(stats, reply) => {
+                state.write().stats = stats;
+                reply.send(());
+            }
+            PlayerCommand::SaveState(reply) => {
+                reply.send(state.read().snapshot());
+            }
+            PlayerCommand::LoadState(snapshot, reply) => {
+                restore(state, snapshot);
+                reply.send(());
+            }
+            #[cfg(feature = "serde")]
+            PlayerCommand::SaveSession(path, reply) => {
+                let snapshot = state.read().snapshot();
+                reply.send((|| {
+                    let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                    serde_json::to_writer(file, &snapshot)?;
+                    Ok(())
+                })());
+            }
+            #[cfg(feature = "serde")]
+            PlayerCommand::RestoreSession(path, reply) => {
+                reply.send((|| {
+                    let file = std::io::BufReader::new(std::fs::File::open(path)?);
+                    let snapshot: PlayerSnapshot = serde_json::from_reader(file)?;
+                    restore(state, snapshot);
+                    Ok(())
+                })());
+            }
+            PlayerCommand::Shutdown(reply) => {
+                shutdown(state);
+                reply.send(());
+            }
+        }
+    }
+}
+
+/// Replace how the output is opened and move current song to the
+/// new device, `device` is the name of the device if it's selected
+/// by name
+fn switch_device(state: &SharedState, output_maker: OutputMaker, device: Option<String>) {
+    let attached = {
+        let mut state = state.write();
+        state.output_maker = output_maker;
+        state.device = device.clone();
+        #[cfg(feature = "test-backend")]
+        {
+            state.null_output = None;
+        }
+        let position = {
+            let hot = state.hot.read();
+            hot.active_sink().map(|sink| hot.sink_pos(sink))
+        };
+        state.emit(PlayerEvent::DeviceChanged { device, position });
+        state.bus.is_attached()
+    };
+    // the sounds playing move to the new device as they
+    // are, the device is opened aside the actor
+    if attached {
+        let state = Arc::clone(state);
+        spawn(move || {
+            if let Err(e) = playback::attach_output(&state) {
+                log!(error, "failed to switch the output: {}", e);
+                state.write().emit(PlayerEvent::OutputFailed {
+                    error: e.to_string(),
+                });
+            }
+        });
+    }
+}
+
+/// Work on the mixer of named channels, which is made on
+/// first use along with the output of the player if it's
+/// not opened yet
+fn with_mixer<T>(state: &SharedState, f: impl FnOnce(&mut Mixer) -> T) -> std::io::Result<T> {
+    // the device is opened without holding the lock
+    let mut opened = None;
+    let (result, dropped) = loop {
+        if opened.is_none() && !state.read().bus.is_attached() {
+            opened = Some(playback::open_output(state)?);
+        }
+        let mut state = state.write();
+        // detached by the end of the play loop meanwhile
+        if opened.is_none() && !state.bus.is_attached() {
+            continue;
+        }
+        let dropped = opened.and_then(|output| state.bus.attach(output));
+        let mixer = match state.mixer.take() {
+            Some(mixer) => mixer,
+            None => Mixer::new(
+                state.bus.inputs.clone(),
+                Arc::clone(&state.cache),
+                Arc::clone(&state.master_volume),
+                Arc::clone(&state.ducking),
+            ),
+        };
+        break (f(state.mixer.insert(mixer)), dropped);
+    };
+    drop(dropped);
+    Ok(result)
+}
+
+/// Output of the mixer for sounds decoded without the lock
+fn outlet(state: &SharedState) -> std::io::Result<Outlet> {
+    with_mixer(state, |mixer| mixer.outlet())
+}
+
+/// Do slow work, e.g. decoding or probing songs, on its
+/// own thread and reply with the result, so that the
+/// commands after it aren't held up
+fn off_actor<T: Send + 'static>(reply: Responder<T>, work: impl FnOnce() -> T + Send + 'static) {
+    spawn(move || reply.send(work()));
+}
+
+/// Total length of the waiting songs, songs of
+/// unknown length are not counted
+fn queue_duration(state: &SharedState) -> Duration {
+    let (mut known, unknown) = {
+        let state = state.read();
+        let mut known = Duration::ZERO;
+        let mut unknown = vec![];
+        for song in state.waiting_q.iter() {
+            match state.durations.get(&song.path) {
+                Some(Some(duration)) => known += song.length_in(*duration),
+                Some(None) => {}
+                None => unknown.push(song.clone()),
+            }
+        }
+        (known, unknown)
+    };
+    // probe new songs without holding the lock
+    let probed: Vec<_> = unknown
+        .into_iter()
+        .map(|song| {
+            // streams and data URIs are of unknown length
+            let duration = song.file().and_then(|path| probe::duration_of(&path));
+            (song, duration)
+        })
+        .collect();
+    let mut state = state.write();
+    for (song, duration) in probed {
+        if let Some(duration) = duration {
+            known += song.length_in(duration);
+        }
+        state.durations.insert(song.path, duration);
+    }
+    known
+}
@@ -0,0 +1,139 @@
+use std::{
+    collections::VecDeque,
+    f32::consts::PI,
+    sync::{Arc, Mutex},
+};
+
+/// Frames kept for analysis, a power of two for the FFT
+const WINDOW: usize = 2048;
+
+/// Most recent frames played, interleaved
+#[derive(Default)]
+pub struct AudioWindow {
+    samples: VecDeque<f32>,
+    channels: u16,
+}
+
+/// Window shared with the final mix
+pub type SharedWindow = Arc<Mutex<AudioWindow>>;
+
+impl AudioWindow {
+    pub(crate) fn push(&mut self, samples: &[f32], channels: u16) {
+        if channels != self.channels {
+            self.samples.clear();
+            self.channels = channels;
+        }
+        self.samples.extend(samples);
+        let limit = WINDOW * channels as usize;
+        if self.samples.len() > limit {
+            self.samples.drain(..self.samples.len() - limit);
+        }
+    }
+
+    /// Forget the frames once nothing plays, keeping
+    /// the memory for the next sound
+    pub(crate) fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Frames mixed down to mono
+    fn mono(&self) -> Vec<f32> {
+        let channels = self.channels.max(1) as usize;
+        let samples: Vec<f32> = self.samples.iter().copied().collect();
+        samples
+            .chunks_exact(channels)
+            .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+            .collect()
+    }
+
+    /// Magnitudes of the window spread over `bins` bands
+    /// evenly from 0 Hz to Nyquist frequency, all zero
+    /// when nothing has been played
+    pub fn spectrum(&self, bins: usize) -> Vec<f32> {
+        let mut spectrum = vec![0.0; bins];
+        let mut mono = self.mono();
+        if bins == 0 || mono.is_empty() {
+            return spectrum;
+        }
+        // a song just started is padded with silence
+        mono.resize(WINDOW, 0.0);
+        let mut re: Vec<f32> = mono
+            .iter()
+            .enumerate()
+            .map(|(i, x)| x * hann(i, WINDOW))
+            .collect();
+        let mut im = vec![0.0; WINDOW];
+        fft(&mut re, &mut im);
+        // a full-scale sine peaks at about 1.0 after the hann window
+        let scale = 4.0 / WINDOW as f32;
+        let half = WINDOW / 2;
+        for (band, value) in spectrum.iter_mut().enumerate() {
+            let from = band * half / bins;
+            let to = ((band + 1) * half / bins).max(from + 1);
+            *value = (from..to)
+                .map(|k| (re[k] * re[k] + im[k] * im[k]).sqrt() * scale)
+                .fold(0.0, f32::max);
+        }
+        spectrum
+    }
+
+    /// Peak and RMS of the window on each channel,
+    /// empty when nothing has been played
+    pub fn levels(&self) -> Vec<(f32, f32)> {
+        let channels = self.channels as usize;
+        let frames = self.samples.len() / channels.max(1);
+        if frames == 0 {
+            return vec![];
+        }
+        let mut levels = vec![(0f32, 0f32); channels];
+        for (i, sample) in self.samples.iter().enumerate() {
+            let (peak, sum) = &mut levels[i % channels];
+            *peak = peak.max(sample.abs());
+            *sum += sample * sample;
+        }
+        for (_, sum) in &mut levels {
+            *sum = (*sum / frames as f32).sqrt();
+        }
+        levels
+    }
+}
+
+fn hann(i: usize, len: usize) -> f32 {
+    0.5 - 0.5 * (2.0 * PI * i as f32 / (len - 1) as f32).cos()
+}
+
+/// In-place radix-2 FFT, the length must be a power of two
+fn fft(re: &mut [f32], im: &mut [f32]) {
+    let n = re.len();
+    // bit-reversal permutation
+    let mut j = 0;
+    for i in 1..n {
+        let mut bit = n >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+    let mut len = 2;
+    while len <= n {
+        let angle = -2.0 * PI / len as f32;
+        for start in (0..n).step_by(len) {
+            for k in 0..len / 2 {
+                let (sin, cos) = (angle * k as f32).sin_cos();
+                let (a, b) = (start + k, start + k + len / 2);
+                let t_re = re[b] * cos - im[b] * sin;
+                let t_im = re[b] * sin + im[b] * cos;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+            }
+        }
+        len <<= 1;
+    }
+}
@@ -1,40 +1,940 @@
-use limited_queue::LimitedQueue;
-use rodio::{OutputStream, OutputStreamHandle, Sink};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    sync::{atomic::AtomicBool, mpsc::Sender, Arc, Mutex, RwLock},
+    thread::JoinHandle,
+    time::{Duration, Instant, SystemTime},
+};
+
+use rodio::{source::SeekError, OutputStream, Sink};
 
 use crate::{
-    make::Make,
-    song::{ActiveSong, Song},
+    actor::PlayerCommand,
+    bus::{Bus, OutputMaker, Taps},
+    cache::{SampleCache, SharedCache},
+    channels::SharedF32,
+    config::{Buffering, DecoderBackend, PlayerConfig, RetryPolicy, SkippedSongs, SpeedMode},
+    ducking::SharedDucking,
+    effect::Effect,
+    equalizer::EqBand,
+    event::PlayerEvent,
+    focus::{AudioFocus, DUCK_VOLUME},
+    logging::log,
+    loudness::Normalization,
+    metrics::PlayerMetrics,
+    mirror::Mirror,
+    mixer::Mixer,
+    playlist::Playlist,
+    queue::SongQueue,
+    ramp::{ramp_volume, SharedRamp, DEFAULT_RAMP},
+    signal::Signal,
+    silence::SilenceTrim,
+    song::{ActiveSong, Song, SongId, SongState},
+    stats::{stats_key, PlayStats},
+    stretch::SharedStretch,
 };
 
+#[cfg(feature = "test-backend")]
+use crate::null_output::NullOutput;
+#[cfg(feature = "recording")]
+use crate::recorder::Recording;
+
+/// Time into a chapter after which `prev_chapter`
+/// restarts it instead of going to the previous one
+const CHAPTER_RESTART: Duration = Duration::from_secs(3);
+
+/// Time into a song after which `previous` restarts
+/// it instead of going back to the last played one
+const PREVIOUS_RESTART: Duration = Duration::from_secs(3);
+
+/// Longest time to wait for the sink to report a seek
+const SEEK_SETTLE: Duration = Duration::from_millis(100);
+
+/// Range of the speed of the songs
+const MIN_SPEED: f32 = 0.25;
+const MAX_SPEED: f32 = 4.0;
+
 #[derive(Clone, Copy, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PlaybackMode {
     #[default]
     NORMAL,
 
     /// Auto play the audio in waiting queue
     AUTO,
+
+    /// Auto play, and wait for songs to be added once
+    /// waiting queue is empty, until stopped
+    CONTINUOUS,
+}
+
+/// What the player is doing, as a whole
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PlaybackState {
+    /// Nothing has been played yet
+    #[default]
+    IDLE,
+
+    PLAYING,
+    PAUSED,
+    /// The last song is over or stopped
+    STOPPED,
+}
+
+/// Identity of a song across its plays, its file, or
+/// its id if it's played from a source
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum BookmarkKey {
+    Path(String),
+    Id(Option<SongId>),
+}
+
+impl BookmarkKey {
+    fn of(song: &Song) -> BookmarkKey {
+        match song.source {
+            Some(_) => BookmarkKey::Id(song.id),
+            None => BookmarkKey::Path(
+                song.file()
+                    .unwrap_or(Cow::Borrowed(&song.path))
+                    .into_owned(),
+            ),
+        }
+    }
 }
 
 pub struct PlayerAsset {
-    pub sink: Option<Sink>,
-    pub waiting_q: LimitedQueue<Song>, // waiting queue
-    pub current: ActiveSong,
-    pub played_q: LimitedQueue<Song>, // played queue
+    /// Sink and current song, locked apart from the rest
+    pub hot: SharedHot,
+    /// Final mix of the sink and every other sound
+    pub bus: Bus,
+    pub waiting_q: SongQueue, // waiting queue
+    pub played_q: SongQueue,  // played queue
+    /// Songs failed to be opened or decoded
+    pub failed_q: SongQueue,
+    /// How to retry a song failed to be opened
+    pub retry: RetryPolicy,
+    /// Where the songs skipped over by a jump go
+    pub skipped_songs: SkippedSongs,
+    /// How songs are read and decoded
+    pub buffering: Buffering,
+    /// Decoder of songs which don't choose one
+    pub decoder: DecoderBackend,
+    /// Interval of progress events, zero if disabled
+    pub progress_interval: Duration,
+    /// Speed of the songs, 1.0 for the original one
+    pub speed: f32,
+    pub speed_mode: SpeedMode,
+    /// Decoded samples of short sounds played recently
+    pub cache: SharedCache,
+    /// Sounds of the channels ducking the others
+    pub ducking: SharedDucking,
+    /// Counters of the player, with stale gauges
+    pub metrics: PlayerMetrics,
+    /// Senders of the event subscribers
+    pub subscribers: Vec<Sender<PlayerEvent>>,
+    /// Senders of the status watchers
+    pub watchers: Vec<Sender<PlayerStatus>>,
+    /// Whether the thread pushing their statuses runs
+    pub watching: bool,
     pub volume: f32,
+    /// Volume scaling the player and all its channels
+    pub master_volume: Arc<SharedF32>,
+    /// Silence the sink, keeping `volume` to restore
+    pub muted: bool,
+    /// Audio focus reported by the OS
+    pub focus: AudioFocus,
+    /// Whether a transient loss of focus paused the player
+    pub paused_by_focus: bool,
+    /// Volume ramp of the sink going on
+    pub volume_ramp: SharedRamp,
     pub mode: PlaybackMode,
-    pub gen_out: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+    /// Time each song fades in from silence
+    pub fade_in: Duration,
+    /// Time each song fades out to silence before its end
+    pub fade_out: Duration,
+    /// Silence between songs played one after another
+    pub track_gap: Duration,
+    /// Equalizer bands shared with the playing source
+    pub eq_bands: Arc<RwLock<Vec<EqBand>>>,
+    /// Effect chain applied to every song, in order
+    pub effects: Vec<Box<dyn Effect + Send + Sync>>,
+    /// Stereo balance, from -1.0 (left) to 1.0 (right)
+    pub balance: Arc<SharedF32>,
+    /// Downmix every song to mono
+    pub mono: Arc<AtomicBool>,
+    /// WAV file recording the final mix, if any
+    #[cfg(feature = "recording")]
+    pub recording: Option<Recording>,
+    /// Thread of the play loop, if it's been started
+    pub play_thread: Option<JoinHandle<()>>,
+    /// Set from the start of the play loop until it's over,
+    /// so that no other loop is started meanwhile
+    pub loop_running: bool,
+    /// Set by `shutdown`, and while a snapshot is restored,
+    /// no song is started while it's set
+    pub halting: bool,
+    /// Loudness normalization applied per song, if any
+    pub normalization: Option<Normalization>,
+    /// Silence skipped at both ends of each song, if any
+    pub silence_trim: Option<SilenceTrim>,
+    /// Position to seek to for the next song being played
+    pub resume_at: Option<Duration>,
+    /// Remember where a song is stopped or skipped, and
+    /// continue from there the next time it's played
+    pub remember_position: bool,
+    /// Positions where songs are stopped or skipped
+    pub bookmarks: HashMap<BookmarkKey, Duration>,
+    /// Region of current song to play repeatedly,
+    /// from the start to the end position
+    pub loop_region: Option<(Duration, Duration)>,
+    /// Time to stop playing, and the length of
+    /// fade-out before it
+    pub sleep_timer: Option<(Instant, Duration)>,
+    /// Stop once current song is over, in any mode
+    pub stop_after_current: bool,
+    /// Playlist loaded into waiting list, kept to be
+    /// queued again if it's repeated
+    pub playlist: Option<Playlist>,
+    /// Playlists managed by the player, by their name
+    pub playlists: HashMap<String, Playlist>,
+    /// Listening statistics of the songs played
+    pub stats: PlayStats,
+    /// Known length of songs by their path
+    pub durations: HashMap<String, Option<Duration>>,
+    /// Set when current song is skipped, so that
+    /// the next song is played in any mode
+    pub skipping: bool,
+    /// Set when current song is skipped to go back to
+    /// the previous one, so that it's not counted as played
+    pub going_back: bool,
+    /// Notified when songs are added to waiting list,
+    /// or the play loop waiting for them should stop
+    pub queue_changed: Arc<Signal>,
+    /// Set while the play loop waits to retry a song,
+    /// which a stop or skip gives up
+    pub retrying: bool,
+    /// Name of the output device selected by name, if any
+    pub device: Option<String>,
+    /// How the output device is opened
+    pub output_maker: OutputMaker,
+    /// Named channels, made on first use
+    pub mixer: Option<Mixer>,
+    /// Folders watched for new files, with the flags
+    /// stopping their watchers
+    pub folder_watches: HashMap<String, Arc<AtomicBool>>,
+    /// Other outputs playing the songs along with the sink
+    pub mirrors: Vec<Mirror>,
+    /// Play without any device, instead of using `output_maker`
+    #[cfg(feature = "test-backend")]
+    pub null_output: Option<NullOutput>,
+    /// Inbox of the actor thread serving this player
+    pub commands: Sender<PlayerCommand>,
 }
 
-impl Make<Self> for PlayerAsset {
-    fn make() -> PlayerAsset {
+impl PlayerAsset {
+    pub fn new(config: PlayerConfig, commands: Sender<PlayerCommand>) -> PlayerAsset {
         PlayerAsset {
-            sink: None,
-            waiting_q: LimitedQueue::with_capacity(1000),
-            current: Default::default(),
-            played_q: LimitedQueue::with_capacity(1000),
-            volume: 0.5f32,
-            mode: Default::default(),
-            gen_out: Box::new(|| OutputStream::try_default().unwrap()),
+            hot: Default::default(),
+            bus: Bus::new(Arc::new(Taps {
+                limiter: Mutex::new(config.limiter),
+                ..Default::default()
+            })),
+            waiting_q: if config.unbounded_waiting {
+                SongQueue::unbounded()
+            } else {
+                SongQueue::with_capacity(config.waiting_capacity)
+            },
+            played_q: SongQueue::with_capacity(config.played_capacity),
+            failed_q: SongQueue::with_capacity(config.played_capacity),
+            retry: config.retry,
+            skipped_songs: config.skipped_songs,
+            buffering: config.buffering,
+            decoder: config.decoder,
+            progress_interval: config.progress_interval,
+            speed: 1.0,
+            speed_mode: config.speed_mode,
+            cache: Arc::new(Mutex::new(SampleCache::new(config.cache_budget))),
+            ducking: Default::default(),
+            metrics: Default::default(),
+            subscribers: vec![],
+            watchers: vec![],
+            watching: false,
+            volume: config.volume,
+            master_volume: Arc::new(SharedF32::new(1.0)),
+            muted: false,
+            focus: AudioFocus::GAINED,
+            paused_by_focus: false,
+            volume_ramp: Default::default(),
+            mode: config.mode,
+            fade_in: config.fade_in,
+            fade_out: config.fade_out,
+            track_gap: config.track_gap,
+            eq_bands: Default::default(),
+            effects: vec![],
+            balance: Default::default(),
+            mono: Default::default(),
+            #[cfg(feature = "recording")]
+            recording: None,
+            play_thread: None,
+            loop_running: false,
+            halting: false,
+            normalization: None,
+            silence_trim: None,
+            resume_at: None,
+            remember_position: false,
+            bookmarks: HashMap::new(),
+            loop_region: None,
+            sleep_timer: None,
+            stop_after_current: false,
+            playlist: None,
+            playlists: HashMap::new(),
+            stats: PlayStats::new(),
+            durations: HashMap::new(),
+            skipping: false,
+            going_back: false,
+            queue_changed: Default::default(),
+            retrying: false,
+            device: None,
+            output_maker: OutputMaker::Maker(Arc::new(|| OutputStream::try_default().unwrap())),
+            mixer: None,
+            mirrors: vec![],
+            folder_watches: HashMap::new(),
+            #[cfg(feature = "test-backend")]
+            null_output: None,
+            commands,
+        }
+    }
+}
+
+/// Part of the state changing as songs are played, under a
+/// lock of its own so that reading what's playing, or the
+/// play loop updating it, never waits for the queues
+///
+/// It's locked after [`PlayerAsset`] if both are, and never
+/// while another guard of it is held
+#[derive(Default)]
+pub struct HotState {
+    pub sink: Option<Arc<Sink>>,
+    pub current: ActiveSong,
+    /// Time stretch of the songs in `SpeedMode::STRETCH`
+    pub stretch: SharedStretch,
+}
+
+/// Hot state of a player, shared by its handles and threads
+pub(crate) type SharedHot = Arc<parking_lot::RwLock<HotState>>;
+
+impl HotState {
+    /// Position in current song of the sink, which
+    /// only counts the output if the song is stretched
+    pub fn sink_pos(&self, sink: &Sink) -> Duration {
+        self.stretch.song_pos(sink.get_pos())
+    }
+
+    /// Check whether the current song is playing
+    pub fn is_playing(&self) -> bool {
+        self.current.state == SongState::PLAY
+    }
+
+    /// Check whether there's a song being played,
+    /// either playing or paused
+    pub fn is_active(&self) -> bool {
+        matches!(self.current.state, SongState::PLAY | SongState::PAUSE)
+    }
+
+    /// Check whether the sink is paused
+    pub fn is_paused(&self) -> bool {
+        self.sink.as_ref().is_some_and(|sink| sink.is_paused())
+    }
+
+    /// Derive the playback state from both current
+    /// song and the sink
+    pub fn playback_state(&self) -> PlaybackState {
+        match self.current.state {
+            SongState::PLAY if self.is_paused() => PlaybackState::PAUSED,
+            SongState::PLAY => PlaybackState::PLAYING,
+            SongState::PAUSE => PlaybackState::PAUSED,
+            SongState::STOP => PlaybackState::STOPPED,
+            SongState::NONE if self.sink.is_some() => PlaybackState::STOPPED,
+            SongState::NONE => PlaybackState::IDLE,
+        }
+    }
+
+    /// Sink playing current song, if any
+    pub fn active_sink(&self) -> Option<&Arc<Sink>> {
+        self.sink.as_ref().filter(|_| self.is_active())
+    }
+
+    /// Position of the output in the current song, zero
+    /// if nothing is played
+    pub fn position(&self) -> Duration {
+        self.active_sink()
+            .map_or(Duration::ZERO, |sink| self.sink_pos(sink))
+    }
+
+    /// Time left of the current song
+    pub fn remaining(&self) -> Duration {
+        match self.is_active() {
+            true => self.current.duration.saturating_sub(self.position()),
+            false => Duration::ZERO,
+        }
+    }
+
+    /// Copy of current song, with the progress of the sink
+    pub fn current_song(&self) -> ActiveSong {
+        let mut current = self.current.clone();
+        if let (Some(sink), Some(_)) = (&self.sink, &current.song) {
+            current.progress = self.sink_pos(sink);
+        }
+        current
+    }
+
+    /// Pause the playing song
+    pub fn pause(&mut self) {
+        if let (Some(sink), SongState::PLAY) = (&self.sink, self.current.state) {
+            sink.pause();
+            self.current.state = SongState::PAUSE;
+        }
+    }
+
+    /// Resume the paused song
+    pub fn resume(&mut self) {
+        if let (Some(sink), SongState::PAUSE) = (&self.sink, self.current.state) {
+            sink.play();
+            self.current.state = SongState::PLAY;
+        }
+    }
+}
+
+/// Plain-data copy of the persistent part of [`PlayerAsset`]
+///
+/// Sink and device maker are left out since they can't
+/// outlive the process anyway
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerSnapshot {
+    pub waiting: Vec<Song>,
+    pub current: ActiveSong,
+    pub played: Vec<Song>,
+    pub volume: f32,
+    pub mode: PlaybackMode,
+}
+
+/// Cheap copy of what's playing, without the queues
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerStatus {
+    pub state: PlaybackState,
+    pub mode: PlaybackMode,
+    pub volume: f32,
+    pub muted: bool,
+    pub song: Option<Song>,
+    pub position: Duration,
+    pub duration: Duration,
+    /// Number of songs in waiting list
+    pub waiting: usize,
+    /// Number of songs in played history
+    pub played: usize,
+}
+
+/// Everything a front-end shows in a frame, taken
+/// under one short lock
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UiState {
+    /// Current song, its progress, volume and mode
+    pub status: PlayerStatus,
+    /// First songs of waiting list
+    pub queue: Vec<Song>,
+    /// Name of the output device selected, `None` for
+    /// the default one or a custom device maker
+    pub device: Option<String>,
+}
+
+/// Time as `mm:ss`, or `h:mm:ss` from an hour
+fn clock(time: Duration) -> String {
+    let secs = time.as_secs();
+    match secs / 3600 {
+        0 => format!("{:02}:{:02}", secs / 60, secs % 60),
+        hours => format!("{}:{:02}:{:02}", hours, secs / 60 % 60, secs % 60),
+    }
+}
+
+/// One line for CLIs and logs, e.g.
+/// `▶ 01:23/03:45 "Song Name" (queue: 12)`
+impl fmt::Display for PlayerStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let icon = match self.state {
+            PlaybackState::PLAYING => "▶",
+            PlaybackState::PAUSED => "⏸",
+            PlaybackState::IDLE | PlaybackState::STOPPED => "■",
+        };
+        write!(f, "{}", icon)?;
+        if let Some(song) = &self.song {
+            write!(
+                f,
+                " {}/{} \"{}\"",
+                clock(self.position),
+                clock(self.duration),
+                song.name
+            )?;
+        }
+        if self.muted {
+            write!(f, " [muted]")?;
+        }
+        write!(f, " (queue: {})", self.waiting)
+    }
+}
+
+impl PlayerAsset {
+    /// Copy of the waiting queue
+    pub fn waiting_list(&self) -> Vec<Song> {
+        self.waiting_q.iter().map(Clone::clone).collect()
+    }
+
+    /// Copy of the played history
+    pub fn played_list(&self) -> Vec<Song> {
+        self.played_q.iter().map(Clone::clone).collect()
+    }
+
+    /// Songs matching the predicate with their indices, in
+    /// waiting list first and then in played history
+    pub fn find(&self, predicate: impl Fn(&Song) -> bool) -> Vec<(usize, Song)> {
+        let matches = |queue: &SongQueue| {
+            queue
+                .iter()
+                .enumerate()
+                .filter(|(_, song)| predicate(song))
+                .map(|(index, song)| (index, song.clone()))
+                .collect::<Vec<_>>()
+        };
+        let mut found = matches(&self.waiting_q);
+        found.extend(matches(&self.played_q));
+        found
+    }
+
+    /// Copy of the failed songs
+    pub fn failed_list(&self) -> Vec<Song> {
+        self.failed_q.iter().map(Clone::clone).collect()
+    }
+
+    /// Send the event to every subscriber still listening
+    pub fn emit(&mut self, event: PlayerEvent) {
+        self.subscribers
+            .retain(|subscriber| subscriber.send(event.clone()).is_ok());
+    }
+
+    /// Push a song to waiting list with a new id
+    pub fn enqueue(&mut self, mut song: Song) -> SongId {
+        let id = SongId::next();
+        song.id = Some(id);
+        log!(debug, "queued {:?} as {:?}", song.name, id);
+        if let Some(song) = self.waiting_q.push(song) {
+            log!(warn, "waiting list is full, dropped {:?}", song.name);
+            self.emit(PlayerEvent::SongDropped { song });
+        }
+        self.queue_changed.notify();
+        id
+    }
+
+    /// Replace waiting list with the songs of the playlist
+    pub fn load_playlist(&mut self, playlist: Playlist) {
+        log!(debug, "loaded playlist {:?}", playlist.name);
+        self.waiting_q.clear();
+        for song in playlist.ordered() {
+            self.enqueue(song);
+        }
+        self.playlist = Some(playlist);
+    }
+
+    /// Pop the next song, the loaded playlist is queued
+    /// again first if it's repeated and all played
+    pub fn next_song(&mut self) -> Option<Song> {
+        if self.waiting_q.is_empty() {
+            if let Some(playlist) = self.playlist.take().filter(|p| p.repeat) {
+                self.load_playlist(playlist);
+            }
+        }
+        self.waiting_q.pop()
+    }
+
+    /// Count a play of the song of which `listened`
+    /// is heard, and whether it's skipped
+    pub fn record_play(&mut self, song: &Song, listened: Duration, skipped: bool) {
+        let stats = self.stats.entry(stats_key(song).into_owned()).or_default();
+        stats.plays += 1;
+        stats.skips += skipped as u32;
+        stats.listen_time += listened;
+        stats.last_played = Some(SystemTime::now());
+    }
+
+    /// Counters of the player with its current gauges
+    pub fn metrics(&self) -> PlayerMetrics {
+        PlayerMetrics {
+            waiting: self.waiting_q.len(),
+            played: self.played_q.len(),
+            playing: self.hot.read().is_playing(),
+            volume: self.volume,
+            ..self.metrics
+        }
+    }
+
+    /// Volume of the sink playing a song of the gain,
+    /// zero if muted
+    pub fn sink_volume(&self, gain: Option<f32>) -> f32 {
+        match (self.muted, self.focus) {
+            (true, _) => 0.0,
+            (false, AudioFocus::DUCK) => self.volume * gain.unwrap_or(1.0) * DUCK_VOLUME,
+            (false, _) => self.volume * gain.unwrap_or(1.0),
+        }
+    }
+
+    /// Ramp the sink to the volume of current song over `ramp`
+    fn ramp_sink(&self, ramp: Duration) {
+        let hot = self.hot.read();
+        if let Some(sink) = &hot.sink {
+            let gain = hot.current.song.as_ref().and_then(|song| song.gain);
+            let target = self.sink_volume(gain);
+            ramp_volume(Arc::clone(sink), target, ramp, &self.volume_ramp);
+        }
+    }
+
+    /// Cancel the sleep timer, bringing back the volume
+    /// its fade out may have lowered
+    pub fn cancel_sleep_timer(&mut self) {
+        if self.sleep_timer.take().is_some() {
+            self.ramp_sink(DEFAULT_RAMP);
+        }
+    }
+
+    /// Change the volume, ramping the sink to it over `ramp`;
+    /// it's kept silent if muted
+    pub fn set_volume(&mut self, volume: f32, ramp: Duration) {
+        self.volume = volume.max(0.0);
+        self.ramp_sink(ramp);
+    }
+
+    /// Change the speed of the songs, by the speed mode
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+        self.apply_speed();
+    }
+
+    /// Change how the speed of the songs is changed,
+    /// keeping the speed
+    pub fn set_speed_mode(&mut self, mode: SpeedMode) {
+        self.speed_mode = mode;
+        self.apply_speed();
+    }
+
+    /// Apply the speed to the sink, or to the time
+    /// stretch of the songs
+    pub fn apply_speed(&self) {
+        let (resample, stretch) = match self.speed_mode {
+            SpeedMode::RESAMPLE => (self.speed, 1.0),
+            #[cfg(feature = "time-stretch")]
+            SpeedMode::STRETCH => (1.0, self.speed),
+        };
+        let hot = self.hot.read();
+        if let Some(sink) = &hot.sink {
+            sink.set_speed(resample);
+        }
+        hot.stretch.set_factor(stretch);
+    }
+
+    /// Pace of the output relative to real time, which only
+    /// differs from 1 on a null output played faster
+    pub fn output_pace(&self) -> f64 {
+        #[cfg(feature = "test-backend")]
+        if let Some(output) = self.null_output {
+            return output.speed.max(0.0) as f64;
+        }
+        1.0
+    }
+
+    /// Silence the sink or restore the volume
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+        self.ramp_sink(DEFAULT_RAMP);
+    }
+
+    /// Pause, duck or resume by the audio focus
+    pub fn set_focus(&mut self, focus: AudioFocus) {
+        let paused_by_focus = std::mem::take(&mut self.paused_by_focus);
+        match focus {
+            AudioFocus::GAINED if paused_by_focus => self.resume(),
+            AudioFocus::GAINED => {}
+            AudioFocus::LOST => self.pause(),
+            AudioFocus::TRANSIENT => {
+                self.paused_by_focus = paused_by_focus || self.is_playing();
+                self.pause();
+            }
+            AudioFocus::DUCK => self.paused_by_focus = paused_by_focus,
+        }
+        self.focus = focus;
+        self.ramp_sink(DEFAULT_RAMP);
+        self.emit(PlayerEvent::AudioFocusChanged { focus });
+    }
+
+    /// Bookmark where current song is, if positions
+    /// are remembered
+    pub fn bookmark_current(&mut self) {
+        if !self.remember_position {
+            return;
+        }
+        let hot = self.hot.read();
+        if let (Some(sink), Some(song)) = (hot.active_sink(), &hot.current.song) {
+            self.bookmarks
+                .insert(BookmarkKey::of(song), hot.sink_pos(sink));
+        }
+    }
+
+    /// Take the bookmark of the song, if positions
+    /// are remembered
+    pub fn take_bookmark(&mut self, song: &Song) -> Option<Duration> {
+        if !self.remember_position {
+            return None;
+        }
+        self.bookmarks.remove(&BookmarkKey::of(song))
+    }
+
+    /// Check whether the current song is playing
+    pub fn is_playing(&self) -> bool {
+        self.hot.read().is_playing()
+    }
+
+    /// Check whether there's a song being played,
+    /// either playing or paused
+    pub fn is_active(&self) -> bool {
+        self.hot.read().is_active()
+    }
+
+    /// Derive the playback state from both current
+    /// song and the sink
+    pub fn playback_state(&self) -> PlaybackState {
+        self.hot.read().playback_state()
+    }
+
+    /// Pause the playing song
+    pub fn pause(&self) {
+        self.hot.write().pause();
+    }
+
+    /// Resume the paused song
+    pub fn resume(&self) {
+        self.hot.write().resume();
+    }
+
+    /// Seek current song to the start of its chapter,
+    /// nothing happens if there's no such chapter
+    pub fn goto_chapter(&self, index: usize) -> Option<Settle> {
+        // seeked without holding the lock
+        let (sink, start) = {
+            let hot = self.hot.read();
+            let song = hot.current.song.as_ref()?;
+            let start = song.chapters.get(index)?.start;
+            (Arc::clone(hot.active_sink()?), start)
+        };
+        seek_sink(&sink, start).ok()
+    }
+
+    /// Index of the chapter being played, its start and the
+    /// position in current song, `None` if nothing is played
+    fn current_chapter(&self) -> Option<(Option<(usize, Duration)>, Duration)> {
+        let hot = self.hot.read();
+        let (sink, song) = (hot.active_sink()?, hot.current.song.as_ref()?);
+        let pos = hot.sink_pos(sink);
+        let chapter = song
+            .chapter_at(pos)
+            .map(|index| (index, song.chapters[index].start));
+        Some((chapter, pos))
+    }
+
+    /// Seek to the start of the next chapter
+    pub fn next_chapter(&self) -> Option<Settle> {
+        let (chapter, _) = self.current_chapter()?;
+        self.goto_chapter(chapter.map_or(0, |(index, _)| index + 1))
+    }
+
+    /// Seek to the start of the chapter being played, or
+    /// the previous one if it's just started
+    pub fn prev_chapter(&self) -> Option<Settle> {
+        let (Some((index, start)), pos) = self.current_chapter()? else {
+            return None;
+        };
+        let played = pos.saturating_sub(start);
+        match played < CHAPTER_RESTART {
+            true => self.goto_chapter(index.saturating_sub(1)),
+            false => self.goto_chapter(index),
+        }
+    }
+
+    /// Go back to the last played song, queuing current song
+    /// after it, or restart current song if it's played
+    /// for a while or there's nothing played before
+    pub fn previous(&mut self) -> Option<Settle> {
+        let (sink, pos, current) = {
+            let hot = self.hot.read();
+            let sink = hot.active_sink().cloned();
+            let pos = sink.as_ref().map(|sink| hot.sink_pos(sink));
+            (sink, pos, hot.current.song.clone())
+        };
+        // a song played only once is never gone back to
+        let last_replayable = self
+            .played_q
+            .iter()
+            .next_back()
+            .is_some_and(Song::replayable);
+        if pos.is_some_and(|pos| pos >= PREVIOUS_RESTART) || !last_replayable {
+            return sink.and_then(|sink| seek_sink(&sink, Duration::ZERO).ok());
+        }
+        let last = self.played_q.take_back(1).pop()?;
+        log!(debug, "back to {:?}", last.name);
+        if let (Some(sink), Some(song)) = (sink, current) {
+            if song.replayable() {
+                self.waiting_q.push_front(song);
+                self.going_back = true;
+            }
+            self.skipping = true;
+            sink.skip_one();
+        }
+        self.waiting_q.push_front(last);
+        self.queue_changed.notify();
+        None
+    }
+
+    /// Move the song at the index of waiting list to its front,
+    /// skipping current song to play it; returns `false` if
+    /// there's no such song
+    pub fn jump_to(&mut self, index: usize) -> bool {
+        if index >= self.waiting_q.len() {
+            return false;
+        }
+        log!(debug, "jump to song {} of waiting list", index);
+        match self.skipped_songs {
+            SkippedSongs::HISTORY => {
+                for song in self.waiting_q.take_front(index) {
+                    self.played_q.push(song);
+                }
+            }
+            SkippedSongs::KEEP => {
+                let id = self.waiting_q.iter().nth(index).and_then(|song| song.id);
+                if let Some(id) = id {
+                    self.waiting_q.move_to(id, 0);
+                }
+            }
+        }
+        self.bookmark_current();
+        if let Some(sink) = self.hot.read().active_sink() {
+            sink.skip_one();
+            self.skipping = true;
+        }
+        true
+    }
+
+    /// Copy what's playing without touching the queues
+    pub fn status(&self) -> PlayerStatus {
+        let hot = self.hot.read();
+        PlayerStatus {
+            state: hot.playback_state(),
+            mode: self.mode,
+            volume: self.volume,
+            muted: self.muted,
+            song: hot.current.song.clone(),
+            position: hot.position(),
+            duration: hot.current.duration,
+            waiting: self.waiting_q.len(),
+            played: self.played_q.len(),
+        }
+    }
+
+    /// Get current status with the first `preview` songs
+    /// of waiting list and the output device
+    pub fn ui_state(&self, preview: usize) -> UiState {
+        UiState {
+            status: self.status(),
+            queue: self.waiting_q.range(0, preview),
+            device: self.device.clone(),
+        }
+    }
+
+    /// Take a snapshot of current queues and settings
+    pub fn snapshot(&self) -> PlayerSnapshot {
+        PlayerSnapshot {
+            waiting: self.waiting_list(),
+            current: self.hot.read().current_song(),
+            played: self.played_list(),
+            volume: self.volume,
+            mode: self.mode,
+        }
+    }
+
+    /// Overwrite queues and settings with the given snapshot
+    ///
+    /// A song interrupted while playing is queued in the
+    /// front and resumed from its saved position; the play
+    /// loop is to be stopped before, see `halting`
+    pub fn restore(&mut self, snapshot: PlayerSnapshot) {
+        self.waiting_q.clear();
+        self.resume_at = None;
+        let current = snapshot.current;
+        self.playlist = None;
+        // ids of another process may collide with ours
+        let renew = |mut song: Song| {
+            song.id = Some(SongId::next());
+            song
+        };
+        if let Some(song) = current.song {
+            if matches!(current.state, SongState::PLAY | SongState::PAUSE) {
+                self.enqueue(song);
+                self.resume_at = Some(current.progress);
+            }
+        }
+        for song in snapshot.waiting {
+            self.enqueue(song);
+        }
+        self.played_q.clear();
+        for song in snapshot.played {
+            self.played_q.push(renew(song));
+        }
+        self.hot.write().current = Default::default();
+        self.volume = snapshot.volume;
+        self.mode = snapshot.mode;
+    }
+}
+
+/// Seek the sink, whose position follows once the sink reads
+/// it in a few milliseconds, see `Settle`
+pub(crate) fn seek_sink(sink: &Arc<Sink>, pos: Duration) -> Result<Settle, SeekError> {
+    sink.try_seek(pos)?;
+    Ok(Settle {
+        sink: Arc::clone(sink),
+        pos,
+        // read once the seek is done, the sink keeps the
+        // position before it until the next read
+        stale: sink.get_pos(),
+    })
+}
+
+/// Seek of a sink whose position is yet to follow, waited
+/// for without holding the lock of the state
+#[must_use]
+pub(crate) struct Settle {
+    sink: Arc<Sink>,
+    pos: Duration,
+    stale: Duration,
+}
+
+impl Settle {
+    /// Wait for the position of the sink to follow the seek
+    pub fn wait(self) {
+        let start = Instant::now();
+        while self.stale != self.pos && !self.sink.empty() && self.sink.get_pos() == self.stale {
+            if start.elapsed() >= SEEK_SETTLE {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
         }
     }
 }
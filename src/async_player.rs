@@ -0,0 +1,138 @@
+use std::future::IntoFuture;
+
+use rodio::{OutputStream, OutputStreamHandle};
+
+use crate::{
+    asset::{PlaybackState, PlayerSnapshot},
+    player::Player,
+    reply::ReplyFuture,
+    shared_player::SharedPlayer,
+    song::{ActiveSong, Song, SongId},
+};
+
+/// Future resolved once a player operation is done
+///
+/// It doesn't depend on any runtime, so it can be awaited
+/// in tokio, async-std or a plain `block_on`
+pub type PlayerFuture<T> = ReplyFuture<T>;
+
+/// Async counterpart of [`Player`], every method
+/// returns a future to be awaited
+pub trait AsyncPlayer {
+    /// Add a song to the player
+    fn add(&self, song: Song) -> PlayerFuture<SongId>;
+    /// Get current waiting list
+    fn waiting_list(&self) -> PlayerFuture<Vec<Song>>;
+    /// Get current played history
+    fn played_list(&self) -> PlayerFuture<Vec<Song>>;
+    /// Get current active song
+    fn current_song(&self) -> PlayerFuture<ActiveSong>;
+    /// Play the song in waiting list, resolved when playing is over
+    fn play(&self) -> PlayerFuture<()>;
+    /// Use normal play mode: playing a single song and stop
+    fn use_normal_play(&self) -> PlayerFuture<()>;
+    /// Use auto play mode: playing all the songs one-by-one in the playlist
+    fn use_auto_play(&self) -> PlayerFuture<()>;
+    /// Toggle play/pause
+    fn toggle(&self) -> PlayerFuture<()>;
+    /// Pause the playing song
+    fn pause(&self) -> PlayerFuture<()>;
+    /// Resume the paused song
+    fn resume(&self) -> PlayerFuture<()>;
+    /// Stop current music
+    fn stop(&self) -> PlayerFuture<()>;
+    /// Skip current song and play the next one in waiting list
+    fn skip(&self) -> PlayerFuture<()>;
+    /// Clear all songs in waiting/played list
+    fn clear(&self) -> PlayerFuture<()>;
+    /// Check whether the current song is playing
+    fn is_playing(&self) -> PlayerFuture<bool>;
+    /// Get whether the player is playing, paused, stopped or idle
+    fn playback_state(&self) -> PlayerFuture<PlaybackState>;
+    /// Set output device generator
+    fn set_device_maker(
+        &self,
+        with_generator: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+    ) -> PlayerFuture<()>;
+    /// Take a snapshot of queues, current song and settings
+    fn save_state(&self) -> PlayerFuture<PlayerSnapshot>;
+    /// Replace queues, current song and settings with a snapshot
+    fn load_state(&self, snapshot: PlayerSnapshot) -> PlayerFuture<()>;
+}
+
+impl AsyncPlayer for SharedPlayer {
+    fn add(&self, song: Song) -> PlayerFuture<SongId> {
+        Player::add(self, song).into_future()
+    }
+
+    fn waiting_list(&self) -> PlayerFuture<Vec<Song>> {
+        Player::waiting_list(self).into_future()
+    }
+
+    fn played_list(&self) -> PlayerFuture<Vec<Song>> {
+        Player::played_list(self).into_future()
+    }
+
+    fn current_song(&self) -> PlayerFuture<ActiveSong> {
+        Player::current_song(self).into_future()
+    }
+
+    fn play(&self) -> PlayerFuture<()> {
+        Player::play(self).into_future()
+    }
+
+    fn use_normal_play(&self) -> PlayerFuture<()> {
+        Player::use_normal_play(self).into_future()
+    }
+
+    fn use_auto_play(&self) -> PlayerFuture<()> {
+        Player::use_auto_play(self).into_future()
+    }
+
+    fn toggle(&self) -> PlayerFuture<()> {
+        Player::toggle(self).into_future()
+    }
+
+    fn pause(&self) -> PlayerFuture<()> {
+        Player::pause(self).into_future()
+    }
+
+    fn resume(&self) -> PlayerFuture<()> {
+        Player::resume(self).into_future()
+    }
+
+    fn stop(&self) -> PlayerFuture<()> {
+        Player::stop(self).into_future()
+    }
+
+    fn skip(&self) -> PlayerFuture<()> {
+        Player::skip(self).into_future()
+    }
+
+    fn clear(&self) -> PlayerFuture<()> {
+        Player::clear(self).into_future()
+    }
+
+    fn is_playing(&self) -> PlayerFuture<bool> {
+        Player::is_playing(self).into_future()
+    }
+
+    fn playback_state(&self) -> PlayerFuture<PlaybackState> {
+        Player::playback_state(self).into_future()
+    }
+
+    fn set_device_maker(
+        &self,
+        with_generator: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+    ) -> PlayerFuture<()> {
+        Player::set_device_maker(self, with_generator).into_future()
+    }
+
+    fn save_state(&self) -> PlayerFuture<PlayerSnapshot> {
+        Player::save_state(self).into_future()
+    }
+
+    fn load_state(&self, snapshot: PlayerSnapshot) -> PlayerFuture<()> {
+        Player::load_state(self, snapshot).into_future()
+    }
+}
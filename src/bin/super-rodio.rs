@@ -0,0 +1,98 @@
+use std::{
+    env, fs,
+    io::{stdout, Write},
+    path::Path,
+    process::exit,
+    time::Duration,
+};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    terminal,
+};
+use super_rodio::{Make, Player, SharedPlayer, Song};
+
+const SEEK_STEP: f32 = 5.0;
+
+/// Make a song from an audio file, named after its file stem
+fn song_from_path(path: &Path) -> Song {
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Song::from(name, path.to_string_lossy().to_string())
+}
+
+/// Read songs from an argument, which is either an audio
+/// file or a M3U playlist with one path per line
+fn songs_from_arg(arg: &str) -> Vec<Song> {
+    let path = Path::new(arg);
+    let is_playlist = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("m3u") || ext.eq_ignore_ascii_case("m3u8"));
+    if !is_playlist {
+        return vec![song_from_path(path)];
+    }
+    let Ok(content) = fs::read_to_string(path) else {
+        eprintln!("cannot read playlist {}", arg);
+        return vec![];
+    };
+    // entries of a playlist are relative to the playlist itself
+    let base = path.parent().unwrap_or(Path::new(""));
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| song_from_path(&base.join(line)))
+        .collect()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("usage: super-rodio <file | playlist.m3u>...");
+        exit(1);
+    }
+
+    let player = SharedPlayer::make();
+    for arg in &args {
+        for song in songs_from_arg(arg) {
+            player.add(song);
+        }
+    }
+    player.use_auto_play();
+    let done = player.play();
+
+    println!("space: play/pause, n: next, left/right: seek, q: quit");
+    terminal::enable_raw_mode().expect("cannot enable raw mode");
+    while !done.is_finished() {
+        if event::poll(Duration::from_millis(250)).unwrap_or(false) {
+            if let Ok(Event::Key(key)) = event::read() {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char(' ') => {
+                        player.toggle();
+                    }
+                    KeyCode::Char('n') => {
+                        player.skip();
+                    }
+                    KeyCode::Left => {
+                        player.seek_by(-SEEK_STEP);
+                    }
+                    KeyCode::Right => {
+                        player.seek_by(SEEK_STEP);
+                    }
+                    KeyCode::Char('q') | KeyCode::Esc => break,
+                    _ => {}
+                }
+            }
+        }
+        print!("\r\x1b[2K{}", player.snapshot());
+        let _ = stdout().flush();
+    }
+    terminal::disable_raw_mode().expect("cannot disable raw mode");
+    println!();
+    player.stop();
+}
@@ -0,0 +1,239 @@
+use std::{thread::spawn, time::Duration};
+
+use rodio::{OutputStream, OutputStreamHandle};
+
+use crate::{
+    asset::PlaybackMode,
+    config::{
+        Buffering, DecoderBackend, LatencyProfile, PlayerConfig, RetryPolicy, SkippedSongs,
+        SpeedMode,
+    },
+    device::StreamConfig,
+    event::PlayerEvent,
+    limiter::Limiter,
+    make::MakeWith,
+    player::Player,
+    shared_player::SharedPlayer,
+};
+
+type DeviceMaker = Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>;
+
+type EventCallback = Box<dyn Fn(&SharedPlayer, PlayerEvent) + Send>;
+
+/// Builder of [`SharedPlayer`], made by `SharedPlayer::builder`
+///
+/// ```
+/// use std::time::Duration;
+/// use super_rodio::{PlaybackMode, SharedPlayer};
+///
+/// let player = SharedPlayer::builder()
+///     .volume(0.8)
+///     .mode(PlaybackMode::AUTO)
+///     .fade_in(Duration::from_millis(200))
+///     .on_event(|event| println!("{:?}", event))
+///     .build();
+/// ```
+#[derive(Default)]
+pub struct PlayerBuilder {
+    config: PlayerConfig,
+    device_maker: Option<DeviceMaker>,
+    stream: Option<StreamConfig>,
+    callbacks: Vec<EventCallback>,
+}
+
+impl PlayerBuilder {
+    /// Start from the given configuration
+    pub fn with_config(config: PlayerConfig) -> PlayerBuilder {
+        PlayerBuilder {
+            config,
+            ..Default::default()
+        }
+    }
+
+    /// Initial volume, 1.0 for the original loudness
+    pub fn volume(mut self, volume: f32) -> Self {
+        self.config.volume = volume;
+        self
+    }
+
+    /// Initial playback mode
+    pub fn mode(mut self, mode: PlaybackMode) -> Self {
+        self.config.mode = mode;
+        self
+    }
+
+    /// Max number of songs in waiting queue
+    pub fn waiting_capacity(mut self, capacity: usize) -> Self {
+        self.config.waiting_capacity = capacity;
+        self.config.unbounded_waiting = false;
+        self
+    }
+
+    /// Never drop songs from waiting queue
+    pub fn unbounded_waiting(mut self) -> Self {
+        self.config.unbounded_waiting = true;
+        self
+    }
+
+    /// Max number of songs in played history
+    pub fn played_capacity(mut self, capacity: usize) -> Self {
+        self.config.played_capacity = capacity;
+        self
+    }
+
+    /// Time each song fades in from silence
+    pub fn fade_in(mut self, fade_in: Duration) -> Self {
+        self.config.fade_in = fade_in;
+        self
+    }
+
+    /// Time each song fades out to silence before its
+    /// end, songs of unknown length don't fade out
+    pub fn fade_out(mut self, fade_out: Duration) -> Self {
+        self.config.fade_out = fade_out;
+        self
+    }
+
+    /// Silence between songs played one after another
+    pub fn track_gap(mut self, gap: Duration) -> Self {
+        self.config.track_gap = gap;
+        self
+    }
+
+    /// Soft limiter keeping the output from clipping
+    pub fn limiter(mut self, limiter: Limiter) -> Self {
+        self.config.limiter = limiter;
+        self
+    }
+
+    /// How to retry a song failed to be opened
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.config.retry = retry;
+        self
+    }
+
+    /// How songs are read and decoded
+    pub fn buffering(mut self, buffering: Buffering) -> Self {
+        self.config.buffering = buffering;
+        self
+    }
+
+    /// Most bytes of decoded samples cached for short sounds
+    pub fn cache_budget(mut self, budget: usize) -> Self {
+        self.config.cache_budget = budget;
+        self
+    }
+
+    /// Where the songs skipped over by `play_song` and
+    /// `play_index` go, played history by default
+    pub fn skipped_songs(mut self, skipped: SkippedSongs) -> Self {
+        self.config.skipped_songs = skipped;
+        self
+    }
+
+    /// Decoder of songs which don't choose one
+    pub fn decoder(mut self, decoder: DecoderBackend) -> Self {
+        self.config.decoder = decoder;
+        self
+    }
+
+    /// Interval of `PlayerEvent::Progress` while a
+    /// song plays, zero to never send it
+    pub fn progress_interval(mut self, interval: Duration) -> Self {
+        self.config.progress_interval = interval;
+        self
+    }
+
+    /// How the speed of the songs is changed
+    pub fn speed_mode(mut self, mode: SpeedMode) -> Self {
+        self.config.speed_mode = mode;
+        self
+    }
+
+    /// Longest time the `try_*` getters of the player
+    /// wait for it before giving up
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.config.lock_timeout = timeout;
+        self
+    }
+
+    /// Set buffering, cache budget and the buffer size
+    /// of the output stream by the latency profile
+    pub fn latency(mut self, profile: LatencyProfile) -> Self {
+        profile.apply(&mut self.config);
+        let buffer_size = profile.buffer_size();
+        if buffer_size.is_some() || self.stream.is_some() {
+            self.stream.get_or_insert_with(Default::default).buffer_size = buffer_size;
+        }
+        self
+    }
+
+    /// Output device generator, instead of `OutputStream::try_default`,
+    /// taking over the stream config and latency profile
+    pub fn device_maker(
+        mut self,
+        maker: impl Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync + 'static,
+    ) -> Self {
+        self.device_maker = Some(Box::new(maker));
+        self
+    }
+
+    /// Format of the output stream on the default device,
+    /// instead of the default format of the device; the
+    /// buffer size of the latency profile is kept if the
+    /// config doesn't give one
+    pub fn stream_config(mut self, mut config: StreamConfig) -> Self {
+        let profile = self.stream.and_then(|stream| stream.buffer_size);
+        config.buffer_size = config.buffer_size.or(profile);
+        self.stream = Some(config);
+        self
+    }
+
+    /// Call `callback` with every event of the player,
+    /// on a thread of its own
+    ///
+    /// A callback capturing a handle of the player keeps it
+    /// from ever shutting down, use `on_event_with` instead
+    pub fn on_event(mut self, callback: impl Fn(PlayerEvent) + Send + 'static) -> Self {
+        self.callbacks
+            .push(Box::new(move |_: &SharedPlayer, event| callback(event)));
+        self
+    }
+
+    /// Call `callback` with the player and every event of
+    /// it, on a thread of its own; the player is lent to
+    /// the callback, so that it still shuts down once
+    /// every other handle is dropped
+    pub fn on_event_with(
+        mut self,
+        callback: impl Fn(&SharedPlayer, PlayerEvent) + Send + 'static,
+    ) -> Self {
+        self.callbacks.push(Box::new(callback));
+        self
+    }
+
+    /// Make the player
+    pub fn build(self) -> SharedPlayer {
+        let player = SharedPlayer::make_with(self.config);
+        if let Some(maker) = self.device_maker {
+            player.set_device_maker(maker);
+        } else if let Some(config) = self.stream {
+            player.set_stream_config(config);
+        }
+        for callback in self.callbacks {
+            if let Ok(events) = player.subscribe().join() {
+                let weak = player.downgrade();
+                // ends once the player is shut down
+                spawn(move || {
+                    for event in events {
+                        match weak.upgrade() {
+                            Some(player) => callback(&player, event),
+                            None => break,
+                        }
+                    }
+                });
+            }
+        }
+        player
+    }
+}
@@ -0,0 +1,472 @@
+use std::{
+    io,
+    panic::{catch_unwind, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        mpsc::{channel, Sender},
+        Arc, Mutex, TryLockError, Weak,
+    },
+    thread::{spawn, JoinHandle},
+    time::Duration,
+};
+
+use rodio::{
+    dynamic_mixer::{self, DynamicMixer, DynamicMixerController},
+    source::SeekError,
+    Device, OutputStream, OutputStreamHandle, Sink, Source,
+};
+
+use crate::{
+    analyzer::SharedWindow,
+    device::StreamConfig,
+    limiter::{Limiter, SoftLimiter},
+    logging::log,
+    poison::Recover,
+};
+
+#[cfg(feature = "test-backend")]
+use crate::null_output::{NullOutput, NullStream};
+
+/// Channels of the mix, converted to those of the device
+pub(crate) const CHANNELS: u16 = 2;
+
+/// Sample rate of the mix, converted to that of the device
+pub(crate) const SAMPLE_RATE: u32 = 44100;
+
+/// Samples mixed at a time for the output
+const BATCH: usize = 512;
+
+/// Samples of an input between checks of its sink
+const CHECK_INTERVAL: usize = 1024;
+
+/// Maker of the output device of the player
+pub(crate) type GenOut = Arc<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>;
+
+/// How the output device of the player is opened
+#[derive(Clone)]
+pub(crate) enum OutputMaker {
+    /// Stream of rodio made by the device maker
+    Maker(GenOut),
+    /// Stream of the default device in the format
+    Config(StreamConfig),
+    /// Stream of the device selected by name
+    Device(Device),
+}
+
+/// What the final mix goes through and is tapped for,
+/// read by the audio thread without ever waiting
+#[derive(Default)]
+pub(crate) struct Taps {
+    /// Soft limiter of the mix, before it's tapped
+    pub limiter: Mutex<Limiter>,
+    /// Most recent audio played, for analysis
+    pub window: SharedWindow,
+    /// Samples to the thread writing the recording, if any
+    #[cfg(feature = "recording")]
+    pub recorder: Mutex<Option<Sender<Vec<f32>>>>,
+}
+
+impl Taps {
+    /// Pass a batch of the mix through the taps, `playing`
+    /// tells whether any sound is heard in it
+    fn process(&self, batch: &mut [f32], playing: bool) {
+        // a batch is dropped rather than waiting for a reader
+        if let Ok(mut window) = self.window.try_lock() {
+            match playing {
+                true => window.push(batch, CHANNELS),
+                false => window.clear(),
+            }
+        }
+        #[cfg(feature = "recording")]
+        if playing {
+            // a recording just starting or stopping misses a batch
+            if let Ok(recorder) = self.recorder.try_lock() {
+                if let Some(recorder) = recorder.as_ref() {
+                    let _ = recorder.send(batch.to_vec());
+                }
+            }
+        }
+    }
+}
+
+/// Mix of all sounds of the player, pulled by one port at a time
+struct Mix {
+    mixer: DynamicMixer<f32>,
+    playing: Arc<AtomicUsize>,
+    taps: Arc<Taps>,
+    limiter: SoftLimiter,
+    // generation of the port pulling the mix
+    port: u64,
+}
+
+impl Mix {
+    fn fill(&mut self, batch: &mut [f32]) {
+        for sample in batch.iter_mut() {
+            *sample = self.mixer.next().unwrap_or(0.0);
+        }
+        // a change of the limiter waits for the next batch
+        if let Ok(limiter) = self.taps.limiter.try_lock() {
+            self.limiter.limiter = *limiter;
+        }
+        self.limiter.process(batch);
+        let playing = self.playing.load(Ordering::Relaxed) > 0;
+        self.taps.process(batch, playing);
+    }
+}
+
+/// Source adapter of a sound added to the mix, counted as
+/// playing while its sink has sounds and isn't paused, or
+/// while it lasts if it's added without a sink
+struct Input<S> {
+    input: S,
+    sink: Option<Weak<Sink>>,
+    playing: Arc<AtomicUsize>,
+    active: bool,
+    until_check: usize,
+}
+
+impl<S> Input<S> {
+    fn set_active(&mut self, active: bool) {
+        if active != self.active {
+            self.active = active;
+            match active {
+                true => self.playing.fetch_add(1, Ordering::Relaxed),
+                false => self.playing.fetch_sub(1, Ordering::Relaxed),
+            };
+        }
+    }
+}
+
+impl<S> Iterator for Input<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.until_check == 0 {
+            self.until_check = CHECK_INTERVAL;
+            let active = match self.sink.as_ref().map(Weak::upgrade) {
+                Some(Some(sink)) => !sink.empty() && !sink.is_paused(),
+                // the sink is dropped, so is the sound
+                Some(None) => return None,
+                None => true,
+            };
+            self.set_active(active);
+        }
+        self.until_check -= 1;
+        self.input.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Input<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+impl<S> Drop for Input<S> {
+    fn drop(&mut self) {
+        self.set_active(false);
+    }
+}
+
+/// Adder of sounds to the mix
+#[derive(Clone)]
+pub(crate) struct Inputs {
+    controller: Arc<DynamicMixerController<f32>>,
+    playing: Arc<AtomicUsize>,
+}
+
+impl Inputs {
+    fn add<S>(&self, source: S, sink: Option<Weak<Sink>>)
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        self.controller.add(Input {
+            input: source,
+            sink,
+            playing: Arc::clone(&self.playing),
+            active: false,
+            until_check: 0,
+        });
+    }
+
+    /// Make a sink playing into the mix until it's dropped
+    pub fn sink(&self) -> Arc<Sink> {
+        let (sink, output) = Sink::new_idle();
+        let sink = Arc::new(sink);
+        self.add(output, Some(Arc::downgrade(&sink)));
+        sink
+    }
+
+    /// Play the source right away, without any sink
+    pub fn play<S>(&self, source: S)
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        self.add(source, None);
+    }
+}
+
+/// Source of an output pulling the mix, which ends
+/// once a newer port takes over the mix
+pub(crate) struct Port {
+    mix: Arc<Mutex<Mix>>,
+    generation: u64,
+    batch: Vec<f32>,
+    next: usize,
+}
+
+impl Iterator for Port {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.next == self.batch.len() {
+            let mix = match self.mix.try_lock() {
+                Ok(mix) => Some(mix),
+                Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+                // pulled by a former port for a moment
+                Err(TryLockError::WouldBlock) => None,
+            };
+            match mix {
+                Some(mix) if mix.port > self.generation => return None,
+                Some(mut mix) => {
+                    mix.port = self.generation;
+                    mix.fill(&mut self.batch);
+                }
+                None => self.batch.fill(0.0),
+            }
+            self.next = 0;
+        }
+        self.next += 1;
+        Some(self.batch[self.next - 1])
+    }
+}
+
+impl Source for Port {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+/// Stream kept alive by an output, never read
+#[allow(dead_code)]
+enum Stream {
+    Device(Keeper),
+    #[cfg(feature = "test-backend")]
+    Null(NullStream),
+}
+
+/// Helper thread keeping the stream of a device,
+/// which can't be sent across threads, until dropped
+struct Keeper {
+    dropped: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Keeper {
+    fn drop(&mut self) {
+        // release the stream and wait for it
+        self.dropped = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Stream of a device held by its keeper thread
+#[allow(dead_code)]
+enum Held {
+    Rodio(OutputStream),
+    Cpal(rodio::cpal::Stream),
+}
+
+/// Play the port on a stream from the device maker, or
+/// the default device if the device maker panics
+fn play_made(gen_out: GenOut, port: Port) -> io::Result<Held> {
+    let (stream, handle) = match catch_unwind(AssertUnwindSafe(|| gen_out())) {
+        Ok(output) => output,
+        Err(_) => {
+            log!(
+                warn,
+                "the device maker panicked, opening the default device"
+            );
+            OutputStream::try_default().map_err(io::Error::other)?
+        }
+    };
+    handle.play_raw(port).map_err(io::Error::other)?;
+    Ok(Held::Rodio(stream))
+}
+
+/// Play the port on a stream of the device, which fails if
+/// the device is gone or busy
+fn play_device(device: &Device, port: Port) -> io::Result<Held> {
+    let (stream, handle) = OutputStream::try_from_device(device).map_err(io::Error::other)?;
+    handle.play_raw(port).map_err(io::Error::other)?;
+    Ok(Held::Rodio(stream))
+}
+
+/// Device or null output playing the mix through its port
+pub(crate) struct Output {
+    generation: u64,
+    _stream: Stream,
+}
+
+impl Output {
+    /// Open a stream of the device and play the port on
+    /// it, the default device is used if the device maker
+    /// panics
+    pub fn device(maker: OutputMaker, port: Port) -> io::Result<Output> {
+        let generation = port.generation;
+        let (opened_tx, opened) = channel();
+        let (dropped, dropped_rx) = channel::<()>();
+        let thread = spawn(move || {
+            let stream = match maker {
+                OutputMaker::Maker(gen_out) => play_made(gen_out, port),
+                OutputMaker::Config(config) => config.play(port).map(Held::Cpal),
+                OutputMaker::Device(device) => play_device(&device, port),
+            };
+            let _stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = opened_tx.send(Err(e));
+                    return;
+                }
+            };
+            let _ = opened_tx.send(Ok(()));
+            // wait until the output is dropped
+            let _ = dropped_rx.recv();
+        });
+        let keeper = Keeper {
+            dropped: Some(dropped),
+            thread: Some(thread),
+        };
+        opened
+            .recv()
+            .unwrap_or_else(|_| Err(io::Error::other("failed to open the output")))?;
+        Ok(Output {
+            generation,
+            _stream: Stream::Device(keeper),
+        })
+    }
+
+    /// Play the port on the null output
+    #[cfg(feature = "test-backend")]
+    pub fn null(output: NullOutput, port: Port) -> Output {
+        Output {
+            generation: port.generation,
+            _stream: Stream::Null(output.open(port)),
+        }
+    }
+}
+
+/// Final mix of the player, where the songs, the channels
+/// and the other sounds meet before the output, so that
+/// the whole output is tapped in one place
+///
+/// The output is attached on demand and can be replaced,
+/// e.g. by another device, while the mix goes on
+pub(crate) struct Bus {
+    pub inputs: Inputs,
+    pub taps: Arc<Taps>,
+    mix: Arc<Mutex<Mix>>,
+    next_port: AtomicU64,
+    output: Option<Output>,
+}
+
+impl Bus {
+    pub fn new(taps: Arc<Taps>) -> Bus {
+        let (controller, mixer) = dynamic_mixer::mixer(CHANNELS, SAMPLE_RATE);
+        let playing = Arc::new(AtomicUsize::new(0));
+        Bus {
+            inputs: Inputs {
+                controller,
+                playing: Arc::clone(&playing),
+            },
+            mix: Arc::new(Mutex::new(Mix {
+                mixer,
+                playing,
+                taps: Arc::clone(&taps),
+                limiter: SoftLimiter::new(Default::default()),
+                port: 0,
+            })),
+            taps,
+            next_port: AtomicU64::new(1),
+            output: None,
+        }
+    }
+
+    /// Port of a new output, which takes over
+    /// the mix once it's pulled
+    pub fn port(&self) -> Port {
+        Port {
+            mix: Arc::clone(&self.mix),
+            generation: self.next_port.fetch_add(1, Ordering::Relaxed),
+            batch: vec![0.0; BATCH],
+            next: BATCH,
+        }
+    }
+
+    /// Check whether an output is playing the mix
+    pub fn is_attached(&self) -> bool {
+        self.output.is_some()
+    }
+
+    /// Keep the output playing the mix, the newest one is kept
+    /// if outputs are opened at the same time; the other one
+    /// is returned to be dropped without holding any lock
+    pub fn attach(&mut self, output: Output) -> Option<Output> {
+        match &self.output {
+            Some(kept) if kept.generation > output.generation => Some(output),
+            _ => self.output.replace(output),
+        }
+    }
+
+    /// Stop the output and drop every sound of the mix, which
+    /// is returned to be dropped without holding any lock
+    pub fn detach(&mut self) -> Option<Output> {
+        let output = self.output.take()?;
+        // no stale window is left behind
+        self.taps.window.lock().recover().clear();
+        let next_port = self.next_port.load(Ordering::Relaxed);
+        *self = Bus::new(Arc::clone(&self.taps));
+        self.next_port = AtomicU64::new(next_port);
+        Some(output)
+    }
+}
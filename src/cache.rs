@@ -0,0 +1,174 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+
+use rodio::{source::SeekError, Source};
+
+/// Samples of a decoded file
+struct Entry {
+    path: String,
+    // the entry is stale once the file is modified
+    modified: Option<SystemTime>,
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl Entry {
+    fn bytes(&self) -> usize {
+        self.samples.len() * size_of::<f32>()
+    }
+}
+
+/// Least recently used cache of decoded files, so that
+/// short sounds played again and again aren't decoded
+/// each time
+pub struct SampleCache {
+    // the most recently used one is at the back
+    entries: VecDeque<Entry>,
+    budget: usize,
+    used: usize,
+}
+
+/// Cache shared by the player and its channels
+pub type SharedCache = Arc<Mutex<SampleCache>>;
+
+impl SampleCache {
+    pub fn new(budget: usize) -> SampleCache {
+        SampleCache {
+            entries: VecDeque::new(),
+            budget,
+            used: 0,
+        }
+    }
+
+    /// Change the budget, dropping the least
+    /// recently used files to fit in it
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.evict(0);
+    }
+
+    /// Whether a file of the given length and format is
+    /// worth caching; one file may take a quarter of the
+    /// budget at most, so that a long song can't flush
+    /// all the short sounds
+    pub fn fits(&self, length: Duration, channels: u16, sample_rate: u32) -> bool {
+        let samples = length.as_secs_f64() * channels as f64 * sample_rate as f64;
+        samples * (size_of::<f32>() as f64) <= self.budget as f64 / 4.0
+    }
+
+    /// Get the file if it's cached and not modified since
+    pub fn get(&mut self, path: &str, modified: Option<SystemTime>) -> Option<CachedSource> {
+        let index = self.entries.iter().position(|entry| entry.path == path)?;
+        let entry = self.entries.remove(index)?;
+        if entry.modified != modified {
+            self.used -= entry.bytes();
+            return None;
+        }
+        let source = CachedSource::new(&entry);
+        self.entries.push_back(entry);
+        Some(source)
+    }
+
+    /// Cache the samples of the file, returns a source of them
+    pub fn insert(
+        &mut self,
+        path: &str,
+        modified: Option<SystemTime>,
+        samples: Vec<f32>,
+        channels: u16,
+        sample_rate: u32,
+    ) -> CachedSource {
+        let entry = Entry {
+            path: path.to_string(),
+            modified,
+            samples: samples.into(),
+            channels,
+            sample_rate,
+        };
+        let source = CachedSource::new(&entry);
+        self.entries.retain(|cached| cached.path != path);
+        self.used = self.entries.iter().map(Entry::bytes).sum();
+        self.evict(entry.bytes());
+        if entry.bytes() <= self.budget {
+            self.used += entry.bytes();
+            self.entries.push_back(entry);
+        }
+        source
+    }
+
+    /// Drop the least recently used files until
+    /// `extra` bytes more fit in the budget
+    fn evict(&mut self, extra: usize) {
+        while self.used + extra > self.budget {
+            let Some(entry) = self.entries.pop_front() else {
+                return;
+            };
+            self.used -= entry.bytes();
+        }
+    }
+}
+
+/// Source of cached samples, sharing them with the cache
+pub struct CachedSource {
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+    pos: usize,
+}
+
+impl CachedSource {
+    fn new(entry: &Entry) -> CachedSource {
+        CachedSource {
+            samples: Arc::clone(&entry.samples),
+            channels: entry.channels,
+            sample_rate: entry.sample_rate,
+            pos: 0,
+        }
+    }
+}
+
+impl Iterator for CachedSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.pos).copied()?;
+        self.pos += 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.samples.len() - self.pos;
+        (left, Some(left))
+    }
+}
+
+impl Source for CachedSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let frames = self.samples.len() / self.channels.max(1) as usize;
+        Some(Duration::from_secs_f64(
+            frames as f64 / self.sample_rate as f64,
+        ))
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let frame = (pos.as_secs_f64() * self.sample_rate as f64) as usize;
+        self.pos = (frame * self.channels as usize).min(self.samples.len());
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Source};
+
+/// `f32` shared with the playing source
+#[derive(Debug, Default)]
+pub struct SharedF32(AtomicU32);
+
+impl SharedF32 {
+    pub fn new(value: f32) -> SharedF32 {
+        SharedF32(AtomicU32::new(value.to_bits()))
+    }
+
+    pub fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    pub fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Source adapter shifting a stereo source to the left (-1.0)
+/// or to the right (1.0), other layouts are left untouched
+pub struct Balance<S> {
+    input: S,
+    balance: Arc<SharedF32>,
+    gains: [f32; 2],
+    channel: u16,
+}
+
+impl<S> Balance<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, balance: Arc<SharedF32>) -> Balance<S> {
+        Balance {
+            input,
+            balance,
+            gains: [1.0, 1.0],
+            channel: 0,
+        }
+    }
+}
+
+impl<S> Iterator for Balance<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        if self.input.channels() != 2 {
+            return Some(sample);
+        }
+        // update gains at the beginning of each frame
+        if self.channel == 0 {
+            let balance = self.balance.get().clamp(-1.0, 1.0);
+            self.gains = [(1.0 - balance).min(1.0), (1.0 + balance).min(1.0)];
+        }
+        let sample = sample * self.gains[self.channel as usize];
+        self.channel = (self.channel + 1) % 2;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Balance<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// Source adapter mixing all channels of each frame
+/// down to mono while the flag is on, the number of
+/// channels is kept so the output device isn't changed
+pub struct Downmix<S> {
+    input: S,
+    mono: Arc<AtomicBool>,
+    frame: Vec<f32>,
+    index: usize,
+}
+
+impl<S> Downmix<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, mono: Arc<AtomicBool>) -> Downmix<S> {
+        Downmix {
+            input,
+            mono,
+            frame: vec![],
+            index: 0,
+        }
+    }
+}
+
+impl<S> Iterator for Downmix<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.index < self.frame.len() {
+            self.index += 1;
+            return Some(self.frame[self.index - 1]);
+        }
+        let channels = self.input.channels() as usize;
+        if channels < 2 || !self.mono.load(Ordering::Relaxed) {
+            return self.input.next();
+        }
+        // read a whole frame and replace it with its average
+        self.frame.clear();
+        self.frame.extend(self.input.by_ref().take(channels));
+        if self.frame.is_empty() {
+            return None;
+        }
+        let avg = self.frame.iter().sum::<f32>() / self.frame.len() as f32;
+        self.frame.fill(avg);
+        self.index = 1;
+        Some(avg)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Downmix<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.frame.clear();
+        self.index = 0;
+        Ok(())
+    }
+}
@@ -0,0 +1,223 @@
+use std::time::Duration;
+
+use crate::{asset::PlaybackMode, limiter::Limiter};
+
+/// Settings to make a player with
+#[derive(Clone, Debug)]
+pub struct PlayerConfig {
+    /// Max number of songs in waiting queue, the oldest
+    /// one is dropped when it's exceeded, see
+    /// `PlayerEvent::SongDropped`
+    pub waiting_capacity: usize,
+    /// Never drop songs from waiting queue,
+    /// `waiting_capacity` is ignored if set
+    pub unbounded_waiting: bool,
+    /// Max number of songs in played history
+    pub played_capacity: usize,
+    /// Initial volume, 1.0 for the original loudness
+    pub volume: f32,
+    /// Initial playback mode
+    pub mode: PlaybackMode,
+    /// Time each song fades in from silence
+    pub fade_in: Duration,
+    /// Time each song fades out to silence before its
+    /// end, songs of unknown length don't fade out
+    pub fade_out: Duration,
+    /// Silence between songs played one after another
+    pub track_gap: Duration,
+    /// Soft limiter keeping the output from clipping
+    pub limiter: Limiter,
+    /// How to retry a song failed to be opened
+    pub retry: RetryPolicy,
+    /// How songs are read and decoded
+    pub buffering: Buffering,
+    /// Most bytes of decoded samples cached for short
+    /// sounds played again, 0 to disable the cache
+    pub cache_budget: usize,
+    /// Where the songs skipped over by `play_song`
+    /// and `play_index` go
+    pub skipped_songs: SkippedSongs,
+    /// Decoder of songs which don't choose one
+    pub decoder: DecoderBackend,
+    /// Interval of `PlayerEvent::Progress` while a
+    /// song plays, zero to never send it
+    pub progress_interval: Duration,
+    /// How the speed of the songs is changed
+    pub speed_mode: SpeedMode,
+    /// Longest time the `try_*` getters of the player
+    /// wait for it before giving up
+    pub lock_timeout: Duration,
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        PlayerConfig {
+            waiting_capacity: 1000,
+            unbounded_waiting: false,
+            played_capacity: 1000,
+            volume: 0.5,
+            mode: PlaybackMode::NORMAL,
+            fade_in: Duration::ZERO,
+            fade_out: Duration::ZERO,
+            track_gap: Duration::ZERO,
+            limiter: Default::default(),
+            retry: Default::default(),
+            buffering: Default::default(),
+            cache_budget: 16 * 1024 * 1024,
+            skipped_songs: Default::default(),
+            decoder: Default::default(),
+            progress_interval: Duration::from_millis(250),
+            speed_mode: Default::default(),
+            lock_timeout: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Where the songs before the one jumped to in waiting
+/// list go, see `Player::play_index`
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum SkippedSongs {
+    /// Moved to played history, as if they were played
+    #[default]
+    HISTORY,
+    /// Kept in waiting list, after the song jumped to
+    KEEP,
+}
+
+/// Decoder of the files of songs
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DecoderBackend {
+    /// Decoders of rodio
+    #[default]
+    RODIO,
+    /// Symphonia, decoding more codecs, e.g. AAC and ALAC
+    /// in M4A files, and knowing the duration of more files
+    #[cfg(feature = "symphonia-decoder")]
+    SYMPHONIA,
+}
+
+/// How `Player::set_speed` changes the speed of the songs
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpeedMode {
+    /// Play the samples faster or slower, shifting
+    /// the pitch with the speed like a tape
+    #[default]
+    RESAMPLE,
+    /// Stretch the songs in time, keeping the pitch,
+    /// e.g. for podcasts played at 1.5x
+    #[cfg(feature = "time-stretch")]
+    STRETCH,
+}
+
+/// Retry policy for songs failed to be opened or decoded,
+/// e.g. ones on a network drive
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RetryPolicy {
+    /// Attempts in total before the song is marked failed
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub backoff: Duration,
+    /// Factor the delay grows by after each retry,
+    /// from 1.0 to 10.0
+    pub multiplier: f32,
+}
+
+impl Default for RetryPolicy {
+    /// No retry at all
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            backoff: Duration::from_millis(500),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Retry at most `retries` times, waiting `backoff`
+    /// before the first one and twice as long each time
+    pub fn new(retries: u32, backoff: Duration) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: retries + 1,
+            backoff,
+            ..Default::default()
+        }
+    }
+
+    /// Delay before the retry after the one waiting `delay`,
+    /// an invalid multiplier keeps it as it is
+    pub fn next_delay(&self, delay: Duration) -> Duration {
+        let multiplier = match self.multiplier.is_nan() {
+            true => 1.0,
+            false => self.multiplier.clamp(1.0, 10.0),
+        };
+        Duration::try_from_secs_f64(delay.as_secs_f64() * multiplier as f64)
+            .unwrap_or(Duration::MAX)
+    }
+}
+
+/// How songs are read from files, trading memory
+/// for robustness against slow or unstable storage
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Buffering {
+    /// Size of the buffer reading a file in bytes
+    pub read_buffer: usize,
+    /// Files up to this size in bytes are decoded entirely
+    /// into memory before played, 0 to never do it
+    pub predecode_limit: u64,
+}
+
+impl Default for Buffering {
+    /// Read in 8 KiB and never pre-decode
+    fn default() -> Self {
+        Buffering {
+            read_buffer: 8 * 1024,
+            predecode_limit: 0,
+        }
+    }
+}
+
+/// Trade-off between the delay of a sound and the
+/// memory used to play it
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum LatencyProfile {
+    /// Stream songs from their files
+    #[default]
+    BALANCED,
+
+    /// Pre-decode small files, cache more short sounds and
+    /// ask for small buffers of the output stream, for games
+    /// and instrument-like apps triggering them; use
+    /// `preload` to decode a sound before it's played
+    LOW,
+}
+
+impl LatencyProfile {
+    /// Frames of each buffer of the output stream,
+    /// `None` for the size chosen by the host
+    pub fn buffer_size(&self) -> Option<u32> {
+        match self {
+            LatencyProfile::BALANCED => None,
+            // 5.3 ms at 48 kHz
+            LatencyProfile::LOW => Some(256),
+        }
+    }
+
+    /// Apply the profile to the buffering and cache budget
+    /// of the configuration
+    pub fn apply(&self, config: &mut PlayerConfig) {
+        let default = PlayerConfig::default();
+        (config.buffering, config.cache_budget) = match self {
+            LatencyProfile::BALANCED => (default.buffering, default.cache_budget),
+            LatencyProfile::LOW => (
+                Buffering {
+                    predecode_limit: 4 * 1024 * 1024,
+                    ..default.buffering
+                },
+                64 * 1024 * 1024,
+            ),
+        };
+    }
+}
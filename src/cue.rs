@@ -0,0 +1,128 @@
+use std::{fs, io, path::Path, time::Duration};
+
+use crate::song::Song;
+
+/// Frames per second of CUE time stamps (mm:ss:ff)
+const FRAMES_PER_SEC: u64 = 75;
+
+/// A track being read from the sheet
+struct Track {
+    file: String,
+    number: u32,
+    title: Option<String>,
+    performer: Option<String>,
+    start: Option<Duration>,
+}
+
+/// Take the argument of a command, with quotes removed
+fn argument(rest: &str) -> String {
+    let rest = rest.trim();
+    match rest.strip_prefix('"') {
+        Some(quoted) => quoted.split('"').next().unwrap_or_default().to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// Take the file name of a FILE command, without the
+/// type of the file following it, e.g. WAVE or MP3
+fn file_argument(rest: &str) -> String {
+    let rest = rest.trim();
+    if rest.starts_with('"') {
+        return argument(rest);
+    }
+    match rest.rsplit_once(char::is_whitespace) {
+        Some((name, _kind)) => name.trim_end().to_string(),
+        None => rest.to_string(),
+    }
+}
+
+/// Parse a time stamp like "05:12:30"
+fn parse_time(stamp: &str) -> Option<Duration> {
+    let mut parts = stamp.trim().split(':').map(|part| part.parse::<u64>().ok());
+    let (min, sec, frame) = (parts.next()??, parts.next()??, parts.next()??);
+    Some(Duration::from_millis(
+        (min * 60 + sec) * 1000 + frame * 1000 / FRAMES_PER_SEC,
+    ))
+}
+
+/// Read songs of the CUE sheet, each track becomes a song
+/// with start/end offsets in the audio file it refers to
+///
+/// Paths of audio files are resolved relative to the sheet
+pub fn load_cue(path: &str) -> io::Result<Vec<Song>> {
+    let sheet = fs::read_to_string(path)?;
+    let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+    let (mut album, mut album_performer) = (None, None);
+    let mut file = None;
+    let mut tracks: Vec<Track> = vec![];
+    for line in sheet.lines() {
+        let line = line.trim();
+        let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match command.to_ascii_uppercase().as_str() {
+            "FILE" => {
+                let name = file_argument(rest);
+                file = Some(dir.join(name).to_string_lossy().into_owned());
+            }
+            "TRACK" => {
+                let Some(file) = file.clone() else {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "TRACK before any FILE",
+                    ));
+                };
+                let number = rest
+                    .split_whitespace()
+                    .next()
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(tracks.len() as u32 + 1);
+                tracks.push(Track {
+                    file,
+                    number,
+                    title: None,
+                    performer: None,
+                    start: None,
+                });
+            }
+            "TITLE" => match tracks.last_mut() {
+                Some(track) => track.title = Some(argument(rest)),
+                None => album = Some(argument(rest)),
+            },
+            "PERFORMER" => match tracks.last_mut() {
+                Some(track) => track.performer = Some(argument(rest)),
+                None => album_performer = Some(argument(rest)),
+            },
+            "INDEX" => {
+                let (index, stamp) = rest.trim().split_once(' ').unwrap_or(("", ""));
+                if let (Some(track), Ok(1)) = (tracks.last_mut(), index.parse::<u32>()) {
+                    track.start = parse_time(stamp);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let songs = (0..tracks.len())
+        .map(|i| {
+            let track = &tracks[i];
+            // a track ends where the next one in the same file starts
+            let end_at = tracks
+                .get(i + 1)
+                .filter(|next| next.file == track.file)
+                .and_then(|next| next.start);
+            Song {
+                name: track
+                    .title
+                    .clone()
+                    .unwrap_or_else(|| format!("Track {:02}", track.number)),
+                path: track.file.clone(),
+                artist: track.performer.clone().or(album_performer.clone()),
+                album: album.clone(),
+                start_at: track.start,
+                end_at,
+                ..Default::default()
+            }
+        })
+        .collect();
+    Ok(songs)
+}
@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Cursor, Read},
+    time::SystemTime,
+};
+
+use rodio::{buffer::SamplesBuffer, Decoder, Source};
+
+use crate::{
+    cache::SharedCache,
+    config::{Buffering, DecoderBackend},
+    poison::Recover,
+    song::Song,
+    uri::SongUri,
+};
+
+#[cfg(feature = "symphonia-decoder")]
+use std::path::Path;
+#[cfg(feature = "symphonia-decoder")]
+use symphonia::core::{
+    io::{MediaSource, MediaSourceStream},
+    probe::Hint,
+};
+
+#[cfg(feature = "symphonia-decoder")]
+use crate::symphonia_source::SymphoniaSource;
+
+/// Decoded samples of a file
+pub type DecodedSource = Box<dyn Source<Item = f32> + Send>;
+
+fn invalid_data(e: rodio::decoder::DecoderError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Decode the song from its source, or its path read
+/// as a URI; files are decoded as `decode_file` does
+/// with the decoder the song chooses or the given one
+pub(crate) fn decode_song(
+    song: &Song,
+    buffering: &Buffering,
+    decoder: DecoderBackend,
+    cache: &SharedCache,
+) -> io::Result<DecodedSource> {
+    match &song.source {
+        Some(source) => source.open_f32(),
+        None => {
+            let decoder = song.decoder.unwrap_or(decoder);
+            decode_uri(SongUri::parse(&song.path)?, buffering, decoder, cache)
+        }
+    }
+}
+
+/// Decode the audio the path of a song, resolved as a URI,
+/// points to or carries
+pub(crate) fn decode_uri(
+    uri: SongUri,
+    buffering: &Buffering,
+    decoder: DecoderBackend,
+    cache: &SharedCache,
+) -> io::Result<DecodedSource> {
+    match uri {
+        SongUri::FILE(path) => decode_file(&path, buffering, decoder, cache),
+        uri => uri.open_f32(),
+    }
+}
+
+/// Decode the file at the path as the buffering says,
+/// small files are decoded entirely at once, and short
+/// ones are kept in the cache
+pub(crate) fn decode_file(
+    path: &str,
+    buffering: &Buffering,
+    decoder: DecoderBackend,
+    cache: &SharedCache,
+) -> io::Result<DecodedSource> {
+    let mut file = File::open(path)?;
+    let metadata = file.metadata()?;
+    let modified = metadata.modified().ok();
+    if let Some(source) = cache.lock().recover().get(path, modified) {
+        return Ok(Box::new(source));
+    }
+    let len = metadata.len();
+    if buffering.predecode_limit == 0 || len > buffering.predecode_limit {
+        let source = match decoder {
+            DecoderBackend::RODIO => {
+                let file = BufReader::with_capacity(buffering.read_buffer, file);
+                Box::new(Decoder::new(file).map_err(invalid_data)?.convert_samples())
+            }
+            #[cfg(feature = "symphonia-decoder")]
+            DecoderBackend::SYMPHONIA => decode_symphonia(file, path)?,
+        };
+        return Ok(decode_with_cache(source, false, path, modified, cache));
+    }
+    let mut bytes = Vec::with_capacity(len as usize);
+    file.read_to_end(&mut bytes)?;
+    let source: DecodedSource = match decoder {
+        DecoderBackend::RODIO => Box::new(
+            Decoder::new(Cursor::new(bytes))
+                .map_err(invalid_data)?
+                .convert_samples(),
+        ),
+        #[cfg(feature = "symphonia-decoder")]
+        DecoderBackend::SYMPHONIA => decode_symphonia(Cursor::new(bytes), path)?,
+    };
+    Ok(decode_with_cache(source, true, path, modified, cache))
+}
+
+/// Decode with Symphonia, guessing the format by
+/// the extension of the path first
+#[cfg(feature = "symphonia-decoder")]
+fn decode_symphonia(media: impl MediaSource + 'static, path: &str) -> io::Result<DecodedSource> {
+    let mut hint = Hint::new();
+    if let Some(extension) = Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(extension);
+    }
+    let stream = MediaSourceStream::new(Box::new(media), Default::default());
+    Ok(Box::new(SymphoniaSource::new(stream, &hint)?))
+}
+
+/// Decode entirely if it's to be cached or pre-decoded,
+/// otherwise the samples are decoded while played
+fn decode_with_cache(
+    source: DecodedSource,
+    predecode: bool,
+    path: &str,
+    modified: Option<SystemTime>,
+    cache: &SharedCache,
+) -> DecodedSource {
+    let (channels, sample_rate) = (source.channels(), source.sample_rate());
+    let cached = source
+        .total_duration()
+        .is_some_and(|length| cache.lock().recover().fits(length, channels, sample_rate));
+    if !cached && !predecode {
+        return source;
+    }
+    // decode without holding the cache
+    let samples: Vec<f32> = source.collect();
+    if cached {
+        let mut cache = cache.lock().recover();
+        Box::new(cache.insert(path, modified, samples, channels, sample_rate))
+    } else {
+        Box::new(SamplesBuffer::new(channels, sample_rate, samples))
+    }
+}
@@ -0,0 +1,212 @@
+use std::{
+    io,
+    sync::{Arc, Mutex},
+};
+
+use rodio::{
+    cpal::{
+        self,
+        traits::{HostTrait, StreamTrait},
+        BufferSize, FromSample, SampleFormat, SampleRate, SizedSample, SupportedBufferSize,
+        SupportedStreamConfig,
+    },
+    source::UniformSourceIterator,
+    Device, DeviceTrait, OutputStream, OutputStreamHandle, Source, StreamError,
+};
+
+use crate::{logging::log, poison::Recover};
+
+/// Format requested for the output stream, the default
+/// of the device is used for anything not given
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct StreamConfig {
+    /// Samples per second of each channel, e.g. 48000
+    pub sample_rate: Option<u32>,
+    /// Number of output channels, e.g. 2 for stereo
+    pub channels: Option<u16>,
+    /// Frames of each buffer of the stream, e.g. 256 for
+    /// low latency, kept within the range of the device;
+    /// the host chooses it if it's not given
+    pub buffer_size: Option<u32>,
+}
+
+impl StreamConfig {
+    /// Get the format supported by the device matching
+    /// this one, `None` if there's no such format
+    pub fn supported_by(&self, device: &Device) -> Option<SupportedStreamConfig> {
+        let default = device.default_output_config().ok()?;
+        let channels = self.channels.unwrap_or(default.channels());
+        let rate = SampleRate(self.sample_rate.unwrap_or(default.sample_rate().0));
+        device
+            .supported_output_configs()
+            .ok()?
+            .filter(|config| config.channels() == channels)
+            .filter(|config| config.min_sample_rate() <= rate && rate <= config.max_sample_rate())
+            .max_by(|a, b| a.cmp_default_heuristics(b))
+            .map(|config| config.with_sample_rate(rate))
+    }
+
+    /// Open a stream on the device in this format, or in
+    /// its default format if this one isn't supported
+    pub fn open(&self, device: &Device) -> Result<(OutputStream, OutputStreamHandle), StreamError> {
+        match self.supported_by(device) {
+            Some(config) => OutputStream::try_from_device_config(device, config),
+            None => OutputStream::try_from_device(device),
+        }
+    }
+
+    /// Open a stream on the default device in this format,
+    /// falling back as `OutputStream::try_default` does
+    ///
+    /// The buffer size isn't taken by streams of rodio,
+    /// the player opens its output with `play` instead
+    pub fn open_default(&self) -> Result<(OutputStream, OutputStreamHandle), StreamError> {
+        cpal::default_host()
+            .default_output_device()
+            .ok_or(StreamError::NoDevice)
+            .and_then(|device| self.open(&device))
+            .or_else(|_| OutputStream::try_default())
+    }
+
+    /// Buffer size within the range supported by the device
+    pub(crate) fn buffer_size_within(&self, supported: &SupportedBufferSize) -> BufferSize {
+        match (self.buffer_size, supported) {
+            (None, _) => BufferSize::Default,
+            (Some(frames), SupportedBufferSize::Range { min, max }) => {
+                BufferSize::Fixed(frames.clamp(*min, (*max).max(*min)))
+            }
+            (Some(frames), SupportedBufferSize::Unknown) => BufferSize::Fixed(frames),
+        }
+    }
+
+    /// Play the source on a stream of the default device in
+    /// this format, the stream plays until it's dropped; the
+    /// buffer size of the host is used if the one given fails
+    pub(crate) fn play<S>(&self, source: S) -> io::Result<cpal::Stream>
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no output device"))?;
+        let supported = match self.supported_by(&device) {
+            Some(supported) => supported,
+            None => device.default_output_config().map_err(io::Error::other)?,
+        };
+        let mut config = supported.config();
+        let samples = UniformSourceIterator::new(source, config.channels, config.sample_rate.0);
+        // shared by the streams tried
+        let samples: Samples = Arc::new(Mutex::new(Box::new(samples)));
+        let format = supported.sample_format();
+        config.buffer_size = self.buffer_size_within(supported.buffer_size());
+        let stream = match build_stream(&device, &config, format, Arc::clone(&samples)) {
+            Err(_e) if config.buffer_size != BufferSize::Default => {
+                log!(
+                    warn,
+                    "the buffer size is refused ({}), using that of the host",
+                    _e
+                );
+                config.buffer_size = BufferSize::Default;
+                build_stream(&device, &config, format, samples)
+            }
+            stream => stream,
+        }?;
+        stream.play().map_err(io::Error::other)?;
+        Ok(stream)
+    }
+}
+
+/// Samples pulled by a stream, in its channels and sample rate
+type Samples = Arc<Mutex<Box<dyn Iterator<Item = f32> + Send>>>;
+
+/// Build a stream pulling the samples in the sample format
+fn build_stream(
+    device: &Device,
+    config: &cpal::StreamConfig,
+    format: SampleFormat,
+    samples: Samples,
+) -> io::Result<cpal::Stream> {
+    match format {
+        SampleFormat::F32 => build_typed::<f32>(device, config, samples),
+        SampleFormat::I16 => build_typed::<i16>(device, config, samples),
+        SampleFormat::U16 => build_typed::<u16>(device, config, samples),
+        SampleFormat::I32 => build_typed::<i32>(device, config, samples),
+        format => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported sample format: {}", format),
+        )),
+    }
+}
+
+fn build_typed<T>(
+    device: &Device,
+    config: &cpal::StreamConfig,
+    samples: Samples,
+) -> io::Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    device
+        .build_output_stream(
+            config,
+            move |data: &mut [T], _| {
+                let mut samples = samples.lock().recover();
+                for out in data.iter_mut() {
+                    *out = T::from_sample(samples.next().unwrap_or(0.0));
+                }
+            },
+            |_e| {
+                log!(error, "output stream error: {}", _e);
+            },
+            None,
+        )
+        .map_err(io::Error::other)
+}
+
+/// Description of an output device
+#[derive(Clone, PartialEq, Debug)]
+pub struct DeviceInfo {
+    /// Name of the audio host (ALSA, WASAPI, CoreAudio...)
+    pub host: String,
+    /// Name of the device, used to select it
+    pub name: String,
+    /// Whether it's the default output device of its host
+    pub is_default: bool,
+}
+
+/// List output devices of all available hosts
+pub fn list_output_devices() -> Vec<DeviceInfo> {
+    let mut res = vec![];
+    for host_id in cpal::available_hosts() {
+        let Ok(host) = cpal::host_from_id(host_id) else {
+            continue;
+        };
+        let default_name = host
+            .default_output_device()
+            .and_then(|device| device.name().ok());
+        let Ok(devices) = host.output_devices() else {
+            continue;
+        };
+        for device in devices {
+            let Ok(name) = device.name() else {
+                continue;
+            };
+            res.push(DeviceInfo {
+                host: host_id.name().to_string(),
+                is_default: default_name.as_ref() == Some(&name),
+                name,
+            });
+        }
+    }
+    res
+}
+
+/// Find the output device with the given name among all hosts
+pub fn find_output_device(name: &str) -> Option<Device> {
+    cpal::available_hosts()
+        .into_iter()
+        .filter_map(|host_id| cpal::host_from_id(host_id).ok())
+        .filter_map(|host| host.output_devices().ok())
+        .flatten()
+        .find(|device| device.name().is_ok_and(|n| n == name))
+}
@@ -0,0 +1,97 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::Source;
+
+use crate::{channels::SharedF32, focus::DUCK_VOLUME, poison::Recover};
+
+/// Interval of following the sounds playing
+const DUCK_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Time a sound takes to duck or to be restored
+const DUCK_RAMP: Duration = Duration::from_millis(100);
+
+/// Priority of a sound; while a sound plays in a channel,
+/// the player and the sounds of lower priorities are
+/// ducked, e.g. the music under a voice prompt
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Priority {
+    LOW,
+    #[default]
+    NORMAL,
+    /// e.g. voice prompts and alarms
+    HIGH,
+}
+
+/// Sounds playing in the channels by their priorities,
+/// and the volume ratio of the ones ducked under them
+pub(crate) struct Ducking {
+    playing: Mutex<HashMap<u64, Priority>>,
+    level: SharedF32,
+}
+
+pub(crate) type SharedDucking = Arc<Ducking>;
+
+impl Default for Ducking {
+    fn default() -> Self {
+        Ducking {
+            playing: Default::default(),
+            level: SharedF32::new(DUCK_VOLUME),
+        }
+    }
+}
+
+impl Ducking {
+    pub fn level(&self) -> f32 {
+        self.level.get()
+    }
+
+    pub fn set_level(&self, level: f32) {
+        self.level.set(level.clamp(0.0, 1.0));
+    }
+
+    /// The sound of the id starts playing
+    pub fn start(&self, id: u64, priority: Priority) {
+        self.playing.lock().recover().insert(id, priority);
+    }
+
+    /// The sound of the id is over
+    pub fn end(&self, id: u64) {
+        self.playing.lock().recover().remove(&id);
+    }
+
+    /// Volume ratio of a sound of the priority, `None`
+    /// if it can't be told without blocking
+    fn ratio_of(&self, priority: Priority) -> Option<f32> {
+        let playing = self.playing.try_lock().ok()?;
+        let ducked = playing.values().any(|&other| other > priority);
+        Some(if ducked { self.level() } else { 1.0 })
+    }
+}
+
+/// Duck the source of the priority while sounds of higher
+/// ones play, gliding between the volumes
+pub(crate) fn with_ducking<S>(
+    source: S,
+    priority: Priority,
+    ducking: SharedDucking,
+) -> impl Source<Item = f32> + Send
+where
+    S: Source<Item = f32> + Send,
+{
+    let step = DUCK_INTERVAL.as_secs_f32() / DUCK_RAMP.as_secs_f32();
+    let mut ratio = ducking.ratio_of(priority).unwrap_or(1.0);
+    source
+        .amplify(ratio)
+        .periodic_access(DUCK_INTERVAL, move |source| {
+            // never block the audio thread, try again later
+            if let Some(target) = ducking.ratio_of(priority) {
+                ratio += (target - ratio).clamp(-step, step);
+                source.set_factor(ratio);
+            }
+        })
+}
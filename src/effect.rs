@@ -0,0 +1,32 @@
+use rodio::Source;
+
+/// Decoded source passed along the effect chain
+pub type BoxedSource = Box<dyn Source<Item = f32> + Send>;
+
+/// Stage of the effect chain, which wraps the source
+/// of every song with rodio source adapters
+/// (reverb, low-pass, custom filters...)
+///
+/// ```
+/// use std::time::Duration;
+/// use rodio::Source;
+/// use super_rodio::{BoxedSource, Make, Player, SharedPlayer};
+///
+/// let player = SharedPlayer::make();
+/// player.add_effect(Box::new(|source: BoxedSource| -> BoxedSource {
+///     Box::new(source.buffered().reverb(Duration::from_millis(40), 0.3))
+/// }));
+/// ```
+pub trait Effect {
+    /// Wrap the source with this effect
+    fn process(&mut self, source: BoxedSource) -> BoxedSource;
+}
+
+impl<F> Effect for F
+where
+    F: FnMut(BoxedSource) -> BoxedSource,
+{
+    fn process(&mut self, source: BoxedSource) -> BoxedSource {
+        self(source)
+    }
+}
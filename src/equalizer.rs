@@ -0,0 +1,236 @@
+use std::{
+    f32::consts::PI,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Source};
+
+/// Center frequencies of the common 10-band equalizer
+pub const TEN_BANDS: [f32; 10] = [
+    31.0, 62.0, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0,
+];
+
+/// Samples between two checks of the shared bands
+const REFRESH_INTERVAL: usize = 1024;
+
+/// A peaking band of the equalizer
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EqBand {
+    /// Center frequency in Hz
+    pub freq: f32,
+    /// Gain in dB, negative to cut
+    pub gain_db: f32,
+    /// Quality factor, i.e. the narrowness of the band
+    pub q: f32,
+}
+
+impl EqBand {
+    pub fn new(freq: f32, gain_db: f32) -> EqBand {
+        EqBand {
+            freq,
+            gain_db,
+            q: 1.41,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EqPreset {
+    #[default]
+    FLAT,
+
+    /// Boost the low frequencies
+    BASS,
+    /// Emphasize the range of human voice
+    VOCAL,
+}
+
+impl EqPreset {
+    /// 10-band setting of this preset
+    pub fn bands(&self) -> Vec<EqBand> {
+        let gains: [f32; 10] = match self {
+            EqPreset::FLAT => [0.0; 10],
+            EqPreset::BASS => [6.0, 5.0, 4.0, 2.5, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+            EqPreset::VOCAL => [-2.0, -2.0, -1.0, 0.0, 2.0, 3.0, 3.0, 2.0, 0.0, -1.0],
+        };
+        TEN_BANDS
+            .iter()
+            .zip(gains)
+            .map(|(&freq, gain)| EqBand::new(freq, gain))
+            .collect()
+    }
+}
+
+/// Normalized coefficients of a biquad filter
+#[derive(Clone, Copy, Default)]
+struct Coefficients {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Coefficients {
+    /// Peaking filter from the RBJ audio EQ cookbook
+    fn peaking(band: &EqBand, sample_rate: u32) -> Coefficients {
+        let a = 10f32.powf(band.gain_db / 40.0);
+        // keep the band below Nyquist frequency
+        let freq = band.freq.min(sample_rate as f32 * 0.45);
+        let w0 = 2.0 * PI * freq / sample_rate as f32;
+        let alpha = w0.sin() / (2.0 * band.q.max(0.01));
+        let cos_w0 = w0.cos();
+        let a0 = 1.0 + alpha / a;
+        Coefficients {
+            b0: (1.0 + alpha * a) / a0,
+            b1: -2.0 * cos_w0 / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: -2.0 * cos_w0 / a0,
+            a2: (1.0 - alpha / a) / a0,
+        }
+    }
+}
+
+/// Filter memory of one band on one channel
+#[derive(Clone, Copy, Default)]
+struct FilterState {
+    x1: f32,
+    x2: f32,
+    y1: f32,
+    y2: f32,
+}
+
+impl FilterState {
+    fn process(&mut self, c: &Coefficients, x: f32) -> f32 {
+        let y = c.b0 * x + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+        self.x2 = self.x1;
+        self.x1 = x;
+        self.y2 = self.y1;
+        self.y1 = y;
+        y
+    }
+}
+
+/// Source adapter applying the equalizer bands,
+/// which are re-read periodically so that changes
+/// take effect on the playing song
+pub struct Equalizer<S> {
+    input: S,
+    shared: Arc<RwLock<Vec<EqBand>>>,
+    bands: Vec<EqBand>,
+    coefficients: Vec<Coefficients>,
+    // states[band * channels + channel]
+    states: Vec<FilterState>,
+    channels: u16,
+    sample_rate: u32,
+    channel: usize,
+    countdown: usize,
+}
+
+impl<S> Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, bands: Arc<RwLock<Vec<EqBand>>>) -> Equalizer<S> {
+        let mut eq = Equalizer {
+            channels: input.channels(),
+            sample_rate: input.sample_rate(),
+            input,
+            shared: bands,
+            bands: vec![],
+            coefficients: vec![],
+            states: vec![],
+            channel: 0,
+            countdown: 0,
+        };
+        eq.refresh();
+        eq
+    }
+
+    /// Reload the bands if they are changed
+    fn refresh(&mut self) {
+        self.countdown = REFRESH_INTERVAL;
+        let Ok(shared) = self.shared.try_read() else {
+            return;
+        };
+        let format_changed =
+            self.channels != self.input.channels() || self.sample_rate != self.input.sample_rate();
+        if *shared == self.bands && !format_changed {
+            return;
+        }
+        self.channels = self.input.channels();
+        self.sample_rate = self.input.sample_rate();
+        self.bands = shared.clone();
+        // flat bands are skipped entirely
+        self.coefficients = self
+            .bands
+            .iter()
+            .filter(|band| band.gain_db != 0.0)
+            .map(|band| Coefficients::peaking(band, self.sample_rate))
+            .collect();
+        self.states.resize(
+            self.coefficients.len() * self.channels as usize,
+            Default::default(),
+        );
+    }
+}
+
+impl<S> Iterator for Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        // only refresh at frame boundary so that channels stay aligned
+        if self.channel == 0 {
+            if self.countdown == 0 {
+                self.refresh();
+            }
+            self.countdown -= 1;
+        }
+        let mut sample = self.input.next()?;
+        let channels = self.channels.max(1) as usize;
+        for (i, c) in self.coefficients.iter().enumerate() {
+            sample = self.states[i * channels + self.channel].process(c, sample);
+        }
+        self.channel = (self.channel + 1) % channels;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Equalizer<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        // old filter memory doesn't belong to the new position
+        self.states.fill(Default::default());
+        self.channel = 0;
+        Ok(())
+    }
+}
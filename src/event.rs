@@ -0,0 +1,46 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{focus::AudioFocus, song::Song};
+
+/// Notifications sent to the subscribers of a player
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum PlayerEvent {
+    /// The song can't be opened or decoded, so it's
+    /// moved to the failed list and skipped
+    SongFailed { song: Song, error: String },
+    /// The output can't be opened, e.g. there's no device,
+    /// so the play loop ends
+    OutputFailed { error: String },
+    /// A live stream announces a new title on air
+    StreamTitle { song: Song, title: String },
+    /// The song is heard long enough to be scrobbled, once
+    /// per play; sent while it's still playing
+    Scrobble {
+        song: Song,
+        started_at: SystemTime,
+        scrobbled_at: SystemTime,
+        duration: Duration,
+    },
+    /// A new file in a watched folder is added to
+    /// waiting list
+    SongAutoAdded { song: Song, folder: String },
+    /// The song is dropped from the front of the full
+    /// waiting list to make room for a song added
+    SongDropped { song: Song },
+    /// The audio focus reported by the OS changed
+    AudioFocusChanged { focus: AudioFocus },
+    /// The output device is switched, by name if it's
+    /// selected by one; the song playing moves to it and
+    /// continues from `position`, if any
+    DeviceChanged {
+        device: Option<String>,
+        position: Option<Duration>,
+    },
+    /// Position of the song playing, sent at the progress
+    /// interval of the player
+    Progress {
+        position: Duration,
+        duration: Duration,
+    },
+}
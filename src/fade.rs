@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use rodio::{source::SeekError, Source};
+
+/// Source adapter fading the input out to silence during
+/// its last `fade_out`, an input of unknown length is
+/// passed through
+pub struct FadeOut<S> {
+    input: S,
+    fade_out: f64,
+    total: Option<f64>,
+    // seconds of the input played
+    pos: f64,
+}
+
+impl<S> FadeOut<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, fade_out: Duration) -> FadeOut<S> {
+        let total = input
+            .total_duration()
+            .filter(|_| !fade_out.is_zero())
+            .map(|total| total.as_secs_f64());
+        FadeOut {
+            input,
+            fade_out: fade_out.as_secs_f64(),
+            total,
+            pos: 0.0,
+        }
+    }
+}
+
+impl<S> Iterator for FadeOut<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        let Some(total) = self.total else {
+            return Some(sample);
+        };
+        let rate = self.input.sample_rate() as f64 * self.input.channels().max(1) as f64;
+        self.pos += 1.0 / rate;
+        let factor = ((total - self.pos) / self.fade_out).clamp(0.0, 1.0);
+        Some(sample * factor as f32)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for FadeOut<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.pos = pos.as_secs_f64();
+        Ok(())
+    }
+}
@@ -0,0 +1,23 @@
+/// Volume ratio while another app ducks the player
+pub(crate) const DUCK_VOLUME: f32 = 0.2;
+
+/// Audio focus of the app, as reported by the OS, e.g. by
+/// `AudioManager.OnAudioFocusChangeListener` on Android
+/// or the interruptions of `AVAudioSession` on iOS
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum AudioFocus {
+    /// The app may play, resuming what a transient
+    /// loss paused
+    #[default]
+    GAINED,
+    /// Another app took over for good, e.g. a music app;
+    /// the player pauses until resumed by the user
+    LOST,
+    /// Another app took over for a while, e.g. a phone
+    /// call; the player pauses until the focus is gained
+    TRANSIENT,
+    /// Another app plays a short sound over the player,
+    /// e.g. a navigation prompt; the player gets quieter
+    DUCK,
+}
@@ -0,0 +1,176 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+use crate::{event::PlayerEvent, probe::is_supported, shared_player::SharedState, song::Song};
+
+/// Interval of checking a watched folder for new files
+const FOLDER_POLL: Duration = Duration::from_millis(500);
+
+/// Extensions of the files worth probing in a folder
+const AUDIO_EXTENSIONS: [&str; 10] = [
+    "wav", "flac", "ogg", "oga", "opus", "mp3", "m4a", "mp4", "aac", "alac",
+];
+
+/// Whether the path has the extension of an audio file
+pub(crate) fn has_audio_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            AUDIO_EXTENSIONS
+                .iter()
+                .any(|audio| audio.eq_ignore_ascii_case(ext))
+        })
+}
+
+/// Compare the names as people do, numbers by their
+/// value and letters ignoring case, e.g. "2" < "10"
+pub(crate) fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek(), b.peek()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let take_number = |chars: &mut std::iter::Peekable<std::str::Chars>| {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+                        digits.push(c);
+                    }
+                    digits
+                };
+                let (x, y) = (take_number(&mut a), take_number(&mut b));
+                let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                let order = x.len().cmp(&y.len()).then_with(|| x.cmp(y));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let order = x.to_lowercase().cmp(y.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+/// Files with audio extensions in the folder, in natural
+/// order, and those of its subfolders after the files
+/// if `recursive`; links to folders aren't followed, so
+/// a link looping back can't recurse forever
+pub(crate) fn audio_files(path: &Path, recursive: bool) -> io::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    let mut folders = vec![];
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        // the type of a link itself, not of its target
+        let kind = entry.file_type()?;
+        let path = entry.path();
+        if kind.is_dir() {
+            folders.push(path);
+        } else if kind.is_symlink() && path.is_dir() {
+            continue;
+        } else if has_audio_extension(&path) {
+            files.push(path);
+        }
+    }
+    let by_name =
+        |a: &PathBuf, b: &PathBuf| natural_cmp(&a.to_string_lossy(), &b.to_string_lossy());
+    files.sort_by(by_name);
+    folders.sort_by(by_name);
+    if recursive {
+        for folder in folders {
+            // an unreadable subfolder loses only its files
+            files.extend(audio_files(&folder, true).unwrap_or_default());
+        }
+    }
+    Ok(files)
+}
+
+/// Song of the audio file, `None` if it can't be played
+pub(crate) fn song_of(file: &Path) -> Option<Song> {
+    let path = file.to_str()?.to_string();
+    let name = file.file_stem()?.to_string_lossy().into_owned();
+    is_supported(&path).then(|| Song::from(name, path))
+}
+
+/// Songs of the playable audio files in the folder, as
+/// `audio_files` orders them
+pub(crate) fn scan_folder(path: &Path, recursive: bool) -> io::Result<Vec<Song>> {
+    let files = audio_files(path, recursive)?;
+    Ok(files.iter().filter_map(|file| song_of(file)).collect())
+}
+
+/// Watch the folder until `stop` is set or the player is
+/// gone, queueing the playable files appearing in it once
+/// they stop growing, i.e. they're copied completely
+pub(crate) fn watch_folder(
+    state: &SharedState,
+    folder: String,
+    recursive: bool,
+    stop: Arc<AtomicBool>,
+) -> io::Result<()> {
+    // files there already are left alone
+    let mut known: HashSet<PathBuf> = audio_files(Path::new(&folder), recursive)?
+        .into_iter()
+        .collect();
+    let state = Arc::downgrade(state);
+    spawn(move || {
+        // sizes of the new files when last seen
+        let mut growing: HashMap<PathBuf, u64> = HashMap::new();
+        loop {
+            sleep(FOLDER_POLL);
+            if stop.load(atomic::Ordering::Relaxed) || state.strong_count() == 0 {
+                return;
+            }
+            // a folder gone for now may come back
+            let Ok(files) = audio_files(Path::new(&folder), recursive) else {
+                continue;
+            };
+            let present: HashSet<&PathBuf> = files.iter().collect();
+            known.retain(|file| present.contains(file));
+            growing.retain(|file, _| present.contains(file));
+            for file in &files {
+                if known.contains(file) {
+                    continue;
+                }
+                let Ok(size) = fs::metadata(file).map(|metadata| metadata.len()) else {
+                    continue;
+                };
+                if growing.insert(file.clone(), size) != Some(size) {
+                    continue;
+                }
+                growing.remove(file);
+                known.insert(file.clone());
+                // a file which can't be played is ignored
+                let Some(mut song) = song_of(file) else {
+                    continue;
+                };
+                let Some(state) = state.upgrade() else {
+                    return;
+                };
+                let mut state = state.write();
+                song.id = Some(state.enqueue(song.clone()));
+                state.emit(PlayerEvent::SongAutoAdded {
+                    song,
+                    folder: folder.clone(),
+                });
+            }
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,111 @@
+use std::{f64::consts::TAU, io, time::Duration};
+
+use rodio::{source::SeekError, Source};
+
+use crate::{effect::BoxedSource, source::IntoAudioSource};
+
+/// Sample rate of generated audio
+const SAMPLE_RATE: u32 = 44100;
+
+/// Kind of audio generated by a [`Generator`]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Waveform {
+    /// Sine wave of the frequency in Hz
+    SINE(f32),
+    SILENCE,
+    WHITENOISE,
+}
+
+/// Procedurally generated audio of a given length,
+/// made by `Song::sine` and so on
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Generator {
+    pub waveform: Waveform,
+    pub duration: Duration,
+}
+
+impl IntoAudioSource for Generator {
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+        Ok(Box::new(self.open_f32()?.convert_samples()))
+    }
+
+    fn open_f32(&self) -> io::Result<BoxedSource> {
+        Ok(Box::new(Generated {
+            generator: *self,
+            length: (self.duration.as_secs_f64() * SAMPLE_RATE as f64) as usize,
+            index: 0,
+            phase: 0.0,
+            // any nonzero seed works for xorshift
+            noise: 0x2545_f491,
+        }))
+    }
+}
+
+/// Mono source of a generator
+struct Generated {
+    generator: Generator,
+    length: usize,
+    index: usize,
+    // turns of the sine wave within its cycle, kept in
+    // [0, 1) so that it stays precise however long it plays
+    phase: f64,
+    noise: u32,
+}
+
+impl Iterator for Generated {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.index >= self.length {
+            return None;
+        }
+        self.index += 1;
+        Some(match self.generator.waveform {
+            Waveform::SINE(freq) => {
+                let sample = (self.phase * TAU).sin() as f32;
+                self.phase = (self.phase + freq as f64 / SAMPLE_RATE as f64).fract();
+                sample
+            }
+            Waveform::SILENCE => 0.0,
+            Waveform::WHITENOISE => {
+                self.noise ^= self.noise << 13;
+                self.noise ^= self.noise >> 17;
+                self.noise ^= self.noise << 5;
+                self.noise as f32 / u32::MAX as f32 * 2.0 - 1.0
+            }
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let left = self.length - self.index;
+        (left, Some(left))
+    }
+}
+
+impl Source for Generated {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.generator.duration)
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        let index = (pos.as_secs_f64() * SAMPLE_RATE as f64) as usize;
+        self.index = index.min(self.length);
+        if let Waveform::SINE(freq) = self.generator.waveform {
+            let turns = self.index as f64 * freq as f64 / SAMPLE_RATE as f64;
+            self.phase = turns.fract();
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+use std::{
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, spawn},
+    time::Duration,
+};
+
+use serde::Serialize;
+use serde_json::json;
+
+use crate::{
+    player::Player,
+    secret::{given_token, same_secret, server_token},
+    shared_player::SharedPlayer,
+    song::Song,
+};
+
+/// Time without any data to drop a client
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Largest body of a request
+const MAX_BODY: usize = 64 * 1024;
+
+/// Largest request line and headers of a request, together
+const MAX_HEAD: usize = 8 * 1024;
+
+/// Longest wait for the rest of a request which is refused
+/// before it's read
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Status line and JSON body of a response
+type Response = (&'static str, Option<String>);
+
+const NO_CONTENT: Response = ("204 No Content", None);
+
+/// Running HTTP service, stopped when dropped
+pub struct HttpServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl HttpServer {
+    /// Address served, e.g. to find the port given by the OS
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for HttpServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        // wake the listener up to see it's stopped
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// Serve a minimal REST API of the player at the address,
+/// e.g. for home automation:
+///
+/// * `POST /play` plays the waiting list, or resumes
+/// * `POST /pause` pauses current song
+/// * `POST /next` skips to the next song
+/// * `GET /queue` gets the waiting list
+/// * `GET /now-playing` gets the current song
+///
+/// Clients give the `token` shared with them as a bearer token
+/// of the `Authorization` header, or in the URL, as `serve_ws`
+/// takes it. A body, if any, has to be JSON, so that no page
+/// can post a form to the player. As with `serve_ws`, songs
+/// can't be added, since their paths name files or URLs for
+/// the player to open, and the paths of the songs sent are
+/// blanked.
+///
+/// ```text
+/// curl -X POST -H "Authorization: Bearer $TOKEN" http://localhost:8080/play
+/// ```
+pub fn serve_http(
+    player: SharedPlayer,
+    addr: impl ToSocketAddrs,
+    token: impl Into<String>,
+) -> io::Result<HttpServer> {
+    let token = server_token(token.into())?;
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let accept_stopped = Arc::clone(&stopped);
+    spawn(move || {
+        for stream in listener.incoming() {
+            if accept_stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let player = player.clone();
+            let token = Arc::clone(&token);
+            spawn(move || {
+                let _ = serve_client(&player, stream, &token);
+            });
+        }
+    });
+    Ok(HttpServer { addr, stopped })
+}
+
+/// Read a line of the head of the request, `None` once the
+/// head is over `MAX_HEAD`, so that no client uses up memory
+fn read_head_line(reader: &mut impl BufRead, left: &mut usize) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    let read = reader.take(*left as u64).read_line(&mut line)?;
+    *left -= read;
+    // a line cut by the limit has no end
+    if *left == 0 && !line.ends_with('\n') {
+        return Ok(None);
+    }
+    Ok(Some(line))
+}
+
+/// Answer one request of a client, then close the connection
+fn serve_client(player: &SharedPlayer, mut stream: TcpStream, token: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let too_large = ("431 Request Header Fields Too Large", None);
+    let mut left = MAX_HEAD;
+    let Some(request) = read_head_line(&mut reader, &mut left)? else {
+        return refuse(&mut stream, &mut reader, too_large);
+    };
+    let mut parts = request.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return write_response(&mut stream, ("400 Bad Request", None));
+    };
+    let (mut length, mut content_type, mut authorization) = (0, None, None);
+    loop {
+        let Some(line) = read_head_line(&mut reader, &mut left)? else {
+            return refuse(&mut stream, &mut reader, too_large);
+        };
+        if line.trim().is_empty() {
+            break;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim().to_string());
+        if key.eq_ignore_ascii_case("content-length") {
+            length = value.parse().unwrap_or(0);
+        } else if key.eq_ignore_ascii_case("content-type") {
+            content_type = Some(value);
+        } else if key.eq_ignore_ascii_case("authorization") {
+            authorization = Some(value);
+        }
+    }
+    if length > MAX_BODY {
+        return refuse(&mut stream, &mut reader, ("413 Payload Too Large", None));
+    }
+    // no endpoint takes a body, it's read to be answered
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let given = given_token(authorization.as_deref(), query);
+    if !given.is_some_and(|given| same_secret(given, token)) {
+        return write_response(&mut stream, error("401 Unauthorized", "the token is wrong"));
+    }
+    // forms of pages are sent with their own types
+    let json = content_type.as_deref().map(|value| {
+        let media = value.split(';').next().unwrap_or_default();
+        media.trim().eq_ignore_ascii_case("application/json")
+    });
+    if json == Some(false) || json.is_none() && length > 0 {
+        let unsupported = error("415 Unsupported Media Type", "the body isn't JSON");
+        return write_response(&mut stream, unsupported);
+    }
+    let response = route(player, method, path.trim_end_matches('/'));
+    write_response(&mut stream, response)
+}
+
+/// Answer a request refused before it's read to its end,
+/// then read the rest, so that the response isn't lost to a
+/// reset of the connection
+fn refuse(stream: &mut TcpStream, reader: &mut impl Read, response: Response) -> io::Result<()> {
+    write_response(stream, response)?;
+    stream.shutdown(Shutdown::Write)?;
+    stream.set_read_timeout(Some(DRAIN_TIMEOUT))?;
+    let _ = io::copy(&mut reader.take(MAX_BODY as u64), &mut io::sink());
+    Ok(())
+}
+
+fn write_response(stream: &mut TcpStream, (status, body): Response) -> io::Result<()> {
+    let body = body.unwrap_or_default();
+    write!(stream, "HTTP/1.1 {}\r\nConnection: close\r\n", status)?;
+    if !body.is_empty() {
+        write!(stream, "Content-Type: application/json\r\n")?;
+    }
+    write!(stream, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    stream.flush()
+}
+
+fn error(status: &'static str, message: impl ToString) -> Response {
+    (
+        status,
+        Some(json!({"error": message.to_string()}).to_string()),
+    )
+}
+
+/// Answer the reply of the player in JSON
+fn ok<T: Serialize>(reply: thread::Result<T>) -> Response {
+    match reply.map(|value| serde_json::to_string(&value)) {
+        Ok(Ok(body)) => ("200 OK", Some(body)),
+        Ok(Err(e)) => error("500 Internal Server Error", e),
+        Err(_) => error("503 Service Unavailable", "the player is shut down"),
+    }
+}
+
+/// Song without its paths, which name local files and are
+/// kept from the clients
+fn hide_paths(mut song: Song) -> Song {
+    song.path.clear();
+    song.cover = None;
+    song
+}
+
+/// Answer a command of the player without content
+fn done(reply: thread::Result<()>) -> Response {
+    match reply {
+        Ok(()) => NO_CONTENT,
+        Err(_) => error("503 Service Unavailable", "the player is shut down"),
+    }
+}
+
+fn route(player: &SharedPlayer, method: &str, path: &str) -> Response {
+    match (method, path) {
+        ("POST", "/play") => {
+            // the reply of play comes once the songs end
+            player.play();
+            done(player.resume().join())
+        }
+        ("POST", "/pause") => done(player.pause().join()),
+        ("POST", "/next") => done(player.skip().join()),
+        ("GET", "/queue") => ok(player
+            .waiting_list()
+            .join()
+            .map(|songs| songs.into_iter().map(hide_paths).collect::<Vec<_>>())),
+        ("GET", "/now-playing") => ok(player.current_song().join().map(|mut current| {
+            current.song = current.song.map(hide_paths);
+            current
+        })),
+        (_, "/play" | "/pause" | "/next" | "/queue" | "/now-playing") => error(
+            "405 Method Not Allowed",
+            format!("{} is not allowed", method),
+        ),
+        _ => error("404 Not Found", format!("no such endpoint: {}", path)),
+    }
+}
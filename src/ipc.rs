@@ -0,0 +1,96 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::spawn,
+};
+
+use crate::{
+    rpc::{self, Caller},
+    shared_player::SharedPlayer,
+};
+
+/// Running IPC service, stopped and its socket
+/// removed when dropped
+pub struct IpcServer {
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+}
+
+impl IpcServer {
+    /// Path of the socket served
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for IpcServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        // wake the listener up to see it's stopped
+        let _ = UnixStream::connect(&self.path);
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Expose the player on a Unix socket at the path, so that
+/// other processes, e.g. scripts or a UI, can control it
+///
+/// Each line sent is a JSON-RPC 2.0 request, answered by a
+/// line of response:
+///
+/// ```text
+/// --> {"jsonrpc": "2.0", "id": 1, "method": "seek", "params": {"pos": 12.5}}
+/// <-- {"jsonrpc": "2.0", "id": 1, "result": null}
+/// ```
+///
+/// The method is named after the `Player` method and the params
+/// after its arguments. Durations of params, `position`,
+/// `queue_duration` and `remaining_duration` are in seconds. Methods taking Rust
+/// values, e.g. `add_effect` or `set_device_maker`, are not
+/// exposed, and `play` answers without waiting for the songs
+/// to end
+pub fn serve_ipc(player: SharedPlayer, socket_path: impl AsRef<Path>) -> io::Result<IpcServer> {
+    let path = socket_path.as_ref().to_path_buf();
+    // a socket left by a process which is gone
+    if path.exists() && UnixStream::connect(&path).is_err() {
+        std::fs::remove_file(&path)?;
+    }
+    let listener = UnixListener::bind(&path)?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let accept_stopped = Arc::clone(&stopped);
+    spawn(move || {
+        for stream in listener.incoming() {
+            if accept_stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let player = player.clone();
+            spawn(move || {
+                let _ = serve_client(&player, stream);
+            });
+        }
+    });
+    Ok(IpcServer { path, stopped })
+}
+
+/// Answer the requests of a client until it disconnects
+fn serve_client(player: &SharedPlayer, stream: UnixStream) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = rpc::handle(player, &line, Caller::Local) {
+            writeln!(writer, "{}", response)?;
+        }
+    }
+    Ok(())
+}
@@ -1,24 +1,141 @@
+#![allow(clippy::upper_case_acronyms)]
+
+// the actor and the play loop block on threads, see README
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+compile_error!("super-rodio can't run in the browser, it needs threads and blocking waits");
+
+mod actor;
+mod analyzer;
 mod asset;
+#[cfg(feature = "async")]
+mod async_player;
+mod builder;
+mod bus;
+mod cache;
+mod channels;
+mod config;
+mod cue;
+mod decode;
+mod device;
+mod ducking;
+mod effect;
+mod equalizer;
+mod event;
+mod fade;
+mod focus;
+mod folder;
+mod generator;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(all(feature = "ipc", unix))]
+mod ipc;
+mod limiter;
+mod logging;
+mod loudness;
 mod make;
+#[cfg(feature = "media-controls")]
+mod media_controls;
+#[cfg(feature = "media-keys")]
+mod media_keys;
+mod metrics;
+mod mirror;
+mod mixer;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+mod mpris;
+#[cfg(feature = "test-backend")]
+mod null_output;
+mod playback;
 mod player;
+mod playlist;
+mod poison;
+mod probe;
+mod queue;
+#[cfg(feature = "radio")]
+mod radio;
+mod ramp;
+#[cfg(feature = "recording")]
+mod recorder;
+mod reply;
+#[cfg(any(feature = "ipc", feature = "remote"))]
+mod rpc;
+#[cfg(any(feature = "http", feature = "remote"))]
+mod secret;
 mod shared_player;
+mod sidechain;
+mod signal;
+mod silence;
+mod sleep_timer;
+mod slice;
 mod song;
+mod source;
+mod spatial;
+mod stall;
+mod stats;
+mod stretch;
+#[cfg(any(feature = "radio", feature = "symphonia-decoder"))]
+mod symphonia_source;
+mod uri;
+mod watch;
+#[cfg(feature = "remote")]
+mod ws;
 
-pub use make::Make;
+pub use actor::PlayerCommand;
+pub use asset::{PlaybackMode, PlaybackState, PlayerSnapshot, PlayerStatus, UiState};
+#[cfg(feature = "async")]
+pub use async_player::{AsyncPlayer, PlayerFuture};
+pub use builder::PlayerBuilder;
+pub use channels::{Balance, Downmix, SharedF32};
+pub use config::{
+    Buffering, DecoderBackend, LatencyProfile, PlayerConfig, RetryPolicy, SkippedSongs, SpeedMode,
+};
+pub use cue::load_cue;
+pub use device::{find_output_device, list_output_devices, DeviceInfo, StreamConfig};
+pub use ducking::Priority;
+pub use effect::{BoxedSource, Effect};
+pub use equalizer::{EqBand, EqPreset, Equalizer, TEN_BANDS};
+pub use event::PlayerEvent;
+pub use focus::AudioFocus;
+pub use generator::{Generator, Waveform};
+#[cfg(feature = "http")]
+pub use http::{serve_http, HttpServer};
+#[cfg(all(feature = "ipc", unix))]
+pub use ipc::{serve_ipc, IpcServer};
+pub use limiter::{Limiter, LimiterMode};
+pub use loudness::{replay_gain, scan_loudness, Normalization};
+pub use make::{Make, MakeWith};
+#[cfg(feature = "media-controls")]
+pub use media_controls::SystemMediaControls;
+#[cfg(feature = "media-keys")]
+pub use media_keys::MediaKeys;
+pub use metrics::PlayerMetrics;
+pub use mixer::Channel;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+pub use mpris::{serve_mpris, MprisServer};
+#[cfg(feature = "test-backend")]
+pub use null_output::NullOutput;
 pub use player::Player;
+pub use playlist::Playlist;
+pub use probe::{is_supported, probe, AudioInfo, Codec};
+#[cfg(feature = "radio")]
+pub use radio::Radio;
+pub use ramp::DEFAULT_RAMP;
+pub use reply::{Reply, ReplyFuture, Responder};
 pub use shared_player::SharedPlayer;
-pub use song::Song;
+pub use silence::SilenceTrim;
+pub use song::{ActiveSong, Chapter, Song, SongBuilder, SongId, SongState};
+pub use source::IntoAudioSource;
+#[cfg(feature = "media-controls")]
+pub use souvlaki::PlatformConfig;
+pub use spatial::SpatialHandle;
+pub use stats::{stats_key, PlayStats, SongStats};
+#[cfg(feature = "remote")]
+pub use ws::{serve_ws, WsServer};
 
 #[cfg(test)]
 mod tests {
     use std::{thread::sleep, time::Duration};
 
-    use rodio::{
-        cpal::{self, traits::HostTrait},
-        DeviceTrait, OutputStream,
-    };
-
-    use crate::{Make, Player, SharedPlayer, Song};
+    use crate::{Make, MakeWith, Player, PlayerConfig, SharedPlayer, Song};
 
     #[test]
     fn test_play_stop() {
@@ -138,27 +255,16 @@ mod tests {
     #[test]
     fn test_choose_output_device() {
         let player = SharedPlayer::make();
-        for host_id in cpal::available_hosts() {
-            println!("In host: {:?}", host_id);
-            let host = cpal::host_from_id(host_id).unwrap();
-            println!("\tInput devices:");
-            for in_d in host.input_devices().unwrap() {
-                let in_d = in_d.name().unwrap();
-                println!("\t\t{}", in_d);
-            }
-            println!("\tOutput devices:");
-            for out_d in host.output_devices().unwrap() {
-                let out_d_copy = out_d.clone();
-                player.set_device_maker(Box::new(move || {
-                    OutputStream::try_from_device(&out_d_copy).unwrap()
-                }));
-                player.add(Song::from("Music".into(), "audio/short_sound".into()));
-                let _ = player.play().join();
-
-                let out_d = out_d.name().unwrap();
-                println!("\t\t{}", out_d);
-            }
+        for device in SharedPlayer::list_output_devices() {
+            println!("In host {}: {:?}", device.host, device);
+            assert!(player.select_device_by_name(&device.name).join().unwrap());
+            player.add(Song::from("Music".into(), "audio/short_sound".into()));
+            let _ = player.play().join();
         }
+        assert!(!player
+            .select_device_by_name("no such device")
+            .join()
+            .unwrap());
     }
 
     #[test]
@@ -176,4 +282,2264 @@ mod tests {
         player.stop();
         // should not be dead if is dead, that is a bug
     }
+
+    #[test]
+    fn test_command_order() {
+        let player = SharedPlayer::make();
+        for i in 0..100 {
+            // no join here, commands are still handled in order
+            player.add(Song::from(format!("Music {}", i), "audio/music".into()));
+        }
+        let waiting = player.waiting_list().join().unwrap();
+        assert_eq!(waiting.len(), 100);
+        assert_eq!(waiting[99].name, "Music 99");
+        assert_eq!(player.waiting_list_now().len(), 100);
+        assert!(!player.is_playing_now());
+    }
+
+    #[test]
+    fn test_song_id() {
+        let player = SharedPlayer::make();
+        let ids: Vec<_> = (0..3)
+            .map(|_| {
+                player
+                    .add(Song::from("Music".into(), "audio/music".into()))
+                    .join()
+                    .unwrap()
+            })
+            .collect();
+        assert!(ids[0] != ids[1] && ids[1] != ids[2]);
+        assert!(player.move_song(ids[2], 0).join().unwrap());
+        let removed = player.remove(ids[0]).join().unwrap();
+        assert_eq!(removed.and_then(|song| song.id), Some(ids[0]));
+        assert!(player.remove(ids[0]).join().unwrap().is_none());
+        let waiting: Vec<_> = player
+            .waiting_list_now()
+            .iter()
+            .map(|song| song.id.unwrap())
+            .collect();
+        assert_eq!(waiting, vec![ids[2], ids[1]]);
+    }
+
+    #[test]
+    fn test_song_builder() {
+        let song = Song::builder("Music", "audio/music")
+            .artist("Artist")
+            .gain(0.5)
+            .start_at(Duration::from_secs(3))
+            .tag("mood", "calm")
+            .build();
+        assert_eq!(song.name, "Music");
+        assert_eq!(song.artist.as_deref(), Some("Artist"));
+        assert_eq!(song.album, None);
+        assert_eq!(song.gain, Some(0.5));
+        assert_eq!(song.start_at, Some(Duration::from_secs(3)));
+        assert_eq!(song.tags["mood"], "calm");
+    }
+
+    #[test]
+    fn test_playlist() {
+        use crate::Playlist;
+
+        let mut playlist = Playlist::new("Chill");
+        for i in 0..3 {
+            playlist.push(Song::from(format!("Music {}", i), "audio/music".into()));
+        }
+        assert!(playlist.move_song(2, 0));
+        assert_eq!(playlist.remove(1).map(|song| song.name).unwrap(), "Music 0");
+        assert!(playlist.remove(5).is_none());
+        assert_eq!(playlist.len(), 2);
+
+        let player = SharedPlayer::make();
+        player.add(Song::from("Other".into(), "audio/music".into()));
+        player.load_playlist(&playlist);
+        let waiting = player.waiting_list().join().unwrap();
+        assert_eq!(waiting.len(), 2);
+        assert_eq!(waiting[0].name, "Music 2");
+        assert!(waiting[0].id.is_some());
+
+        assert!(player.create_playlist("Workout").join().unwrap());
+        assert!(!player.create_playlist("Workout").join().unwrap());
+        assert!(player
+            .add_to_playlist("Workout", Song::from("Run".into(), "audio/music".into()))
+            .join()
+            .unwrap());
+        assert!(!player.queue_playlist("Chill").join().unwrap());
+        assert!(player.queue_playlist("Workout").join().unwrap());
+        assert_eq!(player.waiting_list_now()[0].name, "Run");
+        assert_eq!(player.playlist_names().join().unwrap(), vec!["Workout"]);
+    }
+
+    #[test]
+    fn test_queue_capacity() {
+        use crate::PlayerEvent;
+
+        let player = SharedPlayer::make_with(PlayerConfig {
+            waiting_capacity: 3,
+            ..Default::default()
+        });
+        let events = player.subscribe().join().unwrap();
+        for i in 0..5 {
+            player.add(Song::from(format!("Music {}", i), "audio/music".into()));
+        }
+        let waiting = player.waiting_list().join().unwrap();
+        assert_eq!(waiting.len(), 3);
+        assert_eq!(waiting[0].name, "Music 2");
+        let dropped: Vec<String> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                PlayerEvent::SongDropped { song } => Some(song.name),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(dropped, ["Music 0", "Music 1"]);
+
+        let player = SharedPlayer::make_with(PlayerConfig {
+            waiting_capacity: 3,
+            unbounded_waiting: true,
+            ..Default::default()
+        });
+        for i in 0..5 {
+            player.add(Song::from(format!("Music {}", i), "audio/music".into()));
+        }
+        assert_eq!(player.waiting_list().join().unwrap().len(), 5);
+        player.clear_history();
+        assert_eq!(player.waiting_list().join().unwrap().len(), 5);
+        player.clear_waiting();
+        assert!(player.waiting_list().join().unwrap().is_empty());
+
+        // a huge cap is a limit, not an allocation
+        let player = SharedPlayer::make_with(PlayerConfig {
+            waiting_capacity: usize::MAX,
+            played_capacity: usize::MAX,
+            ..Default::default()
+        });
+        player.add(Song::from("Music".into(), "audio/music".into()));
+        assert_eq!(player.waiting_list().join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_find() {
+        let player = SharedPlayer::make();
+        player.add(Song::from("Intro".into(), "audio/short_sound".into()));
+        player.add(Song::from("Theme".into(), "audio/music".into()));
+        player.add(Song::from("Outro".into(), "audio/Music".into()));
+        let found = player.find("MUSIC").join().unwrap();
+        let found: Vec<_> = found
+            .iter()
+            .map(|(i, song)| (*i, song.name.as_str()))
+            .collect();
+        assert_eq!(found, vec![(1, "Theme"), (2, "Outro")]);
+        let found = player
+            .find_by(Box::new(|song| song.name.ends_with("tro")))
+            .join()
+            .unwrap();
+        assert_eq!(found.len(), 2);
+        assert!(player.find("nothing").join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_queue_range() {
+        let player = SharedPlayer::make();
+        for i in 0..10 {
+            player.add(Song::from(format!("Music {}", i), "audio/music".into()));
+        }
+        let page = player.waiting_range(4, 3).join().unwrap();
+        let names: Vec<_> = page.iter().map(|song| song.name.as_str()).collect();
+        assert_eq!(names, vec!["Music 4", "Music 5", "Music 6"]);
+        assert_eq!(player.waiting_range(8, 5).join().unwrap().len(), 2);
+        assert!(player.played_range(0, 5).join().unwrap().is_empty());
+        assert_eq!(
+            player.with_waiting(|front, back| front.len() + back.len()),
+            10
+        );
+        assert_eq!(
+            player.with_waiting(|front, back| front
+                .iter()
+                .chain(back)
+                .last()
+                .unwrap()
+                .name
+                .clone()),
+            "Music 9"
+        );
+    }
+
+    #[test]
+    fn test_limiter() {
+        use crate::{bus::Bus, limiter::SoftLimiter, poison::Recover, Limiter, LimiterMode};
+        use rodio::buffer::SamplesBuffer;
+
+        let limit = |mode, mut batch: Vec<f32>| {
+            let mut limiter = SoftLimiter::new(Limiter {
+                mode,
+                ..Default::default()
+            });
+            limiter.process(&mut batch);
+            batch
+        };
+        let limited = limit(LimiterMode::ALWAYS, vec![0.5, 2.0, -3.0]);
+        assert_eq!(limited[0], 0.5);
+        assert!(limited[1] > 0.9 && limited[1] <= 1.0);
+        assert!(limited[2] < -0.9 && limited[2] >= -1.0);
+        // only while the mix goes over the full scale
+        assert_eq!(limit(LimiterMode::AUTO, vec![0.5, 0.95]), vec![0.5, 0.95]);
+        assert_eq!(limit(LimiterMode::AUTO, vec![0.5, 2.0, -3.0]), limited);
+        assert_eq!(
+            limit(LimiterMode::NEVER, vec![0.5, 2.0, -3.0]),
+            vec![0.5, 2.0, -3.0]
+        );
+
+        // sounds played without the sink of the songs are
+        // limited too, once they are mixed
+        let bus = Bus::new(Default::default());
+        let mix = |bus: &Bus| {
+            for level in [0.5, 0.8] {
+                bus.inputs
+                    .play(SamplesBuffer::new(2, 44100, vec![level; 2048]));
+            }
+            bus.port().skip(512).take(1024).collect::<Vec<_>>()
+        };
+        assert!(mix(&bus)
+            .iter()
+            .all(|sample| *sample > 0.9 && *sample <= 1.0));
+        *bus.taps.limiter.lock().recover() = Limiter {
+            mode: LimiterMode::NEVER,
+            ..Default::default()
+        };
+        assert!(mix(&bus).iter().all(|sample| *sample > 1.2));
+    }
+
+    #[test]
+    fn test_mirror_feed() {
+        use crate::mirror::{Branch, Feed, Mirrored};
+        use rodio::buffer::SamplesBuffer;
+        use std::sync::{Arc, Weak};
+
+        let samples: Vec<f32> = (0..3000).map(|i| (i % 100) as f32 / 100.0).collect();
+        let feed = Arc::new(Feed::default());
+        let branch = Branch::new(Arc::clone(&feed), 2, 44100, Weak::new());
+        let source = SamplesBuffer::new(2, 44100, samples.clone());
+        let played: Vec<f32> = Mirrored::new(source, vec![feed]).collect();
+        assert_eq!(played, samples);
+        // the mirror plays the same, and ends with the song
+        assert_eq!(branch.collect::<Vec<_>>(), samples);
+    }
+
+    #[test]
+    fn test_device_changed() {
+        use crate::PlayerEvent;
+        use rodio::OutputStream;
+
+        let player = SharedPlayer::make();
+        let events = player.subscribe().join().unwrap();
+        // not opened while nothing plays
+        player
+            .switch_device(Box::new(|| OutputStream::try_default().unwrap()))
+            .join()
+            .unwrap();
+        let event = events.try_recv().unwrap();
+        assert!(matches!(
+            event,
+            PlayerEvent::DeviceChanged {
+                device: None,
+                position: None
+            }
+        ));
+    }
+
+    #[test]
+    fn test_song_uri() {
+        use crate::uri::SongUri;
+
+        let file = SongUri::parse("file:///music/My%20Song.wav").unwrap();
+        assert_eq!(file, SongUri::FILE("/music/My Song.wav".into()));
+        let bare = SongUri::parse("music/a.wav").unwrap();
+        assert_eq!(bare, SongUri::FILE("music/a.wav".into()));
+        let radio = SongUri::parse("HTTP://radio.example/live").unwrap();
+        assert_eq!(radio, SongUri::HTTP("HTTP://radio.example/live"));
+        let data = SongUri::parse("data:audio/wav;base64,aGVs bG8=").unwrap();
+        assert_eq!(data, SongUri::DATA(b"hello".to_vec()));
+        assert!(SongUri::parse("data:audio/wav;base64,a*b").is_err());
+        #[cfg(not(feature = "tls"))]
+        {
+            let https = SongUri::parse("https://radio.example/live").unwrap_err();
+            assert_eq!(https.kind(), std::io::ErrorKind::Unsupported);
+        }
+
+        let song = Song::from("A".into(), "file:///music/My%20Song.wav".into());
+        assert_eq!(song.file().as_deref(), Some("/music/My Song.wav"));
+        assert_eq!(crate::stats::stats_key(&song), "/music/My Song.wav");
+        assert_eq!(song.url().unwrap(), "file:///music/My%20Song.wav");
+        let radio = Song::from("Radio".into(), "http://radio.example/live".into());
+        assert_eq!(radio.file(), None);
+        assert_eq!(radio.url().unwrap(), "http://radio.example/live");
+        assert_eq!(crate::stats::stats_key(&radio), "http://radio.example/live");
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_play_file_uri() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_uri.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.add(Song::from("Tone".into(), format!("file://{}", path)));
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        assert!(player.failed_list().join().unwrap().is_empty());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_add_folder() {
+        use crate::folder::natural_cmp;
+        use std::{cmp::Ordering, fs};
+
+        assert_eq!(natural_cmp("Track 2", "track 10"), Ordering::Less);
+        assert_eq!(natural_cmp("a010", "a10"), Ordering::Equal);
+        let dir = std::env::temp_dir().join("super_rodio_folder");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let tone = write_tone("super_rodio_folder.wav", Duration::from_millis(100));
+        for name in ["2.wav", "10.wav", "1.WAV", "sub/3.wav"] {
+            fs::copy(&tone, dir.join(name)).unwrap();
+        }
+        fs::write(dir.join("bad.wav"), "not audio").unwrap();
+        fs::write(dir.join("notes.txt"), "not audio").unwrap();
+        // a link back to the folder isn't followed
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("sub/loop")).unwrap();
+        let folder = dir.to_string_lossy();
+        let player = SharedPlayer::make();
+        let ids = player.add_folder(&folder, false).join().unwrap().unwrap();
+        assert_eq!(ids.len(), 3);
+        let names: Vec<String> = player
+            .waiting_list_now()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["1", "2", "10"]);
+        player.clear_waiting().join().unwrap();
+        let ids = player.add_folder(&folder, true).join().unwrap().unwrap();
+        assert_eq!(ids.len(), 4);
+        assert!(player
+            .add_folder("audio/missing", false)
+            .join()
+            .unwrap()
+            .is_err());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_watch_folder() {
+        use crate::PlayerEvent;
+        use std::fs;
+
+        let dir = std::env::temp_dir().join("super_rodio_watch_folder");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let tone = write_tone("super_rodio_watched.wav", Duration::from_millis(100));
+        fs::copy(&tone, dir.join("old.wav")).unwrap();
+        let folder = dir.to_string_lossy();
+        let player = SharedPlayer::make();
+        let events = player.subscribe().join().unwrap();
+        player.watch_folder(&folder, false).join().unwrap().unwrap();
+        fs::copy(&tone, dir.join("new.wav")).unwrap();
+        fs::write(dir.join("bad.wav"), "not audio").unwrap();
+        let event = events.recv_timeout(Duration::from_secs(5)).unwrap();
+        let PlayerEvent::SongAutoAdded { song, .. } = event else {
+            panic!("unexpected event {:?}", event);
+        };
+        assert_eq!(song.name, "new");
+        // only the new playable file is added
+        let names: Vec<String> = player
+            .waiting_list_now()
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, ["new"]);
+        assert!(player.unwatch_folder(&folder).join().unwrap());
+        assert!(!player.unwatch_folder(&folder).join().unwrap());
+    }
+
+    #[test]
+    fn test_ui_state() {
+        let player = SharedPlayer::make();
+        for name in ["One", "Two", "Three"] {
+            player.add(Song::from(name.into(), format!("audio/{}", name)));
+        }
+        player.set_volume(0.5).join().unwrap();
+        let ui = player.ui_state(2);
+        assert_eq!(ui.queue.len(), 2);
+        assert_eq!(ui.queue[1].name, "Two");
+        assert_eq!(ui.status.waiting, 3);
+        assert_eq!(ui.status.volume, 0.5);
+        assert!(ui.status.song.is_none());
+        assert!(ui.device.is_none());
+    }
+
+    #[test]
+    fn test_status_display() {
+        use crate::{PlaybackState, PlayerStatus};
+
+        let mut status = PlayerStatus {
+            waiting: 12,
+            ..Default::default()
+        };
+        assert_eq!(status.to_string(), "■ (queue: 12)");
+        status.state = PlaybackState::PLAYING;
+        status.song = Some(Song::from("Song Name".into(), "audio/music".into()));
+        status.position = Duration::from_secs(83);
+        status.duration = Duration::from_secs(225);
+        assert_eq!(
+            status.to_string(),
+            "▶ 01:23/03:45 \"Song Name\" (queue: 12)"
+        );
+        status.duration = Duration::from_secs(3725);
+        assert!(status.to_string().contains("01:23/1:02:05"));
+    }
+
+    #[test]
+    fn test_try_getters() {
+        let player = SharedPlayer::builder()
+            .lock_timeout(Duration::from_millis(20))
+            .build();
+        player
+            .add(Song::from("Music".into(), "audio/music".into()))
+            .join()
+            .unwrap();
+        assert_eq!(player.try_waiting_list().unwrap().len(), 1);
+        assert_eq!(player.try_is_playing(), Some(false));
+        {
+            let _busy = player.state().write();
+            assert!(player.try_waiting_list().is_none());
+            // what's playing is read apart from the queues
+            assert_eq!(player.try_is_playing(), Some(false));
+        }
+        {
+            let _busy = player.hot().write();
+            assert!(player.try_current_song().is_none());
+            assert!(player.try_playback_state().is_none());
+        }
+        assert!(player.try_played_list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_equalizer() {
+        use std::sync::{Arc, RwLock};
+
+        use rodio::{source::SineWave, Source};
+
+        use crate::{EqBand, EqPreset, Equalizer};
+
+        let peak = |bands: Vec<EqBand>| {
+            let sine = SineWave::new(62.0).take_duration(Duration::from_secs(1));
+            Equalizer::new(sine, Arc::new(RwLock::new(bands)))
+                .skip(22050) // skip the transient of filters
+                .fold(0f32, |peak, x| peak.max(x.abs()))
+        };
+        let flat = peak(EqPreset::FLAT.bands());
+        let bass = peak(EqPreset::BASS.bands());
+        assert!((flat - 1.0).abs() < 0.01);
+        assert!(bass > 1.5);
+    }
+
+    #[test]
+    fn test_balance_downmix() {
+        use std::sync::{atomic::AtomicBool, Arc};
+
+        use rodio::buffer::SamplesBuffer;
+
+        use crate::{Balance, Downmix, SharedF32};
+
+        let stereo = || SamplesBuffer::new(2, 44100, vec![1.0f32, 0.0, 1.0, 0.0]);
+        let mono = Arc::new(AtomicBool::new(true));
+        let balance = Arc::new(SharedF32::new(0.0));
+        let mixed: Vec<f32> = Balance::new(Downmix::new(stereo(), mono), balance).collect();
+        assert_eq!(mixed, vec![0.5, 0.5, 0.5, 0.5]);
+
+        let balance = Arc::new(SharedF32::new(0.5));
+        let panned: Vec<f32> = Balance::new(stereo(), balance).collect();
+        assert_eq!(panned, vec![0.5, 0.0, 0.5, 0.0]);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_queue() {
+        use std::{
+            future::Future,
+            pin::pin,
+            sync::Arc,
+            task::{Context, Poll, Wake, Waker},
+            thread::{self, Thread},
+        };
+
+        use crate::AsyncPlayer;
+
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn block_on<F: Future>(fut: F) -> F::Output {
+            let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            let mut fut = pin!(fut);
+            loop {
+                if let Poll::Ready(res) = fut.as_mut().poll(&mut cx) {
+                    return res;
+                }
+                thread::park();
+            }
+        }
+
+        let player = SharedPlayer::make();
+        block_on(async {
+            AsyncPlayer::add(&player, Song::from("Music".into(), "audio/music".into())).await;
+            AsyncPlayer::add(
+                &player,
+                Song::from("Sound".into(), "audio/short_sound".into()),
+            )
+            .await;
+            let waiting = AsyncPlayer::waiting_list(&player).await;
+            assert_eq!(waiting.len(), 2);
+            assert_eq!(waiting[1].name, "Sound");
+        });
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_waveform() {
+        let path = write_tone("super_rodio_waveform.wav", Duration::from_secs(2));
+        let song = Song::builder("Tone", path)
+            .end_at(Duration::from_secs(1))
+            .build();
+        let waveform = song.compute_waveform(10);
+        assert_eq!(waveform.len(), 10);
+        // the tone peaks at 8000 / 32768
+        assert!(waveform.iter().all(|&peak| (peak - 0.244).abs() < 0.01));
+        let missing = Song::from("Missing".into(), "audio/missing".into());
+        assert!(missing.compute_waveform(10).is_empty());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_predecode() {
+        use crate::{Buffering, NullOutput};
+
+        let path = write_tone("super_rodio_predecode.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make_with(PlayerConfig {
+            buffering: Buffering {
+                read_buffer: 512,
+                predecode_limit: 1 << 20,
+            },
+            ..Default::default()
+        });
+        player.use_null_output(NullOutput::new(100.0));
+        let song = Song::builder("Tone", path)
+            .start_at(Duration::from_millis(500))
+            .build();
+        player.add(song);
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        assert!(player.failed_list().join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sample_cache() {
+        use crate::cache::SampleCache;
+        use rodio::Source;
+
+        // room for 1000 samples, 250 for each file
+        let mut cache = SampleCache::new(4000);
+        assert!(cache.fits(Duration::from_millis(250), 1, 1000));
+        assert!(!cache.fits(Duration::from_millis(251), 1, 1000));
+        for name in ["a", "b", "c", "d"] {
+            cache.insert(name, None, vec![0.5; 250], 1, 1000);
+        }
+        let source = cache.get("a", None).unwrap();
+        assert_eq!(source.total_duration(), Some(Duration::from_millis(250)));
+        assert_eq!(source.sum::<f32>(), 125.0);
+        // "b" is the least recently used one now
+        cache.insert("e", None, vec![0.5; 250], 1, 1000);
+        assert!(cache.get("b", None).is_none());
+        assert!(cache.get("a", None).is_some());
+        // a modified file is decoded again
+        assert!(cache.get("c", Some(std::time::SystemTime::now())).is_none());
+        cache.set_budget(0);
+        assert!(cache.get("a", None).is_none());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_poisoned_lock() {
+        use crate::{BoxedSource, NullOutput};
+
+        let path = write_tone("super_rodio_poisoned.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.add_effect(Box::new(|_: BoxedSource| -> BoxedSource {
+            panic!("bad effect");
+        }));
+        player.add(Song::from("Tone".into(), path.clone()));
+        // the loop dies with the effect, but not the player
+        assert!(player.play().join().is_err());
+        assert!(!player.is_playing_now());
+        player.clear_effects().join().unwrap();
+        player.add(Song::from("Tone".into(), path));
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_shutdown() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_shutdown.wav", Duration::from_secs(10));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from("Tone".into(), path.clone()));
+        player.add(Song::from("Tone".into(), path));
+        player.use_auto_play();
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        player.shutdown().join().unwrap();
+        // the loop is over long before the songs
+        assert!(t.is_finished());
+        assert!(!player.is_playing_now());
+        assert!(player.hot().read().sink.is_none());
+        assert!(player.queue_len().join().is_err());
+
+        // dropping the last handle does the same
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from(
+            "Tone".into(),
+            write_tone("super_rodio_drop.wav", Duration::from_secs(10)),
+        ));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        let handle = player.clone();
+        drop(player);
+        assert!(!t.is_finished());
+        drop(handle);
+        assert!(t.is_finished());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_builder() {
+        use crate::{NullOutput, PlaybackMode, PlayerEvent, StreamConfig};
+        use rodio::cpal::{BufferSize, SupportedBufferSize};
+        use std::sync::mpsc::channel;
+
+        let (sender, events) = channel();
+        let (volume_sender, volumes) = channel();
+        let stream = StreamConfig {
+            sample_rate: Some(48000),
+            channels: Some(2),
+            buffer_size: Some(4096),
+        };
+        // the buffer size stays within the range of the device
+        let range = SupportedBufferSize::Range { min: 64, max: 2048 };
+        assert_eq!(stream.buffer_size_within(&range), BufferSize::Fixed(2048));
+        let host = StreamConfig::default();
+        assert_eq!(host.buffer_size_within(&range), BufferSize::Default);
+        // the device is never opened with the null output
+        let player = SharedPlayer::builder()
+            .stream_config(stream)
+            .volume(0.8)
+            .mode(PlaybackMode::AUTO)
+            .waiting_capacity(2)
+            .fade_in(Duration::from_millis(100))
+            .fade_out(Duration::from_millis(100))
+            .on_event(move |event| sender.send(event).unwrap())
+            .on_event_with(move |player, event| {
+                if let PlayerEvent::SongFailed { .. } = event {
+                    volume_sender.send(player.snapshot().volume).unwrap();
+                }
+            })
+            .build();
+        let status = player.snapshot();
+        assert_eq!(status.volume, 0.8);
+        assert_eq!(status.mode, PlaybackMode::AUTO);
+        player.use_null_output(NullOutput::new(100.0));
+        let path = write_tone("super_rodio_builder.wav", Duration::from_secs(1));
+        for _ in 0..3 {
+            player.add(Song::from("Missing".into(), "audio/missing".into()));
+        }
+        player.add(Song::from("Tone".into(), path));
+        assert_eq!(player.queue_len().join().unwrap(), 2);
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        // two of the missing songs didn't fit
+        for _ in 0..2 {
+            let event = events.recv_timeout(Duration::from_secs(1)).unwrap();
+            assert!(matches!(event, PlayerEvent::SongDropped { .. }));
+        }
+        let event = events.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert!(matches!(event, PlayerEvent::SongFailed { .. }));
+        assert_eq!(volumes.recv_timeout(Duration::from_secs(1)), Ok(0.8));
+    }
+
+    #[test]
+    fn test_fade_out() {
+        use crate::fade::FadeOut;
+        use rodio::buffer::SamplesBuffer;
+
+        // 1 s at 1 kHz fading out in its last 0.5 s
+        let source = SamplesBuffer::new(1, 1000, vec![1.0; 1000]);
+        let faded: Vec<f32> = FadeOut::new(source, Duration::from_millis(500)).collect();
+        assert_eq!(faded[400], 1.0);
+        assert!((faded[749] - 0.5).abs() < 0.01, "faded to {}", faded[749]);
+        assert!(faded[999] < 0.01);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_audio_source() {
+        use crate::NullOutput;
+        use rodio::{source::SineWave, Source};
+        use std::sync::Arc;
+
+        let path = write_tone("super_rodio_audio_source.wav", Duration::from_secs(1));
+        let bytes: Arc<[u8]> = std::fs::read(path).unwrap().into();
+        let beep = || -> std::io::Result<Box<dyn Source<Item = i16> + Send>> {
+            let beep = SineWave::new(880.0).take_duration(Duration::from_millis(500));
+            Ok(Box::new(beep.convert_samples()))
+        };
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.add(Song::from_source("Bytes", bytes));
+        player.add(Song::from_source("Beep", beep));
+        let tone = SineWave::new(440.0).take_duration(Duration::from_millis(500));
+        player.add_source("Tone", Box::new(tone));
+        player.use_auto_play();
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 3);
+        assert!(player.failed_list().join().unwrap().is_empty());
+        // only the sources which can be opened again replay
+        player.replay_history();
+        assert_eq!(player.queue_len().join().unwrap(), 2);
+        player.play().join().unwrap();
+        assert!(player.failed_list().join().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_slow_commands() {
+        use rodio::{source::SineWave, Source};
+        use std::time::Instant;
+
+        let slow = || -> std::io::Result<Box<dyn Source<Item = i16> + Send>> {
+            sleep(Duration::from_millis(500));
+            let beep = SineWave::new(880.0).take_duration(Duration::from_millis(10));
+            Ok(Box::new(beep.convert_samples()))
+        };
+        let player = SharedPlayer::make();
+        let preload = player.preload(Song::from_source("Slow", slow));
+        // decoded aside, without holding up the commands
+        let start = Instant::now();
+        player.queue_len().join().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(300));
+        assert!(preload.join().unwrap().is_ok());
+    }
+
+    #[test]
+    fn test_no_output() {
+        let player = SharedPlayer::make();
+        player.set_device_maker(Box::new(|| panic!("no device")));
+        // the channels fail instead of the player
+        assert!(player.channel("sfx").join().unwrap().is_err());
+        let song = Song::from("Music".into(), "audio/music".into());
+        assert!(player.play_once(song).join().unwrap().is_err());
+        assert_eq!(player.queue_len().join().unwrap(), 0);
+    }
+
+    #[cfg(feature = "test-backend")]
+    #[test]
+    fn test_slow_source() {
+        use crate::NullOutput;
+        use rodio::{source::SineWave, Source};
+
+        // a source stuck for longer than the stall timeout,
+        // like a network stream buffering
+        let slow = || -> std::io::Result<Box<dyn Source<Item = i16> + Send>> {
+            let mut stuck = false;
+            let slow = SineWave::new(440.0)
+                .take_duration(Duration::from_millis(500))
+                .periodic_access(Duration::from_millis(100), move |_| {
+                    if !std::mem::replace(&mut stuck, true) {
+                        sleep(Duration::from_millis(2500));
+                    }
+                });
+            Ok(Box::new(slow.convert_samples()))
+        };
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from_source("Slow", slow));
+        player.play().join().unwrap();
+        assert_eq!(player.metrics().join().unwrap().underruns, 0);
+        assert_eq!(player.played_list_now().len(), 1);
+    }
+
+    #[cfg(feature = "test-backend")]
+    #[test]
+    fn test_generated_songs() {
+        use crate::NullOutput;
+
+        let length = Duration::from_millis(100);
+        let samples =
+            |song: Song| -> Vec<f32> { song.source.unwrap().open_f32().unwrap().collect() };
+        let sine = samples(Song::sine(440.0, length));
+        assert_eq!(sine.len(), 4410);
+        let peak = sine.iter().fold(0f32, |peak, x| peak.max(x.abs()));
+        assert!(peak > 0.99 && peak <= 1.0);
+        // the wave stays in tune after hours of playing
+        let mut late = Song::sine(441.0, Duration::from_secs(36000))
+            .source
+            .unwrap()
+            .open_f32()
+            .unwrap();
+        late.try_seek(Duration::from_secs(35999)).unwrap();
+        let late: Vec<f32> = late.take(100).collect();
+        let early = samples(Song::sine(441.0, length));
+        for (late, early) in late.iter().zip(&early) {
+            assert!((late - early).abs() < 1e-3);
+        }
+        assert!(samples(Song::silence(length)).iter().all(|x| *x == 0.0));
+        let noise = samples(Song::white_noise(length));
+        assert!(noise.iter().all(|x| x.abs() <= 1.0));
+        assert!(noise.iter().any(|x| x.abs() > 0.5));
+
+        // generated songs can be played again
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.add(Song::sine(440.0, length));
+        player.add(Song::silence(length));
+        player.use_auto_play();
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 2);
+        player.replay_history();
+        player.play().join().unwrap();
+        assert!(player.failed_list().join().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "radio")]
+    #[test]
+    fn test_radio() {
+        use crate::{
+            radio::{parse_stream_title, resolve, split_url},
+            IntoAudioSource, Radio,
+        };
+        use std::{
+            io::{BufRead, BufReader, Write},
+            net::TcpListener,
+            thread::spawn,
+        };
+
+        let title = parse_stream_title(b"StreamTitle='Rock 'n' Roll';StreamUrl='';\0\0");
+        assert_eq!(title.as_deref(), Some("Rock 'n' Roll"));
+        assert_eq!(parse_stream_title(b"StreamTitle='';"), None);
+
+        let target = split_url("HTTPS://radio.example/live").unwrap();
+        assert!(target.tls);
+        assert_eq!(target.host, "radio.example");
+        assert_eq!(target.address, "radio.example:443");
+        assert_eq!(target.path, "/live");
+        let target = split_url("Http://radio.example:8000").unwrap();
+        assert!(!target.tls);
+        assert_eq!(target.address, "radio.example:8000");
+        assert_eq!(target.path, "/");
+        assert!(split_url("ftp://radio.example/live").is_err());
+
+        let url = "http://radio.example/old/live?id=1";
+        assert_eq!(resolve(url, "/new"), "http://radio.example/new");
+        assert_eq!(resolve(url, "mount"), "http://radio.example/old/mount");
+        assert_eq!(resolve(url, "//cdn.example/a"), "http://cdn.example/a");
+        assert_eq!(
+            resolve(url, "https://cdn.example/a"),
+            "https://cdn.example/a"
+        );
+        assert_eq!(
+            resolve("http://radio.example", "live"),
+            "http://radio.example/live"
+        );
+
+        // silent MPEG-1 layer III frames, 128 kbps at 44.1 kHz
+        let mut frame = vec![0u8; 417];
+        frame[..4].copy_from_slice(&[0xff, 0xfb, 0x90, 0x64]);
+        let audio = frame.repeat(40);
+        let metaint = 4096;
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // the old mount redirects to the stream by a relative location
+        let url = format!("HTTP://{}/old", listener.local_addr().unwrap());
+        spawn(move || {
+            let (mut client, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(client.try_clone().unwrap());
+            let mut line = String::new();
+            request.read_line(&mut line).unwrap();
+            assert!(line.starts_with("GET /old "));
+            write!(client, "HTTP/1.0 302 Found\r\nLocation: live\r\n\r\n").unwrap();
+            drop(client);
+            let (mut client, _) = listener.accept().unwrap();
+            let mut request = BufReader::new(client.try_clone().unwrap());
+            let mut line = String::new();
+            while request.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                client,
+                "ICY 200 OK\r\ncontent-type: audio/mpeg\r\nicy-metaint: {}\r\n\r\n",
+                metaint
+            )
+            .unwrap();
+            for (i, chunk) in audio.chunks(metaint).enumerate() {
+                client.write_all(chunk).unwrap();
+                if chunk.len() == metaint {
+                    let mut metadata = format!("StreamTitle='Song {}';", i).into_bytes();
+                    let blocks = metadata.len().div_ceil(16);
+                    metadata.resize(blocks * 16, 0);
+                    client.write_all(&[blocks as u8]).unwrap();
+                    client.write_all(&metadata).unwrap();
+                }
+            }
+        });
+        let radio = Radio::new(url);
+        assert!(radio.open_f32().unwrap().count() > 0);
+        assert_eq!(radio.stream_title().as_deref(), Some("Song 3"));
+        assert!(Radio::new("https://localhost/live").open_f32().is_err());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_remember_position() {
+        use crate::NullOutput;
+        use std::time::Instant;
+
+        let path = write_tone("super_rodio_bookmark.wav", Duration::from_secs(4));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(2.0));
+        player.remember_position(true);
+        player.add(Song::from("Book".into(), path.clone()));
+        let done = player.play();
+        sleep(Duration::from_millis(1200));
+        player.stop().join().unwrap();
+        done.join().unwrap();
+        assert_eq!(player.state().read().bookmarks.len(), 1);
+
+        // continue from the bookmark, to the end
+        let start = Instant::now();
+        player.add(Song::from("Book".into(), path));
+        player.play().join().unwrap();
+        assert!(start.elapsed() < Duration::from_millis(1600));
+        assert!(player.state().read().bookmarks.is_empty());
+    }
+
+    #[test]
+    fn test_silence_trim() {
+        use crate::{silence::TrimSilence, SilenceTrim};
+        use rodio::{buffer::SamplesBuffer, Source};
+
+        // 0.5 s of silence around 0.5 s of sound at 1 kHz
+        let samples: Vec<f32> = [0.0, 0.5, 0.0]
+            .iter()
+            .flat_map(|&level| vec![level; 500])
+            .collect();
+        let trimmed =
+            |trim| TrimSilence::new(SamplesBuffer::new(1, 1000, samples.clone()), trim).count();
+        assert_eq!(trimmed(None), 1500);
+        assert_eq!(trimmed(Some(SilenceTrim::default())), 500);
+        let short = SilenceTrim {
+            max_trim: Duration::from_millis(200),
+            ..Default::default()
+        };
+        assert_eq!(trimmed(Some(short)), 1100);
+        // the length leaves out the leading silence skipped
+        let length = |trim| {
+            TrimSilence::new(SamplesBuffer::new(1, 1000, samples.clone()), trim).total_duration()
+        };
+        assert_eq!(length(None), Some(Duration::from_millis(1500)));
+        assert_eq!(
+            length(Some(SilenceTrim::default())),
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(length(Some(short)), Some(Duration::from_millis(1300)));
+    }
+
+    #[test]
+    fn test_spectrum() {
+        use crate::analyzer::AudioWindow;
+
+        let mut window = AudioWindow::default();
+        assert_eq!(window.spectrum(8), vec![0.0; 8]);
+        // 500 Hz at 8 kHz, in the 9th of 64 bands
+        let tone: Vec<f32> = (0..4096)
+            .map(|i| (i as f32 * 500.0 / 8000.0 * std::f32::consts::TAU).sin())
+            .collect();
+        window.push(&tone, 1);
+        let spectrum = window.spectrum(64);
+        let peak = (0..64)
+            .max_by(|&a, &b| spectrum[a].total_cmp(&spectrum[b]))
+            .unwrap();
+        assert_eq!(peak, 8);
+        assert!((spectrum[peak] - 1.0).abs() < 0.1);
+        assert!(spectrum[32] < 0.01);
+    }
+
+    #[test]
+    fn test_levels() {
+        use crate::analyzer::AudioWindow;
+
+        let mut window = AudioWindow::default();
+        assert!(window.levels().is_empty());
+        // full-scale square wave on the left, silence on the right
+        let frames: Vec<f32> = (0..1024)
+            .flat_map(|i| [if i % 2 == 0 { 1.0 } else { -1.0 }, 0.0])
+            .collect();
+        window.push(&frames, 2);
+        assert_eq!(window.levels(), vec![(1.0, 1.0), (0.0, 0.0)]);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_mix_levels() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_levels.wav", Duration::from_millis(500));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        // the channels are metered along with the songs
+        let sfx = player.channel("sfx").join().unwrap().unwrap();
+        sfx.add(Song::from("Tone".into(), path)).unwrap();
+        sleep(Duration::from_millis(200));
+        let (peak, rms) = player.levels().join().unwrap();
+        assert!(peak > 0.1 && rms > 0.0, "levels {peak} {rms}");
+        while !sfx.is_empty() {
+            sleep(Duration::from_millis(5));
+        }
+        sleep(Duration::from_millis(100));
+        assert_eq!(player.levels().join().unwrap(), (0.0, 0.0));
+    }
+
+    /// Write a mono 16-bit WAV file of a sine tone
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    fn write_tone(name: &str, length: Duration) -> String {
+        let rate = 8000u32;
+        let samples: Vec<i16> = (0..(rate as f32 * length.as_secs_f32()) as usize)
+            .map(|i| {
+                ((i as f32 * 440.0 / rate as f32 * std::f32::consts::TAU).sin() * 8000.0) as i16
+            })
+            .collect();
+        let data_len = samples.len() as u32 * 2;
+        let mut bytes = vec![];
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(36 + data_len).to_le_bytes());
+        bytes.extend_from_slice(b"WAVEfmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes()); // mono
+        bytes.extend_from_slice(&rate.to_le_bytes());
+        bytes.extend_from_slice(&(rate * 2).to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());
+        bytes.extend_from_slice(&16u16.to_le_bytes());
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&data_len.to_le_bytes());
+        samples
+            .iter()
+            .for_each(|x| bytes.extend_from_slice(&x.to_le_bytes()));
+        let path = std::env::temp_dir().join(name);
+        std::fs::write(&path, bytes).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_null_output_auto_play() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_null_output.wav", Duration::from_secs(2));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        for i in 0..3 {
+            player.add(Song::from(format!("Tone {}", i), path.clone()));
+        }
+        player.use_auto_play();
+        // a 2s song is played in about 20ms
+        player.play().join().unwrap();
+        let played = player.played_list_now();
+        assert_eq!(played.len(), 3);
+        assert_eq!(played[2].name, "Tone 2");
+        assert!(player.waiting_list_now().is_empty());
+        assert!(!player.is_playing_now());
+
+        player.replay_last(2).join().unwrap();
+        let waiting = player.waiting_list_now();
+        assert_eq!(waiting.len(), 2);
+        assert_eq!(waiting[0].name, "Tone 1");
+        assert_eq!(player.played_list_now().len(), 1);
+        player.replay_history().join().unwrap();
+        assert_eq!(player.waiting_list_now()[2].name, "Tone 0");
+        assert!(player.played_list_now().is_empty());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_loop_region() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_loop_region.wav", Duration::from_secs(4));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(10.0));
+        player.add(Song::from("Tone".into(), path));
+        player.set_loop_region(Duration::ZERO, Duration::from_secs(1));
+        let t = player.play();
+        // the song would be over in about 400ms without the loop
+        sleep(Duration::from_millis(800));
+        assert!(player.is_playing_now());
+        player.clear_loop_region();
+        t.join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_chapters() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_chapters.wav", Duration::from_secs(8));
+        let song = Song::builder("Book", path)
+            .chapter("Two", Duration::from_secs(2))
+            .chapter("One", Duration::ZERO)
+            .chapter("Three", Duration::from_secs(4))
+            .build();
+        assert_eq!(song.chapters[1].name, "Two");
+        assert_eq!(song.chapter_at(Duration::from_secs(3)), Some(1));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(song);
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        // the position is updated shortly after a seek
+        let chapter = || {
+            sleep(Duration::from_millis(100));
+            let pos = player.snapshot().position;
+            player.current_song_now().song.unwrap().chapter_at(pos)
+        };
+        player.goto_chapter(2).join().unwrap();
+        assert_eq!(chapter(), Some(2));
+        player.prev_chapter().join().unwrap();
+        assert_eq!(chapter(), Some(1));
+        player.next_chapter().join().unwrap();
+        assert_eq!(chapter(), Some(2));
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_continuous_play() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_continuous.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(10.0));
+        player.use_continuous_play();
+        player.add(Song::from("Tone".into(), path.clone()));
+        let t = player.play();
+        sleep(Duration::from_millis(300));
+        assert_eq!(player.played_list_now().len(), 1);
+        // played by the waiting loop without another play
+        player.add(Song::from("Tone".into(), path));
+        sleep(Duration::from_millis(300));
+        assert_eq!(player.played_list_now().len(), 2);
+        player.stop();
+        t.join().unwrap();
+        assert!(player.waiting_list_now().is_empty());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_play_twice() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_play_twice.wav", Duration::from_secs(4));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.use_auto_play();
+        for name in ["One", "Two"] {
+            player.add(Song::from(name.into(), path.clone()));
+        }
+        // the second play finds the first loop running
+        let first = player.play();
+        player.play().join().unwrap();
+        sleep(Duration::from_millis(200));
+        assert_eq!(player.waiting_list_now().len(), 1);
+        player.clear_waiting().join().unwrap();
+        player.stop().join().unwrap();
+        first.join().unwrap();
+        sleep(Duration::from_millis(100));
+        assert!(!player.is_playing_now());
+        assert!(player.current_song_now().song.is_none());
+        // it can be played again once the loop is over
+        player.add(Song::from("Three".into(), path));
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        assert_eq!(player.current_song_now().song.unwrap().name, "Three");
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_load_state_while_playing() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_load_state.wav", Duration::from_secs(4));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from("One".into(), path.clone()));
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        let snapshot = player.save_state().join().unwrap();
+        // the song playing is stopped along with its loop
+        player.load_state(snapshot).join().unwrap();
+        t.join().unwrap();
+        assert!(!player.is_playing_now());
+        let waiting = player.waiting_list_now();
+        assert_eq!(waiting.len(), 1);
+        assert_eq!(waiting[0].name, "One");
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        assert_eq!(player.current_song_now().song.unwrap().name, "One");
+        assert!(player.waiting_list_now().is_empty());
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_play_index() {
+        use crate::{NullOutput, SkippedSongs};
+
+        let path = write_tone("super_rodio_play_index.wav", Duration::from_secs(8));
+        let name = |player: &SharedPlayer| player.current_song_now().song.map(|song| song.name);
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        for name in ["One", "Two", "Three"] {
+            player.add(Song::from(name.into(), path.clone()));
+        }
+        assert!(!player.play_index(3).join().unwrap());
+        // starts playing if nothing is played
+        assert!(player.play_index(1).join().unwrap());
+        sleep(Duration::from_millis(200));
+        assert_eq!(name(&player).as_deref(), Some("Two"));
+        assert_eq!(player.played_list_now()[0].name, "One");
+        assert_eq!(player.waiting_list_now().len(), 1);
+        player.stop();
+
+        let player = SharedPlayer::builder()
+            .skipped_songs(SkippedSongs::KEEP)
+            .build();
+        player.use_null_output(NullOutput::new(1.0));
+        let mut ids = vec![];
+        for name in ["One", "Two", "Three"] {
+            ids.push(
+                player
+                    .add(Song::from(name.into(), path.clone()))
+                    .join()
+                    .unwrap(),
+            );
+        }
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        assert!(player.play_song(ids[2]).join().unwrap());
+        sleep(Duration::from_millis(200));
+        assert_eq!(name(&player).as_deref(), Some("Three"));
+        assert_eq!(player.waiting_list_now()[0].name, "Two");
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_previous() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_previous.wav", Duration::from_secs(8));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from("One".into(), path.clone()));
+        player.add(Song::from("Two".into(), path));
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        player.skip().join().unwrap();
+        sleep(Duration::from_millis(200));
+        let name = || player.current_song_now().song.map(|song| song.name);
+        assert_eq!(name().as_deref(), Some("Two"));
+        player.previous().join().unwrap();
+        sleep(Duration::from_millis(200));
+        assert_eq!(name().as_deref(), Some("One"));
+        assert!(player.played_list_now().is_empty());
+        assert_eq!(player.waiting_list_now()[0].name, "Two");
+        // restart the song played for a while
+        player.seek(Duration::from_secs(5)).join().unwrap();
+        sleep(Duration::from_millis(100));
+        player.previous().join().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(name().as_deref(), Some("One"));
+        assert!(player.snapshot().position < Duration::from_secs(1));
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_position() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_position.wav", Duration::from_secs(8));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        assert_eq!(player.position(), Duration::ZERO);
+        player.add(Song::from("Tone".into(), path));
+        let t = player.play();
+        sleep(Duration::from_millis(200));
+        player.pause().join().unwrap();
+        // read right after the seek, while paused
+        player.seek(Duration::from_secs(5)).join().unwrap();
+        let pos = player.position();
+        assert!(pos >= Duration::from_secs(5) && pos < Duration::from_millis(5100));
+        sleep(Duration::from_millis(100));
+        assert_eq!(player.position(), pos);
+        // offsets out of any position are ignored
+        player.seek_by(f32::INFINITY).join().unwrap();
+        player.seek_by(f32::NAN).join().unwrap();
+        assert_eq!(player.position(), pos);
+        player.stop();
+        t.join().unwrap();
+        assert_eq!(player.position(), Duration::ZERO);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_progress_events() {
+        use crate::{NullOutput, PlayerEvent};
+
+        let path = write_tone("super_rodio_progress.wav", Duration::from_secs(1));
+        let player = SharedPlayer::builder()
+            .progress_interval(Duration::from_millis(100))
+            .build();
+        player.use_null_output(NullOutput::new(1.0));
+        let events = player.subscribe().join().unwrap();
+        player.add(Song::from("Tone".into(), path.clone()));
+        player.play().join().unwrap();
+        let progress: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                PlayerEvent::Progress { position, duration } => Some((position, duration)),
+                _ => None,
+            })
+            .collect();
+        assert!(progress.len() >= 5);
+        assert!(progress.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        assert!(progress
+            .iter()
+            .all(|&(_, duration)| duration == Duration::from_secs(1)));
+        // disabled
+        player.set_progress_interval(Duration::ZERO).join().unwrap();
+        player.add(Song::from("Tone".into(), path));
+        player.play().join().unwrap();
+        assert_eq!(events.try_iter().count(), 0);
+    }
+
+    #[cfg(all(
+        feature = "symphonia-decoder",
+        feature = "test-backend",
+        feature = "wav"
+    ))]
+    #[test]
+    fn test_symphonia_decoder() {
+        use crate::{DecoderBackend, NullOutput};
+
+        let path = write_tone("super_rodio_symphonia.wav", Duration::from_secs(4));
+        let player = SharedPlayer::builder()
+            .decoder(DecoderBackend::SYMPHONIA)
+            .build();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from("Tone".into(), path.clone()));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        assert_eq!(player.snapshot().duration, Duration::from_secs(4));
+        player.seek(Duration::from_secs(3)).join().unwrap();
+        assert!(player.position() >= Duration::from_secs(3));
+        t.join().unwrap();
+        // a song may choose the decoder itself
+        let song = Song::builder("Tone", path)
+            .decoder(DecoderBackend::RODIO)
+            .build();
+        assert!(player.preload(song).join().unwrap().is_ok());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_stats() {
+        use crate::{stats_key, NullOutput};
+
+        let path = write_tone("super_rodio_stats.wav", Duration::from_secs(2));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(2.0));
+        player.use_auto_play();
+        for _ in 0..2 {
+            player.add(Song::from("Tone".into(), path.clone()));
+        }
+        let t = player.play();
+        sleep(Duration::from_millis(300));
+        player.skip().join().unwrap();
+        t.join().unwrap();
+        let stats = player.stats().join().unwrap();
+        let song = Song::from("Tone".into(), path);
+        let tone = stats[stats_key(&song).as_ref()];
+        assert_eq!((tone.plays, tone.skips), (2, 1));
+        assert!(tone.listen_time >= Duration::from_millis(1000));
+        assert!(tone.last_played.is_some());
+
+        player.load_stats(Default::default()).join().unwrap();
+        assert!(player.stats().join().unwrap().is_empty());
+
+        // skipping ahead isn't listening
+        let long = write_tone("super_rodio_stats_seek.wav", Duration::from_secs(20));
+        player.use_null_output(NullOutput::new(10.0));
+        player.add(Song::from("Long".into(), long.clone()));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        player.seek(Duration::from_secs(18)).join().unwrap();
+        t.join().unwrap();
+        let stats = player.stats().join().unwrap();
+        let heard = stats[stats_key(&Song::from("Long".into(), long)).as_ref()].listen_time;
+        assert!(heard < Duration::from_secs(8), "heard {heard:?}");
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_scrobble() {
+        use crate::{NullOutput, PlayerEvent};
+
+        let long = write_tone("super_rodio_scrobble.wav", Duration::from_secs(40));
+        let short = write_tone("super_rodio_no_scrobble.wav", Duration::from_secs(20));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(40.0));
+        player.use_auto_play();
+        let events = player.subscribe().join().unwrap();
+        player.add(Song::from("Short".into(), short));
+        player.add(Song::from("Long".into(), long));
+        player.play().join().unwrap();
+        let scrobbles: Vec<_> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                PlayerEvent::Scrobble {
+                    song,
+                    started_at,
+                    scrobbled_at,
+                    duration,
+                } => Some((song.name, started_at, scrobbled_at, duration)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(scrobbles.len(), 1);
+        let (name, started_at, scrobbled_at, duration) = &scrobbles[0];
+        assert_eq!(name, "Long");
+        assert!(started_at < scrobbled_at);
+        assert_eq!(duration.as_secs(), 40);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_volume_ramp() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_volume_ramp.wav", Duration::from_secs(2));
+        let player = SharedPlayer::builder().volume(1.0).build();
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from("Tone".into(), path));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        let sink_volume = || player.hot().read().sink.as_ref().unwrap().volume();
+        player.set_volume_smooth(0.0, Duration::from_millis(400));
+        sleep(Duration::from_millis(100));
+        assert_eq!(player.snapshot().volume, 0.0);
+        assert!(sink_volume() > 0.2 && sink_volume() < 1.0);
+        sleep(Duration::from_millis(400));
+        assert_eq!(sink_volume(), 0.0);
+        // a newer ramp overrides the older one
+        player.set_volume_smooth(1.0, Duration::from_millis(200));
+        player.set_volume(0.5).join().unwrap();
+        sleep(Duration::from_millis(300));
+        assert_eq!(sink_volume(), 0.5);
+        // muting keeps the volume to be restored
+        player.mute().join().unwrap();
+        assert!(player.is_muted().join().unwrap());
+        player.set_volume(0.8);
+        sleep(Duration::from_millis(100));
+        assert_eq!(sink_volume(), 0.0);
+        assert_eq!(player.snapshot().volume, 0.8);
+        player.unmute().join().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(sink_volume(), 0.8);
+        assert!(!player.snapshot().muted);
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_audio_focus() {
+        use crate::{AudioFocus, NullOutput, PlayerEvent};
+
+        let path = write_tone("super_rodio_audio_focus.wav", Duration::from_secs(2));
+        let player = SharedPlayer::builder().volume(1.0).build();
+        player.use_null_output(NullOutput::new(1.0));
+        let events = player.subscribe().join().unwrap();
+        player.add(Song::from("Tone".into(), path));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        // a phone call pauses until the focus comes back
+        player
+            .set_audio_focus(AudioFocus::TRANSIENT)
+            .join()
+            .unwrap();
+        assert!(player.is_paused().join().unwrap());
+        player.set_audio_focus(AudioFocus::GAINED).join().unwrap();
+        assert!(!player.is_paused().join().unwrap());
+        player.set_audio_focus(AudioFocus::DUCK).join().unwrap();
+        sleep(Duration::from_millis(100));
+        let sink_volume = player.hot().read().sink.as_ref().unwrap().volume();
+        assert!(sink_volume < 0.5);
+        // a permanent loss waits for the user
+        player.set_audio_focus(AudioFocus::LOST).join().unwrap();
+        player.set_audio_focus(AudioFocus::GAINED).join().unwrap();
+        assert!(player.is_paused().join().unwrap());
+        let focuses: Vec<AudioFocus> = events
+            .try_iter()
+            .filter_map(|event| match event {
+                PlayerEvent::AudioFocusChanged { focus } => Some(focus),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(focuses.len(), 5);
+        player.stop();
+        t.join().unwrap();
+    }
+
+    #[test]
+    fn test_master_volume() {
+        use crate::{mixer::with_master, SharedF32};
+        use rodio::buffer::SamplesBuffer;
+        use std::sync::Arc;
+
+        let master = Arc::new(SharedF32::new(0.5));
+        let source = SamplesBuffer::new(1, 1000, vec![1.0f32; 1000]);
+        let mut source = with_master(source, Arc::clone(&master));
+        assert_eq!(source.next(), Some(0.5));
+        // followed within 5ms, i.e. 5 samples at 1 kHz
+        master.set(0.25);
+        assert_eq!(source.nth(10), Some(0.25));
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_preload() {
+        use crate::{bus::OutputMaker, LatencyProfile, PlayerConfig, StreamConfig};
+        use std::sync::Arc;
+
+        let mut config = PlayerConfig::default();
+        LatencyProfile::LOW.apply(&mut config);
+        assert!(config.buffering.predecode_limit > 0);
+        let path = write_tone("super_rodio_preload.wav", Duration::from_millis(500));
+        let modified = std::fs::metadata(&path).unwrap().modified().ok();
+        let player = SharedPlayer::builder().latency(LatencyProfile::LOW).build();
+        let song = Song::from("Tone".into(), path.clone());
+        player.preload(song).join().unwrap().unwrap();
+        let cache = Arc::clone(&player.state().read().cache);
+        assert!(cache.lock().unwrap().get(&path, modified).is_some());
+        let missing = Song::from("Missing".into(), "audio/missing".into());
+        assert!(player.preload(missing).join().unwrap().is_err());
+        // small buffers are asked of the output stream
+        assert!(matches!(
+            player.state().read().output_maker,
+            OutputMaker::Config(StreamConfig {
+                buffer_size: Some(256),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_spatial_glide() {
+        use crate::spatial::{Positions, Spatializer};
+        use rodio::buffer::SamplesBuffer;
+        use std::sync::{Arc, Mutex};
+
+        let left = Positions {
+            emitter: [-1.0, 0.0, 1.0],
+            left_ear: [-0.1, 0.0, 0.0],
+            right_ear: [0.1, 0.0, 0.0],
+        };
+        let right = Positions {
+            emitter: [1.0, 0.0, 1.0],
+            ..left
+        };
+        let positions = Arc::new(Mutex::new(left));
+        let source = SamplesBuffer::new(1, 1000, vec![1.0f32; 1000]);
+        let mut source = Spatializer::new(source, Arc::clone(&positions));
+        let [l, r] = left.gains();
+        assert!(l > r, "louder on the nearer ear");
+        assert_eq!(source.next(), Some(l));
+        assert_eq!(source.next(), Some(r));
+        *positions.lock().unwrap() = right;
+        // the gains glide within 20ms, i.e. 20 frames
+        let lefts: Vec<f32> = source.by_ref().take(60).step_by(2).collect();
+        let steps = lefts.windows(2).map(|pair| (pair[1] - pair[0]).abs());
+        assert!(steps.fold(0f32, f32::max) < (l - r) / 10.0);
+        let [l, _] = right.gains();
+        assert_eq!(lefts.last(), Some(&l));
+    }
+
+    #[test]
+    fn test_priority_ducking() {
+        use crate::ducking::{with_ducking, Ducking, Priority};
+        use rodio::buffer::SamplesBuffer;
+        use std::sync::Arc;
+
+        let ducking = Arc::new(Ducking::default());
+        let tone = || SamplesBuffer::new(1, 1000, vec![1.0f32; 1000]);
+        let mut music = with_ducking(tone(), Priority::NORMAL, Arc::clone(&ducking));
+        let mut prompt = with_ducking(tone(), Priority::HIGH, Arc::clone(&ducking));
+        assert_eq!(music.next(), Some(1.0));
+        ducking.start(1, Priority::HIGH);
+        // glides down within 100ms, i.e. 100 samples
+        let ducked: Vec<f32> = music.by_ref().take(150).collect();
+        assert!(ducked.windows(2).all(|pair| pair[1] <= pair[0]));
+        assert!((ducked[149] - 0.2).abs() < 1e-3);
+        assert_eq!(prompt.nth(149), Some(1.0));
+        ducking.end(1);
+        assert!((music.nth(149).unwrap() - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_sidechain_ducking() {
+        use crate::sidechain::{with_sidechain, ChannelLevel, Meter, Sidechain};
+        use rodio::buffer::SamplesBuffer;
+        use std::sync::{Arc, Mutex};
+
+        let level = Arc::new(ChannelLevel::default());
+        let sidechains = Arc::new(Mutex::new(vec![Sidechain {
+            source: "sfx".into(),
+            target: "music".into(),
+            level: Arc::clone(&level),
+            amount: 0.5,
+            attack: Duration::from_millis(20),
+            release: Duration::from_millis(100),
+        }]));
+        let tone = || SamplesBuffer::new(1, 1000, vec![1.0f32; 1000]);
+        let mut music = with_sidechain(tone(), "music".into(), Arc::clone(&sidechains));
+        assert_eq!(music.next(), Some(1.0));
+        let mut sfx = Meter::new(tone(), Arc::clone(&level));
+        sfx.next();
+        // ducked within the attack of 20ms, i.e. 20 samples
+        assert_eq!(music.nth(29), Some(0.5));
+        // a silent channel ducks nothing
+        level.volume.set(0.0);
+        assert!(music.nth(19).unwrap() < 1.0);
+        assert_eq!(music.nth(99), Some(1.0));
+        level.volume.set(1.0);
+        assert_eq!(music.nth(29), Some(0.5));
+        drop(sfx);
+        assert_eq!(music.nth(149), Some(1.0));
+    }
+
+    #[cfg(feature = "time-stretch")]
+    #[test]
+    fn test_time_stretch() {
+        use crate::stretch::{Stretch, TimeStretch};
+        use rodio::buffer::SamplesBuffer;
+        use std::{f32::consts::PI, sync::Arc};
+
+        // 200Hz for a second
+        let samples: Vec<f32> = (0..8000)
+            .map(|i| (2.0 * PI * 200.0 * i as f32 / 8000.0).sin())
+            .collect();
+        let tone = || SamplesBuffer::new(1, 8000, samples.clone());
+        let crossings = |wave: &[f32]| {
+            wave.windows(2)
+                .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+                .count()
+        };
+        let stretch = Arc::new(Stretch::default());
+        let same: Vec<f32> = TimeStretch::new(tone(), Arc::clone(&stretch)).collect();
+        assert_eq!(same, samples);
+
+        stretch.set_factor(2.0);
+        let fast: Vec<f32> = TimeStretch::new(tone(), Arc::clone(&stretch)).collect();
+        // half as long, at the same pitch
+        assert!((fast.len() as i32 - 4000).abs() <= 160);
+        assert!((crossings(&fast) as i32 - 100).abs() <= 3);
+        // the song is ahead of the output
+        let pos = stretch.song_pos(Duration::from_millis(500));
+        assert!((pos.as_secs_f32() - 1.0).abs() < 0.03);
+    }
+
+    #[cfg(feature = "time-stretch")]
+    #[test]
+    fn test_pitch_shift() {
+        use crate::stretch::{Resample, Stretch, TimeStretch};
+        use rodio::buffer::SamplesBuffer;
+        use std::{f32::consts::PI, sync::Arc};
+
+        let samples: Vec<f32> = (0..8000)
+            .map(|i| (2.0 * PI * 200.0 * i as f32 / 8000.0).sin())
+            .collect();
+        let crossings = |wave: &[f32]| {
+            wave.windows(2)
+                .filter(|pair| pair[0] < 0.0 && pair[1] >= 0.0)
+                .count()
+        };
+        let stretch = Arc::new(Stretch::default());
+        stretch.set_pitch(12.0);
+        let tone = SamplesBuffer::new(1, 8000, samples);
+        let shifted: Vec<f32> = Resample::new(
+            TimeStretch::new(tone, Arc::clone(&stretch)),
+            Arc::clone(&stretch),
+        )
+        .collect();
+        // an octave up, as long as before
+        assert!((shifted.len() as i32 - 8000).abs() <= 160);
+        assert!((crossings(&shifted) as i32 - 400).abs() <= 6);
+        let pos = stretch.song_pos(Duration::from_millis(500));
+        assert!((pos.as_secs_f32() - 0.5).abs() < 0.03);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_stop_after() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_stop_after.wav", Duration::from_secs(2));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        for _ in 0..3 {
+            player.add(Song::from("Tone".into(), path.clone()));
+        }
+        player.use_auto_play();
+        player.stop_after_current();
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        assert_eq!(player.waiting_list_now().len(), 2);
+
+        // play in real time so that the timer is hit
+        player.use_null_output(NullOutput::new(1.0));
+        player.stop_after(Duration::from_millis(300), Some(Duration::from_millis(100)));
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 2);
+        assert_eq!(player.waiting_list_now().len(), 1);
+
+        // cancelled in the middle of the fade out
+        let sink_volume = || {
+            let volume = player.state().read().volume;
+            player.hot().read().sink.as_ref().unwrap().volume() / volume
+        };
+        player.stop_after(Duration::from_millis(600), Some(Duration::from_millis(500)));
+        let t = player.play();
+        sleep(Duration::from_millis(300));
+        assert!(sink_volume() < 0.9);
+        player.cancel_stop_after().join().unwrap();
+        sleep(Duration::from_millis(100));
+        assert_eq!(sink_volume(), 1.0);
+        player.stop();
+        t.join().unwrap();
+
+        // a timer up while nothing plays is over
+        player.add(Song::from("Tone".into(), path.clone()));
+        player.stop_after(Duration::from_millis(50), None);
+        sleep(Duration::from_millis(100));
+        player.use_null_output(NullOutput::new(100.0));
+        player.play().join().unwrap();
+        assert!(player.waiting_list_now().is_empty());
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_pause_resume() {
+        use crate::{NullOutput, PlaybackState, SongState};
+
+        let path = write_tone("super_rodio_pause_resume.wav", Duration::from_secs(2));
+        let player = SharedPlayer::make();
+        assert_eq!(player.playback_state_now(), PlaybackState::IDLE);
+        player.use_null_output(NullOutput::new(1.0));
+        player.add(Song::from("Tone".into(), path));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        assert_eq!(player.playback_state_now(), PlaybackState::PLAYING);
+        player.pause().join().unwrap();
+        assert_eq!(player.current_song_now().state, SongState::PAUSE);
+        assert!(!player.is_playing_now());
+        assert!(player.is_paused().join().unwrap());
+        let status = player.snapshot();
+        assert_eq!(status.state, PlaybackState::PAUSED);
+        assert_eq!(status.song.unwrap().name, "Tone");
+        assert!(status.position > Duration::ZERO);
+        assert_eq!(status.duration, Duration::from_secs(2));
+        assert_eq!(status.waiting, 0);
+        // play doesn't start another loop while paused
+        player.play().join().unwrap();
+        player.resume().join().unwrap();
+        assert_eq!(player.current_song_now().state, SongState::PLAY);
+        player.toggle().join().unwrap();
+        assert_eq!(player.current_song_now().state, SongState::PAUSE);
+        player.skip();
+        t.join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        assert_eq!(player.playback_state_now(), PlaybackState::STOPPED);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_queue_duration() {
+        let path = write_tone("super_rodio_queue_duration.wav", Duration::from_secs(2));
+        let info = crate::probe(&path).unwrap();
+        assert_eq!(info.codec, crate::Codec::WAV);
+        assert_eq!((info.sample_rate, info.channels), (8000, 1));
+        assert_eq!(info.duration, Some(Duration::from_secs(2)));
+        assert!(!crate::is_supported("audio/music"));
+
+        let player = SharedPlayer::make();
+        for _ in 0..3 {
+            player.add(Song::from("Tone".into(), path.clone()));
+        }
+        // unknown length isn't counted
+        player.add(Song::from("Music".into(), "audio/music".into()));
+        assert_eq!(player.queue_len().join().unwrap(), 4);
+        assert_eq!(
+            player.queue_duration().join().unwrap(),
+            Duration::from_secs(6)
+        );
+        assert_eq!(
+            player.remaining_duration().join().unwrap(),
+            Duration::from_secs(6)
+        );
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_cue() {
+        use crate::{load_cue, NullOutput};
+
+        write_tone("super_rodio_cue.wav", Duration::from_secs(4));
+        let cue = std::env::temp_dir().join("super_rodio_cue.cue");
+        std::fs::write(
+            &cue,
+            [
+                "PERFORMER \"Band\"",
+                "TITLE \"Album\"",
+                "FILE \"super_rodio_cue.wav\" WAVE",
+                "  TRACK 01 AUDIO",
+                "    TITLE \"One\"",
+                "    INDEX 01 00:00:00",
+                "  TRACK 02 AUDIO",
+                "    TITLE \"Two\"",
+                "    INDEX 00 00:02:50",
+                "    INDEX 01 00:03:00",
+            ]
+            .join("\n"),
+        )
+        .unwrap();
+        let songs = load_cue(&cue.to_string_lossy()).unwrap();
+        assert_eq!(songs.len(), 2);
+        assert_eq!(songs[0].name, "One");
+        assert_eq!(songs[1].artist.as_deref(), Some("Band"));
+        assert_eq!(songs[0].end_at, Some(Duration::from_secs(3)));
+        assert_eq!(songs[1].start_at, Some(Duration::from_secs(3)));
+        // an unquoted name is followed by the type too
+        std::fs::write(&cue, "FILE super_rodio_cue.wav WAVE\nTRACK 01 AUDIO").unwrap();
+        let unquoted = load_cue(&cue.to_string_lossy()).unwrap();
+        assert_eq!(unquoted[0].path, songs[0].path);
+
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        for song in songs {
+            player.add(song);
+        }
+        assert_eq!(
+            player.queue_duration().join().unwrap(),
+            Duration::from_secs(4)
+        );
+        player.use_auto_play();
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 2);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_skip_failed_song() {
+        use crate::{NullOutput, PlayerEvent};
+
+        let path = write_tone("super_rodio_skip_failed.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        let events = player.subscribe().join().unwrap();
+        player.add(Song::from("Missing".into(), "audio/missing".into()));
+        player.add(Song::from("Tone".into(), path));
+        player.add(Song::from("Missing".into(), "audio/missing".into()));
+        player.use_auto_play();
+        player.play().join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        assert_eq!(player.failed_list().join().unwrap().len(), 2);
+        let failed: Vec<_> = events.try_iter().collect();
+        assert_eq!(failed.len(), 2);
+        assert!(
+            matches!(&failed[0], PlayerEvent::SongFailed { song, .. } if song.name == "Missing")
+        );
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_metrics() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_metrics.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.add(Song::from("Missing".into(), "audio/missing".into()));
+        player.add(Song::from("Tone".into(), path.clone()));
+        player.use_auto_play();
+        player.play().join().unwrap();
+        player.add(Song::from("Tone".into(), path));
+        let metrics = player.metrics().join().unwrap();
+        assert_eq!(metrics.songs_played, 1);
+        assert_eq!(metrics.decode_errors, 1);
+        assert_eq!(metrics.waiting, 1);
+        assert!(!metrics.playing);
+        let text = metrics.to_prometheus();
+        assert!(text.contains("# TYPE super_rodio_songs_played_total counter\n"));
+        assert!(text.contains("\nsuper_rodio_decode_errors_total 1\n"));
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_watch() {
+        use crate::{NullOutput, PlaybackState, PlayerStatus};
+
+        let path = write_tone("super_rodio_watch.wav", Duration::from_secs(10));
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(1.0));
+        let watch = player.watch().join().unwrap();
+        let first = watch.recv().unwrap();
+        assert!(first.song.is_none());
+        player.add(Song::from("Tone".into(), path));
+        let t = player.play();
+        let next = |check: &dyn Fn(&PlayerStatus) -> bool| loop {
+            let status = watch.recv_timeout(Duration::from_secs(2)).unwrap();
+            if check(&status) {
+                break status;
+            }
+        };
+        let playing = next(&|s| s.state == PlaybackState::PLAYING && s.song.is_some());
+        assert_eq!(playing.song.unwrap().name, "Tone");
+        // progress is pushed once a second
+        let later = next(&|s| s.position >= Duration::from_secs(1));
+        assert_eq!(later.state, PlaybackState::PLAYING);
+        player.pause().join().unwrap();
+        next(&|s| s.state == PlaybackState::PAUSED);
+        player.stop().join().unwrap();
+        t.join().unwrap();
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_track_gap() {
+        use crate::NullOutput;
+        use std::time::Instant;
+
+        let path = write_tone("super_rodio_gap.wav", Duration::from_secs(1));
+        let player = SharedPlayer::make();
+        // ten times faster, the gap takes 0.5 s
+        player.use_null_output(NullOutput::new(10.0));
+        player.set_track_gap(Duration::from_secs(5));
+        player.add(Song::from("One".into(), path.clone()));
+        player.add(Song::from("Two".into(), path));
+        player.use_auto_play();
+        let start = Instant::now();
+        player.play().join().unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(500));
+        assert_eq!(player.played_list_now().len(), 2);
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "wav"))]
+    #[test]
+    fn test_retry_policy() {
+        use crate::{NullOutput, RetryPolicy};
+
+        let name = "super_rodio_retry.wav";
+        let path = std::env::temp_dir().join(name);
+        let _ = std::fs::remove_file(&path);
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.set_retry_policy(RetryPolicy::new(5, Duration::from_millis(50)));
+        player.add(Song::from("Late".into(), path.to_string_lossy().into()));
+        let t = player.play();
+        // the file shows up after the first attempts
+        sleep(Duration::from_millis(120));
+        write_tone(name, Duration::from_secs(1));
+        t.join().unwrap();
+        assert_eq!(player.played_list_now().len(), 1);
+        assert!(player.failed_list().join().unwrap().is_empty());
+
+        // a stop ends the wait for a retry at once
+        let missing = std::env::temp_dir().join("super_rodio_retry_missing.wav");
+        player.set_retry_policy(RetryPolicy::new(1, Duration::from_secs(10)));
+        player.add(Song::from(
+            "Missing".into(),
+            missing.to_string_lossy().into(),
+        ));
+        let t = player.play();
+        sleep(Duration::from_millis(100));
+        let start = std::time::Instant::now();
+        player.stop().join().unwrap();
+        t.join().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(1));
+
+        // a bad multiplier keeps the delay as it is
+        let retry = RetryPolicy {
+            multiplier: f32::NAN,
+            ..RetryPolicy::new(1, Duration::from_secs(1))
+        };
+        assert_eq!(retry.next_delay(retry.backoff), Duration::from_secs(1));
+        let retry = RetryPolicy {
+            multiplier: -2.0,
+            ..retry
+        };
+        assert_eq!(retry.next_delay(retry.backoff), Duration::from_secs(1));
+    }
+
+    #[cfg(all(feature = "test-backend", feature = "recording", feature = "wav"))]
+    #[test]
+    fn test_recording() {
+        use crate::NullOutput;
+
+        let path = write_tone("super_rodio_record_src.wav", Duration::from_secs(1));
+        let out = std::env::temp_dir().join("super_rodio_record_out.wav");
+        let out = out.to_string_lossy().to_string();
+        let player = SharedPlayer::make();
+        player.use_null_output(NullOutput::new(100.0));
+        player.start_recording(out.clone()).join().unwrap().unwrap();
+        player.add(Song::from("Tone".into(), path.clone()));
+        player.play().join().unwrap();
+        player.stop_recording().join().unwrap().unwrap();
+
+        let reader = hound::WavReader::open(&out).unwrap();
+        let spec = reader.spec();
+        let seconds = reader.duration() as f32 / spec.sample_rate as f32;
+        assert!((seconds - 1.0).abs() < 0.05, "recorded {seconds}s");
+        // nothing recorded after stopping
+        assert!(player.stop_recording().join().unwrap().is_ok());
+
+        // sounds out of the queue are recorded too
+        player.start_recording(out.clone()).join().unwrap().unwrap();
+        let sfx = player.channel("sfx").join().unwrap().unwrap();
+        sfx.add(Song::from("Tone".into(), path)).unwrap();
+        while !sfx.is_empty() {
+            sleep(Duration::from_millis(5));
+        }
+        player.stop_recording().join().unwrap().unwrap();
+        let reader = hound::WavReader::open(&out).unwrap();
+        let seconds = reader.duration() as f32 / reader.spec().sample_rate as f32;
+        assert!((seconds - 1.0).abs() < 0.05, "recorded {seconds}s");
+    }
+
+    #[cfg(all(feature = "ipc", unix))]
+    #[test]
+    fn test_ipc() {
+        use crate::serve_ipc;
+        use serde_json::{json, Value};
+        use std::{
+            io::{BufRead, BufReader, Write},
+            os::unix::net::UnixStream,
+        };
+
+        let path = std::env::temp_dir().join("super_rodio_ipc.sock");
+        let player = SharedPlayer::make();
+        let server = serve_ipc(player.clone(), &path).unwrap();
+        let mut client = UnixStream::connect(server.path()).unwrap();
+        let mut lines = BufReader::new(client.try_clone().unwrap()).lines();
+        let mut call = |request: String| -> Value {
+            writeln!(client, "{}", request).unwrap();
+            serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap()
+        };
+        let song = json!({"name": "Song", "path": "/music/song.wav"});
+        let added = call(
+            json!({"jsonrpc": "2.0", "id": 1, "method": "add", "params": {"song": song}})
+                .to_string(),
+        );
+        assert_eq!(added["id"], 1);
+        assert!(added["result"].is_u64());
+        let waiting =
+            call(json!({"jsonrpc": "2.0", "id": 2, "method": "waiting_list"}).to_string());
+        assert_eq!(waiting["result"][0]["name"], "Song");
+
+        // errors are answered with the codes of JSON-RPC
+        let missing = call(json!({"jsonrpc": "2.0", "id": 3, "method": "fly"}).to_string());
+        assert_eq!(missing["error"]["code"], -32601);
+        let bad = call(
+            json!({"jsonrpc": "2.0", "id": 4, "method": "seek", "params": {"pos": "soon"}})
+                .to_string(),
+        );
+        assert_eq!(bad["error"]["code"], -32602);
+        // a notification isn't answered, the parse error after it is
+        let notification = json!({"jsonrpc": "2.0", "method": "pause"});
+        let parse = call(format!("{}\nnot json", notification));
+        assert_eq!(parse["error"]["code"], -32700);
+        assert_eq!(parse["id"], Value::Null);
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[cfg(feature = "remote")]
+    #[test]
+    fn test_ws() {
+        use crate::serve_ws;
+        use serde_json::{json, Value};
+        use tungstenite::{client::IntoClientRequest, connect, http::StatusCode, Error, Message};
+
+        let player = SharedPlayer::make();
+        assert!(serve_ws(player.clone(), "127.0.0.1:0", "").is_err());
+        let server = serve_ws(player.clone(), "127.0.0.1:0", "secret").unwrap();
+        let url = format!("ws://{}/?token=secret", server.local_addr());
+        let (mut socket, _) = connect(&url).unwrap();
+        let mut call = |request: Value| -> Value {
+            socket.send(Message::Text(request.to_string())).unwrap();
+            // notifications come in between
+            loop {
+                let Message::Text(text) = socket.read().unwrap() else {
+                    continue;
+                };
+                let response: Value = serde_json::from_str(&text).unwrap();
+                if response["id"] == request["id"] {
+                    return response;
+                }
+            }
+        };
+        player.add(Song::from("Song".into(), "/music/song.wav".into()));
+        let waiting = call(json!({"jsonrpc": "2.0", "id": 2, "method": "waiting_list"}));
+        assert_eq!(waiting["result"][0]["name"], "Song");
+        // paths of files are kept from the network, both ways
+        assert_eq!(waiting["result"][0]["path"], "");
+        let saved = call(json!({"jsonrpc": "2.0", "id": 3, "method": "save_state"}));
+        assert_eq!(saved["result"]["waiting"][0]["path"], "");
+        let session = call(
+            json!({"jsonrpc": "2.0", "id": 4, "method": "save_session", "params": {"path": "/tmp/x"}}),
+        );
+        assert_eq!(session["error"]["code"], -32601);
+        let song = json!({"name": "Secret", "path": "/etc/passwd"});
+        for (id, method) in ["add", "play_once", "preload"].into_iter().enumerate() {
+            let refused = call(
+                json!({"jsonrpc": "2.0", "id": 10 + id, "method": method, "params": {"song": song}}),
+            );
+            assert_eq!(refused["error"]["code"], -32601);
+        }
+        let shutdown = call(json!({"jsonrpc": "2.0", "id": 20, "method": "shutdown"}));
+        assert_eq!(shutdown["error"]["code"], -32601);
+        assert_eq!(player.queue_len().join().unwrap(), 1);
+
+        let rejected = |request| match connect(request) {
+            Err(Error::Http(response)) => response.status(),
+            other => panic!("accepted: {:?}", other.map(|(_, response)| response)),
+        };
+        let wrong = format!("ws://{}/?token=secrets", server.local_addr());
+        assert_eq!(
+            rejected(wrong.into_client_request().unwrap()),
+            StatusCode::UNAUTHORIZED
+        );
+        let mut foreign = url.as_str().into_client_request().unwrap();
+        foreign
+            .headers_mut()
+            .insert("Origin", "http://evil.example".parse().unwrap());
+        assert_eq!(rejected(foreign), StatusCode::FORBIDDEN);
+        let mut same = url.as_str().into_client_request().unwrap();
+        let origin = format!("http://{}", server.local_addr());
+        same.headers_mut().insert("Origin", origin.parse().unwrap());
+        assert!(connect(same).is_ok());
+    }
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn test_http() {
+        use crate::serve_http;
+        use std::{
+            io::{Read, Write},
+            net::TcpStream,
+        };
+
+        let player = SharedPlayer::make();
+        assert!(serve_http(player.clone(), "127.0.0.1:0", "").is_err());
+        let server = serve_http(player.clone(), "127.0.0.1:0", "secret").unwrap();
+        let send = |head: &str| -> (String, String) {
+            let mut stream = TcpStream::connect(server.local_addr()).unwrap();
+            stream.write_all(head.as_bytes()).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            let (head, body) = response.split_once("\r\n\r\n").unwrap();
+            let status = head.lines().next().unwrap()[9..].to_string();
+            (status, body.to_string())
+        };
+        let request = |method: &str, path: &str| {
+            send(&format!(
+                "{} {} HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n",
+                method, path
+            ))
+        };
+        player.add(Song::from("Song".into(), "/a.wav".into()));
+        let (status, body) = request("GET", "/queue/");
+        assert_eq!(status, "200 OK");
+        let waiting: Vec<Song> = serde_json::from_str(&body).unwrap();
+        assert_eq!(waiting[0].name, "Song");
+        // paths of files are kept from the clients
+        assert_eq!(waiting[0].path, "");
+        let (status, body) = request("GET", "/now-playing?x=1");
+        assert_eq!(status, "200 OK");
+        let current: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(current["song"], serde_json::Value::Null);
+        assert_eq!(request("POST", "/pause").0, "204 No Content");
+        let query = send("POST /pause?token=secret HTTP/1.1\r\n\r\n");
+        assert_eq!(query.0, "204 No Content");
+
+        // errors are answered with their status
+        assert_eq!(request("POST", "/queue").0, "405 Method Not Allowed");
+        assert_eq!(request("DELETE", "/queue").0, "405 Method Not Allowed");
+        assert_eq!(request("GET", "/stop").0, "404 Not Found");
+        let anonymous = send("POST /pause HTTP/1.1\r\n\r\n");
+        assert_eq!(anonymous.0, "401 Unauthorized");
+        let wrong = send("POST /pause HTTP/1.1\r\nAuthorization: Bearer secrets\r\n\r\n");
+        assert_eq!(wrong.0, "401 Unauthorized");
+        let form = send(
+            "POST /pause?token=secret HTTP/1.1\r\n\
+             Content-Type: application/x-www-form-urlencoded\r\nContent-Length: 3\r\n\r\na=b",
+        );
+        assert_eq!(form.0, "415 Unsupported Media Type");
+        let json = send(
+            "POST /pause?token=secret HTTP/1.1\r\n\
+             Content-Type: application/json; charset=utf-8\r\nContent-Length: 2\r\n\r\n{}",
+        );
+        assert_eq!(json.0, "204 No Content");
+        let huge = format!(
+            "GET /queue HTTP/1.1\r\nX-Padding: {}\r\n\r\n",
+            "a".repeat(10_000)
+        );
+        assert_eq!(send(&huge).0, "431 Request Header Fields Too Large");
+    }
 }
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use crate::bus::{CHANNELS, SAMPLE_RATE};
+
+/// Time the limiter keeps working in `LimiterMode::AUTO` after
+/// the mix last went over the full scale, so that it isn't
+/// switched on and off between the peaks
+const LIMITER_HOLD: Duration = Duration::from_secs(1);
+
+/// When the output limiter works
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LimiterMode {
+    /// Only while the final mix goes over the full scale,
+    /// e.g. volumes and gains above 1.0 stacking up, and
+    /// for a moment after it
+    #[default]
+    AUTO,
+    ALWAYS,
+    NEVER,
+}
+
+/// Soft limiter on the final mix of the player, the songs,
+/// channels and other sounds together, keeping stacked
+/// gains from clipping
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Limiter {
+    pub mode: LimiterMode,
+    /// Level from which the peaks are softened, as a
+    /// ratio of the full scale, from 0.1 to 0.99
+    pub threshold: f32,
+}
+
+impl Default for Limiter {
+    /// Work when needed, from about -1 dBFS
+    fn default() -> Self {
+        Limiter {
+            mode: LimiterMode::AUTO,
+            threshold: 0.9,
+        }
+    }
+}
+
+impl Limiter {
+    /// Soften the sample so that it never goes
+    /// over the full scale
+    fn soften(&self, sample: f32) -> f32 {
+        let threshold = self.threshold.clamp(0.1, 0.99);
+        let level = sample.abs();
+        if level <= threshold {
+            return sample;
+        }
+        let knee = 1.0 - threshold;
+        let level = threshold + knee * ((level - threshold) / knee).tanh();
+        level.copysign(sample)
+    }
+}
+
+/// Limiter working on the final mix, a batch at a time
+pub(crate) struct SoftLimiter {
+    pub limiter: Limiter,
+    /// Samples left to limit in `LimiterMode::AUTO`
+    hold: usize,
+}
+
+impl SoftLimiter {
+    pub fn new(limiter: Limiter) -> SoftLimiter {
+        SoftLimiter { limiter, hold: 0 }
+    }
+
+    pub fn process(&mut self, batch: &mut [f32]) {
+        if batch.iter().any(|sample| sample.abs() > 1.0) {
+            self.hold =
+                (LIMITER_HOLD.as_secs_f32() * (SAMPLE_RATE * CHANNELS as u32) as f32) as usize;
+        } else {
+            self.hold = self.hold.saturating_sub(batch.len());
+        }
+        let active = match self.limiter.mode {
+            LimiterMode::AUTO => self.hold > 0,
+            LimiterMode::ALWAYS => true,
+            LimiterMode::NEVER => false,
+        };
+        if active {
+            for sample in batch.iter_mut() {
+                *sample = self.limiter.soften(*sample);
+            }
+        }
+    }
+}
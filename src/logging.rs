@@ -0,0 +1,17 @@
+//! Events logged through `tracing` by the feature of the
+//! same name, compiled away without it
+
+/// Log an event at the level, e.g. `log!(info, "playing {}", name)`
+#[cfg(feature = "tracing")]
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {
+        tracing::$level!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! log {
+    ($level:ident, $($arg:tt)*) => {};
+}
+
+pub(crate) use log;
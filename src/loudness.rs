@@ -0,0 +1,133 @@
+#[cfg(feature = "replay-gain")]
+use std::path::Path;
+use std::{fs::File, io::BufReader, time::Duration};
+
+use rodio::{Decoder, Source};
+#[cfg(feature = "replay-gain")]
+use symphonia::core::{
+    formats::FormatOptions,
+    io::MediaSourceStream,
+    meta::{MetadataOptions, MetadataRevision, StandardTagKey},
+    probe::Hint,
+};
+
+/// Reference loudness of ReplayGain, in dBFS RMS
+const REPLAY_GAIN_REFERENCE: f32 = -18.0;
+
+/// Length of audio analysed by a loudness scan
+const SCAN_LENGTH: Duration = Duration::from_secs(60);
+
+/// Limit of the gain applied by normalization, in dB
+const MAX_GAIN: f32 = 12.0;
+
+/// Per-song loudness normalization settings
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Normalization {
+    /// Loudness to normalize to, in dBFS RMS
+    pub target: f32,
+    /// Scan the song when it has no ReplayGain tag
+    pub scan: bool,
+}
+
+impl Default for Normalization {
+    fn default() -> Self {
+        Normalization {
+            target: REPLAY_GAIN_REFERENCE,
+            scan: true,
+        }
+    }
+}
+
+impl Normalization {
+    /// Linear gain to apply to the song at the given path
+    pub fn gain_of(&self, path: &str) -> f32 {
+        let db = match replay_gain(path) {
+            Some(gain) => gain + self.target - REPLAY_GAIN_REFERENCE,
+            None if self.scan => scan_loudness(path)
+                .map(|loudness| self.target - loudness)
+                .unwrap_or(0.0),
+            None => 0.0,
+        };
+        10f32.powf(db.clamp(-MAX_GAIN, MAX_GAIN) / 20.0)
+    }
+}
+
+/// Parse a gain value like "-6.52 dB"
+#[cfg(feature = "replay-gain")]
+fn parse_db(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .trim_end_matches(|c: char| c.is_alphabetic() || c.is_whitespace())
+        .parse()
+        .ok()
+}
+
+#[cfg(feature = "replay-gain")]
+fn track_gain_of(revision: &MetadataRevision) -> Option<f32> {
+    revision.tags().iter().find_map(|tag| {
+        let is_track_gain = tag.std_key == Some(StandardTagKey::ReplayGainTrackGain)
+            || tag.key.eq_ignore_ascii_case("replaygain_track_gain");
+        if is_track_gain {
+            parse_db(&tag.value.to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Read ReplayGain track gain (in dB) from the tags of the file,
+/// read for the formats of the codec features enabled
+#[cfg(feature = "replay-gain")]
+pub fn replay_gain(path: &str) -> Option<f32> {
+    let file = File::open(path).ok()?;
+    let stream = MediaSourceStream::new(Box::new(file), Default::default());
+    let mut hint = Hint::new();
+    if let Some(ext) = Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        hint.with_extension(ext);
+    }
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            stream,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    // tags may be found either before the container (e.g. ID3v2)
+    // or inside of it
+    if let Some(gain) = probed
+        .metadata
+        .get()
+        .as_ref()
+        .and_then(|metadata| metadata.current())
+        .and_then(track_gain_of)
+    {
+        return Some(gain);
+    }
+    let metadata = probed.format.metadata();
+    metadata.current().and_then(track_gain_of)
+}
+
+/// Read ReplayGain track gain, never found without
+/// the `replay-gain` feature
+#[cfg(not(feature = "replay-gain"))]
+pub fn replay_gain(_path: &str) -> Option<f32> {
+    None
+}
+
+/// Measure RMS loudness (in dBFS) of the beginning of the file
+pub fn scan_loudness(path: &str) -> Option<f32> {
+    let file = BufReader::new(File::open(path).ok()?);
+    let source = Decoder::new(file).ok()?.convert_samples::<f32>();
+    let (sum, count) = source
+        .take_duration(SCAN_LENGTH)
+        .fold((0f64, 0usize), |(sum, count), x| {
+            (sum + (x as f64) * (x as f64), count + 1)
+        });
+    if count == 0 || sum == 0.0 {
+        return None;
+    }
+    let rms = (sum / count as f64).sqrt();
+    Some(20.0 * rms.log10() as f32)
+}
@@ -8,3 +8,10 @@ pub trait Make<T> {
     /// after rust reference coercion
     fn make() -> T;
 }
+
+/// Trait that this structure can
+/// be made with a configuration
+pub trait MakeWith<T, C> {
+    /// Make a new structure with the given configuration
+    fn make_with(config: C) -> T;
+}
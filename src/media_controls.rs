@@ -0,0 +1,125 @@
+use std::{
+    sync::{mpsc::RecvTimeoutError, Arc, Mutex, Weak},
+    thread::spawn,
+    time::Duration,
+};
+
+use souvlaki::{
+    MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
+};
+
+use crate::{
+    asset::{PlaybackState, PlayerStatus},
+    player::Player,
+    poison::Recover,
+    shared_player::SharedPlayer,
+};
+
+/// Longest wait of the watcher for a status, after which it
+/// checks whether the controls are dropped
+const WATCH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Registration of the player in the media controls of
+/// the OS, i.e. SMTC on Windows, MPNowPlayingInfoCenter
+/// on macOS and MPRIS on Linux
+///
+/// Hardware media keys are forwarded to the player right
+/// away, and a helper thread watching the player shows the
+/// song and its state, until the controls are dropped
+pub struct SystemMediaControls {
+    controls: Arc<Mutex<MediaControls>>,
+    player: SharedPlayer,
+}
+
+impl SystemMediaControls {
+    /// Register the player with the given platform config
+    pub fn attach(
+        player: SharedPlayer,
+        config: PlatformConfig,
+    ) -> Result<SystemMediaControls, souvlaki::Error> {
+        let mut controls = MediaControls::new(config)?;
+        let handled = player.clone();
+        controls.attach(move |event| match event {
+            MediaControlEvent::Play => {
+                if handled.hot().read().is_active() {
+                    handled.resume();
+                } else {
+                    handled.play();
+                }
+            }
+            MediaControlEvent::Pause => {
+                handled.pause();
+            }
+            MediaControlEvent::Toggle => {
+                if handled.hot().read().is_active() {
+                    handled.toggle();
+                } else {
+                    handled.play();
+                }
+            }
+            MediaControlEvent::Next => {
+                handled.skip();
+            }
+            MediaControlEvent::Previous => {
+                handled.previous();
+            }
+            MediaControlEvent::Stop => {
+                handled.stop();
+            }
+            _ => {}
+        })?;
+        let controls = Arc::new(Mutex::new(controls));
+        let mut res = SystemMediaControls { controls, player };
+        res.update()?;
+        watch(&res.player, Arc::downgrade(&res.controls));
+        Ok(res)
+    }
+
+    /// Reflect current song and its state to the OS right
+    /// away, without waiting for the watcher
+    pub fn update(&mut self) -> Result<(), souvlaki::Error> {
+        show(&mut self.controls.lock().recover(), &self.player.snapshot())
+    }
+}
+
+/// Show the status in the controls until they're dropped
+/// or the player is gone
+fn watch(player: &SharedPlayer, controls: Weak<Mutex<MediaControls>>) {
+    let Ok(statuses) = player.watch().join() else {
+        return;
+    };
+    spawn(move || loop {
+        let status = match statuses.recv_timeout(WATCH_TIMEOUT) {
+            Ok(status) => Some(status),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        let Some(controls) = controls.upgrade() else {
+            return;
+        };
+        if let Some(status) = status {
+            let _ = show(&mut controls.lock().recover(), &status);
+        }
+    });
+}
+
+/// Show the song of the status, with its artist and album,
+/// and whether it's playing
+fn show(controls: &mut MediaControls, status: &PlayerStatus) -> Result<(), souvlaki::Error> {
+    let song = match (&status.song, status.state) {
+        (Some(song), PlaybackState::PLAYING | PlaybackState::PAUSED) => song,
+        _ => return controls.set_playback(MediaPlayback::Stopped),
+    };
+    controls.set_metadata(MediaMetadata {
+        title: Some(&song.name),
+        artist: song.artist.as_deref(),
+        album: song.album.as_deref(),
+        duration: Some(status.duration),
+        ..Default::default()
+    })?;
+    let progress = Some(MediaPosition(status.position));
+    controls.set_playback(match status.state {
+        PlaybackState::PAUSED => MediaPlayback::Paused { progress },
+        _ => MediaPlayback::Playing { progress },
+    })
+}
@@ -0,0 +1,45 @@
+use livesplit_hotkey::{Hook, Hotkey, KeyCode, Modifiers};
+
+use crate::{player::Player, shared_player::SharedPlayer};
+
+/// Global media keys of the desktop, i.e. play/pause, next,
+/// previous and stop, mapped to the player even while the
+/// app isn't focused; they're released once it's dropped
+///
+/// Unlike `SystemMediaControls` of `media-controls`,
+/// the player isn't shown by the OS, only its keys are taken
+pub struct MediaKeys {
+    _hook: Hook,
+}
+
+impl MediaKeys {
+    /// Listen for the media keys on a thread of the hook
+    pub fn listen(player: SharedPlayer) -> Result<MediaKeys, livesplit_hotkey::Error> {
+        let hook = Hook::new()?;
+        let key = |key_code| Hotkey {
+            key_code,
+            modifiers: Modifiers::empty(),
+        };
+        let handled = player.clone();
+        hook.register(key(KeyCode::MediaPlayPause), move || {
+            // start the waiting list if nothing is playing
+            if handled.hot().read().is_active() {
+                handled.toggle();
+            } else {
+                handled.play();
+            }
+        })?;
+        let handled = player.clone();
+        hook.register(key(KeyCode::MediaTrackNext), move || {
+            handled.skip();
+        })?;
+        let handled = player.clone();
+        hook.register(key(KeyCode::MediaTrackPrevious), move || {
+            handled.previous();
+        })?;
+        hook.register(key(KeyCode::MediaStop), move || {
+            player.stop();
+        })?;
+        Ok(MediaKeys { _hook: hook })
+    }
+}
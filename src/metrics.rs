@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+/// Counters and gauges of a player, for monitoring it
+/// like any other service, see `Player::metrics`
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlayerMetrics {
+    /// Songs played, to the end or skipped
+    pub songs_played: u64,
+    /// Songs skipped in the middle
+    pub songs_skipped: u64,
+    /// Songs failed to be opened or decoded
+    pub decode_errors: u64,
+    /// Times the output stopped consuming a song,
+    /// e.g. the device is unplugged or starved
+    pub underruns: u64,
+    /// Number of songs in waiting list
+    pub waiting: usize,
+    /// Number of songs in played history
+    pub played: usize,
+    /// Whether a song is playing
+    pub playing: bool,
+    pub volume: f32,
+}
+
+impl PlayerMetrics {
+    /// Render in the text format of Prometheus, each
+    /// metric named with the prefix `super_rodio_`
+    pub fn to_prometheus(&self) -> String {
+        let metrics = [
+            (
+                "songs_played_total",
+                "counter",
+                "Songs played",
+                self.songs_played as f64,
+            ),
+            (
+                "songs_skipped_total",
+                "counter",
+                "Songs skipped",
+                self.songs_skipped as f64,
+            ),
+            (
+                "decode_errors_total",
+                "counter",
+                "Songs failed to be opened or decoded",
+                self.decode_errors as f64,
+            ),
+            (
+                "underruns_total",
+                "counter",
+                "Stalls of the output",
+                self.underruns as f64,
+            ),
+            (
+                "waiting_songs",
+                "gauge",
+                "Songs in waiting list",
+                self.waiting as f64,
+            ),
+            (
+                "played_songs",
+                "gauge",
+                "Songs in played history",
+                self.played as f64,
+            ),
+            (
+                "playing",
+                "gauge",
+                "Whether a song is playing",
+                self.playing as u8 as f64,
+            ),
+            (
+                "volume",
+                "gauge",
+                "Volume of the player",
+                self.volume as f64,
+            ),
+        ];
+        let mut text = String::new();
+        for (name, kind, help, value) in metrics {
+            let _ = writeln!(text, "# HELP super_rodio_{} {}", name, help);
+            let _ = writeln!(text, "# TYPE super_rodio_{} {}", name, kind);
+            let _ = writeln!(text, "super_rodio_{} {}", name, value);
+        }
+        text
+    }
+}
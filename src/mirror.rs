@@ -0,0 +1,274 @@
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{channel, Sender},
+        Arc, Mutex, Weak,
+    },
+    thread::{spawn, JoinHandle},
+    time::Duration,
+};
+
+use rodio::{source::SeekError, OutputStream, OutputStreamHandle, Sink, Source};
+
+use crate::poison::Recover;
+
+/// Samples passed to the mirrors at once
+const BATCH: usize = 1024;
+
+/// Most audio kept for a mirror falling behind the
+/// main output, the oldest samples are dropped over it
+const MIRROR_BUFFER: Duration = Duration::from_millis(500);
+
+/// Interval of following the volume of the main output
+const MIRROR_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Samples of the song playing on the main output, on
+/// their way to a mirror
+#[derive(Default)]
+pub(crate) struct Feed {
+    samples: Mutex<VecDeque<f32>>,
+    /// Set once the song is over or stopped
+    done: AtomicBool,
+}
+
+/// Another output playing what the main one plays,
+/// e.g. a network sink along with the speakers
+pub(crate) struct Mirror {
+    pub name: String,
+    sink: Sink,
+    keeper: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Mirror {
+    pub fn open(
+        name: String,
+        gen_out: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+    ) -> io::Result<Mirror> {
+        let (handle_tx, handle_rx) = channel();
+        let (keeper, dropped) = channel::<()>();
+        let thread = spawn(move || {
+            let (_stream, handle) = gen_out();
+            let _ = handle_tx.send(handle);
+            // wait until the mirror is dropped
+            let _ = dropped.recv();
+        });
+        let handle = handle_rx
+            .recv()
+            .map_err(|_| io::Error::other("failed to open output of the mirror"))?;
+        Ok(Mirror {
+            name,
+            sink: Sink::try_new(&handle).map_err(io::Error::other)?,
+            keeper: Some(keeper),
+            thread: Some(thread),
+        })
+    }
+
+    /// Volume of this output on top of the player volume
+    pub fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+    }
+
+    /// Play the samples fed in the format, at the volume
+    /// of the main sink
+    pub fn play(&self, channels: u16, sample_rate: u32, main: Weak<Sink>) -> Arc<Feed> {
+        let feed = Arc::new(Feed::default());
+        self.sink
+            .append(Branch::new(Arc::clone(&feed), channels, sample_rate, main));
+        feed
+    }
+}
+
+impl Drop for Mirror {
+    fn drop(&mut self) {
+        // release the stream and wait for it
+        self.sink.stop();
+        self.keeper = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Source adapter passing a copy of the samples to
+/// the feeds of the mirrors
+pub(crate) struct Mirrored<S> {
+    input: S,
+    feeds: Vec<Arc<Feed>>,
+    buffer: Vec<f32>,
+    limit: usize,
+}
+
+impl<S> Mirrored<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, feeds: Vec<Arc<Feed>>) -> Mirrored<S> {
+        let frames = input.sample_rate() as f32 * MIRROR_BUFFER.as_secs_f32();
+        Mirrored {
+            limit: frames as usize * input.channels() as usize,
+            input,
+            feeds,
+            buffer: Vec::with_capacity(BATCH),
+        }
+    }
+}
+
+impl<S> Mirrored<S> {
+    fn flush(&mut self) {
+        for feed in &self.feeds {
+            let mut samples = feed.samples.lock().recover();
+            samples.extend(&self.buffer);
+            let over = samples.len().saturating_sub(self.limit);
+            samples.drain(..over);
+        }
+        self.buffer.clear();
+    }
+
+    fn finish(&mut self) {
+        self.flush();
+        for feed in &self.feeds {
+            feed.done.store(true, Ordering::Release);
+        }
+    }
+}
+
+impl<S> Iterator for Mirrored<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.feeds.is_empty() {
+            return self.input.next();
+        }
+        match self.input.next() {
+            Some(sample) => {
+                self.buffer.push(sample);
+                // whole frames only, to keep the channels in order
+                let channels = self.input.channels() as usize;
+                if self.buffer.len() >= BATCH && self.buffer.len().is_multiple_of(channels) {
+                    self.flush();
+                }
+                Some(sample)
+            }
+            None => {
+                self.finish();
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Mirrored<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        // the mirrors jump along, dropping what's left
+        self.buffer.clear();
+        for feed in &self.feeds {
+            feed.samples.lock().recover().clear();
+        }
+        self.input.try_seek(pos)
+    }
+}
+
+impl<S> Drop for Mirrored<S> {
+    fn drop(&mut self) {
+        // a skipped or stopped song ends on the mirrors too
+        self.finish();
+    }
+}
+
+/// Source of a mirror playing the samples fed, silent
+/// while the main output is paused
+pub(crate) struct Branch {
+    feed: Arc<Feed>,
+    pending: VecDeque<f32>,
+    channels: u16,
+    sample_rate: u32,
+    main: Weak<Sink>,
+    volume: f32,
+    left: usize,
+}
+
+impl Branch {
+    pub fn new(feed: Arc<Feed>, channels: u16, sample_rate: u32, main: Weak<Sink>) -> Branch {
+        Branch {
+            feed,
+            pending: VecDeque::new(),
+            channels,
+            sample_rate,
+            main,
+            volume: 1.0,
+            left: 0,
+        }
+    }
+}
+
+impl Iterator for Branch {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.left == 0 {
+            self.volume = self.main.upgrade().map_or(1.0, |sink| sink.volume());
+            let frames = self.sample_rate as f32 * MIRROR_INTERVAL.as_secs_f32();
+            self.left = (frames as usize).max(1) * self.channels as usize;
+        }
+        let frame_start = self.left.is_multiple_of(self.channels as usize);
+        self.left -= 1;
+        if self.pending.is_empty() && frame_start {
+            // checked before taking the samples, so that
+            // none is lost if the song ends meanwhile
+            let done = self.feed.done.load(Ordering::Acquire);
+            std::mem::swap(&mut self.pending, &mut *self.feed.samples.lock().recover());
+            if self.pending.is_empty() {
+                return if done { None } else { Some(0.0) };
+            }
+        }
+        // a frame started in silence ends in silence
+        Some(self.pending.pop_front().unwrap_or(0.0) * self.volume)
+    }
+}
+
+impl Source for Branch {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
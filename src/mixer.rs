@@ -0,0 +1,297 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    io,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use rodio::{source::EmptyCallback, Sink, Source};
+
+use crate::{
+    bus::Inputs,
+    cache::SharedCache,
+    channels::SharedF32,
+    decode::decode_song,
+    ducking::{with_ducking, Priority, SharedDucking},
+    poison::Recover,
+    sidechain::{with_sidechain, ChannelLevel, Meter, SharedSidechains, Sidechain},
+    song::Song,
+    spatial::{Positions, SpatialHandle},
+};
+
+/// Interval of applying a change of the master volume
+const MASTER_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Scale the source by the master volume, following
+/// its changes while playing
+pub(crate) fn with_master<S>(source: S, master: Arc<SharedF32>) -> impl Source<Item = f32> + Send
+where
+    S: Source<Item = f32> + Send,
+{
+    source
+        .amplify(master.get())
+        .periodic_access(MASTER_INTERVAL, move |source| {
+            source.set_factor(master.get())
+        })
+}
+
+/// State shared by the sounds of a mixer
+#[derive(Clone)]
+struct Shared {
+    cache: SharedCache,
+    master: Arc<SharedF32>,
+    ducking: SharedDucking,
+    sidechains: SharedSidechains,
+}
+
+impl Shared {
+    /// Decode the song with its own gain, the master volume
+    /// and the ducking under sounds of higher priorities
+    fn decode(
+        &self,
+        song: &Song,
+        priority: Priority,
+    ) -> io::Result<impl Source<Item = f32> + Send> {
+        let source = decode_song(song, &Default::default(), Default::default(), &self.cache)?;
+        let source = source.amplify(song.gain.unwrap_or(1.0));
+        let source = with_master(source, Arc::clone(&self.master));
+        Ok(with_ducking(source, priority, Arc::clone(&self.ducking)))
+    }
+}
+
+/// A named channel of the player, e.g. "music" or "sfx",
+/// playing its own queue with its own volume, mixed with
+/// the other channels into the output of the player
+#[derive(Clone)]
+pub struct Channel {
+    name: String,
+    sink: Arc<Sink>,
+    // songs not finished yet, the first one is playing
+    queue: Arc<Mutex<VecDeque<(u64, Song)>>>,
+    priority: Arc<Mutex<Priority>>,
+    level: Arc<ChannelLevel>,
+    // ids of the songs of all channels
+    next_id: Arc<AtomicU64>,
+    shared: Shared,
+}
+
+impl Channel {
+    fn new(name: &str, inputs: &Inputs, next_id: Arc<AtomicU64>, shared: Shared) -> Channel {
+        Channel {
+            name: name.to_string(),
+            sink: inputs.sink(),
+            queue: Default::default(),
+            priority: Default::default(),
+            level: Default::default(),
+            next_id,
+            shared,
+        }
+    }
+
+    /// Name of this channel
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn priority(&self) -> Priority {
+        *self.priority.lock().recover()
+    }
+
+    /// Set the priority of songs added from now on,
+    /// unless they have their own
+    pub fn set_priority(&self, priority: Priority) {
+        *self.priority.lock().recover() = priority;
+    }
+
+    /// Add a song to the channel, it's played once
+    /// the songs added before are over
+    pub fn add(&self, song: Song) -> io::Result<()> {
+        let priority = song.priority.unwrap_or_else(|| self.priority());
+        let source = Meter::new(
+            self.shared.decode(&song, priority)?,
+            Arc::clone(&self.level),
+        );
+        let sidechains = Arc::clone(&self.shared.sidechains);
+        let source = with_sidechain(source, self.name.clone(), sidechains);
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.queue.lock().recover().push_back((id, song));
+        let ducking = Arc::clone(&self.shared.ducking);
+        self.sink
+            .append(EmptyCallback::<f32>::new(Box::new(move || {
+                ducking.start(id, priority);
+            })));
+        self.sink.append(source);
+        // remove the song from the queue once it's over
+        let (queue, ducking) = (Arc::clone(&self.queue), Arc::clone(&self.shared.ducking));
+        self.sink
+            .append(EmptyCallback::<f32>::new(Box::new(move || {
+                queue.lock().recover().retain(|(i, _)| *i != id);
+                ducking.end(id);
+            })));
+        Ok(())
+    }
+
+    /// Songs not finished yet, the first one is playing
+    pub fn waiting_list(&self) -> Vec<Song> {
+        let queue = self.queue.lock().recover();
+        queue.iter().map(|(_, song)| song.clone()).collect()
+    }
+
+    /// Check whether all songs of this channel are over
+    pub fn is_empty(&self) -> bool {
+        self.sink.empty()
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.sink.volume()
+    }
+
+    /// Set volume of this channel only, scaled by
+    /// the master volume of the player
+    pub fn set_volume(&self, volume: f32) {
+        self.sink.set_volume(volume);
+        self.level.volume.set(volume);
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause();
+        // a paused channel ducks nothing
+        self.level.peak.set(0.0);
+    }
+
+    pub fn resume(&self) {
+        self.sink.play();
+    }
+
+    /// Skip the playing song of this channel
+    pub fn skip(&self) {
+        self.sink.skip_one();
+    }
+
+    /// Stop and remove all songs of this channel
+    pub fn stop(&self) {
+        self.sink.clear();
+        for (id, _) in self.queue.lock().recover().drain(..) {
+            self.shared.ducking.end(id);
+        }
+        self.sink.play();
+    }
+}
+
+/// Named channels of the player, mixed into its output
+pub struct Mixer {
+    inputs: Inputs,
+    channels: HashMap<String, Channel>,
+    next_id: Arc<AtomicU64>,
+    shared: Shared,
+}
+
+impl Mixer {
+    pub(crate) fn new(
+        inputs: Inputs,
+        cache: SharedCache,
+        master: Arc<SharedF32>,
+        ducking: SharedDucking,
+    ) -> Mixer {
+        Mixer {
+            inputs,
+            channels: HashMap::new(),
+            next_id: Default::default(),
+            shared: Shared {
+                cache,
+                master,
+                ducking,
+                sidechains: Default::default(),
+            },
+        }
+    }
+
+    /// Output of the mixer for sounds detached from any
+    /// channel, which can be used without the mixer
+    pub fn outlet(&self) -> Outlet {
+        Outlet {
+            inputs: self.inputs.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Duck the target channel by the amount, 1.0 to silence
+    /// it, while the source channel is audible; the volume
+    /// glides down in `attack` and back in `release`
+    pub fn set_ducking(
+        &mut self,
+        source: &str,
+        target: &str,
+        amount: f32,
+        attack: Duration,
+        release: Duration,
+    ) {
+        let level = Arc::clone(&self.channel(source).level);
+        self.channel(target);
+        let mut sidechains = self.shared.sidechains.lock().recover();
+        sidechains.retain(|sidechain| sidechain.source != source || sidechain.target != target);
+        if amount > 0.0 {
+            sidechains.push(Sidechain {
+                source: source.to_string(),
+                target: target.to_string(),
+                level,
+                amount: amount.min(1.0),
+                attack,
+                release,
+            });
+        }
+    }
+
+    /// Get the channel with the given name, which
+    /// is created if it doesn't exist
+    pub fn channel(&mut self, name: &str) -> Channel {
+        if let Some(channel) = self.channels.get(name) {
+            return channel.clone();
+        }
+        let next_id = Arc::clone(&self.next_id);
+        let channel = Channel::new(name, &self.inputs, next_id, self.shared.clone());
+        self.channels.insert(name.to_string(), channel.clone());
+        channel
+    }
+}
+
+/// Output of a mixer playing sounds detached from any channel
+pub(crate) struct Outlet {
+    inputs: Inputs,
+    shared: Shared,
+}
+
+impl Outlet {
+    /// Play the song right away, detached from any channel
+    pub fn play_once(&self, song: &Song) -> io::Result<()> {
+        let source = self
+            .shared
+            .decode(song, song.priority.unwrap_or_default())?;
+        self.inputs.play(source);
+        Ok(())
+    }
+
+    /// Play the song at the emitter position, heard by
+    /// the ears at the given positions
+    pub fn play_spatial(
+        &self,
+        song: &Song,
+        emitter: [f32; 3],
+        left_ear: [f32; 3],
+        right_ear: [f32; 3],
+    ) -> io::Result<SpatialHandle> {
+        let source = self
+            .shared
+            .decode(song, song.priority.unwrap_or_default())?;
+        let sink = self.inputs.sink();
+        let positions = Positions {
+            emitter,
+            left_ear,
+            right_ear,
+        };
+        Ok(SpatialHandle::new(sink, source, positions))
+    }
+}
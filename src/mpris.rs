@@ -0,0 +1,360 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+use zbus::{
+    blocking::{connection, Connection},
+    fdo, interface,
+    zvariant::{ObjectPath, OwnedValue, Value},
+    SignalContext,
+};
+
+use crate::{
+    player::Player,
+    shared_player::SharedPlayer,
+    song::{ActiveSong, Song, SongState},
+    uri::SongUri,
+};
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+
+/// Id of the track playing, the only one known to clients
+const TRACK_ID: &str = "/org/super_rodio/CurrentTrack";
+
+/// `org.mpris.MediaPlayer2` interface
+struct MprisRoot {
+    identity: String,
+}
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl MprisRoot {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        self.identity.clone()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec!["file".into()]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// `org.mpris.MediaPlayer2.Player` interface
+/// forwarding calls to the shared player
+struct MprisPlayer {
+    player: SharedPlayer,
+}
+
+impl MprisPlayer {
+    fn is_active(&self) -> bool {
+        self.player.hot().read().is_active()
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl MprisPlayer {
+    fn next(&self) {
+        self.player.skip();
+    }
+
+    fn previous(&self) {
+        self.player.previous();
+    }
+
+    fn pause(&self) {
+        self.player.pause();
+    }
+
+    fn play_pause(&self) {
+        if self.is_active() {
+            self.player.toggle();
+        } else {
+            self.player.play();
+        }
+    }
+
+    fn stop(&self) {
+        self.player.stop();
+    }
+
+    fn play(&self) {
+        if self.is_active() {
+            self.player.resume();
+        } else {
+            self.player.play();
+        }
+    }
+
+    /// Seek by the offset in microseconds, backward if it's
+    /// negative
+    async fn seek(
+        &self,
+        offset: i64,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> fdo::Result<()> {
+        if !self.is_active() {
+            return Ok(());
+        }
+        let position = (self.player.position().as_micros() as i64).saturating_add(offset);
+        let _ = self.player.seek(micros(position.max(0))).join();
+        let _ = Self::seeked(&ctxt, position.max(0)).await;
+        Ok(())
+    }
+
+    /// Seek to the position in microseconds, if the track
+    /// is still the one playing
+    async fn set_position(
+        &self,
+        track_id: ObjectPath<'_>,
+        position: i64,
+        #[zbus(signal_context)] ctxt: SignalContext<'_>,
+    ) -> fdo::Result<()> {
+        let current = self.player.current_song_now();
+        let length = current.duration.as_micros() as i64;
+        if track_id.as_str() != TRACK_ID
+            || current.song.is_none()
+            || position < 0
+            || position > length
+        {
+            return Ok(());
+        }
+        let _ = self.player.seek(micros(position)).join();
+        let _ = Self::seeked(&ctxt, position).await;
+        Ok(())
+    }
+
+    /// Play the `file://` URI right away
+    fn open_uri(&self, uri: String) -> fdo::Result<()> {
+        let not_supported = || fdo::Error::NotSupported(format!("can't open {:.64}", uri));
+        if !uri
+            .get(..7)
+            .is_some_and(|scheme| scheme.eq_ignore_ascii_case("file://"))
+        {
+            return Err(not_supported());
+        }
+        let Ok(SongUri::FILE(path)) = SongUri::parse(&uri) else {
+            return Err(not_supported());
+        };
+        let name = std::path::Path::new(path.as_ref())
+            .file_stem()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let id = self
+            .player
+            .add(Song::from(name, uri))
+            .join()
+            .map_err(|_| fdo::Error::Failed("the player is shut down".into()))?;
+        self.player.play_song(id);
+        Ok(())
+    }
+
+    #[zbus(signal)]
+    async fn seeked(ctxt: &SignalContext<'_>, position: i64) -> zbus::Result<()>;
+
+    /// Position in microseconds, read by clients when
+    /// they need it, so it's not signalled
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        self.player.position().as_micros() as i64
+    }
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        playback_status(&self.player.current_song_now()).into()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        metadata(&self.player.current_song_now())
+    }
+
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.player.state().read().volume as f64
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+fn micros(micros: i64) -> Duration {
+    Duration::from_micros(micros as u64)
+}
+
+fn playback_status(current: &ActiveSong) -> &'static str {
+    match current.state {
+        SongState::PLAY => "Playing",
+        SongState::PAUSE => "Paused",
+        _ => "Stopped",
+    }
+}
+
+fn metadata(current: &ActiveSong) -> HashMap<String, OwnedValue> {
+    let mut map = HashMap::new();
+    let Some(song) = &current.song else {
+        return map;
+    };
+    let mut insert = |key: &str, value: Value| {
+        if let Ok(value) = OwnedValue::try_from(value) {
+            map.insert(key.to_string(), value);
+        }
+    };
+    insert(
+        "mpris:trackid",
+        Value::from(ObjectPath::from_static_str_unchecked(TRACK_ID)),
+    );
+    insert(
+        "mpris:length",
+        Value::from(current.duration.as_micros() as i64),
+    );
+    insert("xesam:title", Value::from(song.name.as_str()));
+    if let Some(url) = song.url() {
+        insert("xesam:url", Value::from(url));
+    }
+    map
+}
+
+/// Running MPRIS service, stopped when dropped
+pub struct MprisServer {
+    connection: Connection,
+    stopped: Arc<AtomicBool>,
+}
+
+impl MprisServer {
+    /// D-Bus connection serving the player
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+impl Drop for MprisServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Expose the player on the session bus as
+/// `org.mpris.MediaPlayer2.<name>`
+///
+/// A helper thread emits `PropertiesChanged` whenever
+/// the song or its state changes, until the returned
+/// server is dropped
+pub fn serve_mpris(player: SharedPlayer, name: &str) -> zbus::Result<MprisServer> {
+    let connection = connection::Builder::session()?
+        .name(format!("org.mpris.MediaPlayer2.{}", name))?
+        .serve_at(
+            OBJECT_PATH,
+            MprisRoot {
+                identity: name.to_string(),
+            },
+        )?
+        .serve_at(
+            OBJECT_PATH,
+            MprisPlayer {
+                player: player.clone(),
+            },
+        )?
+        .build()?;
+
+    let stopped = Arc::new(AtomicBool::new(false));
+    let watched = connection.clone();
+    let watch_stopped = Arc::clone(&stopped);
+    spawn(move || {
+        let Ok(iface) = watched
+            .object_server()
+            .interface::<_, MprisPlayer>(OBJECT_PATH)
+        else {
+            return;
+        };
+        let mut last = (String::new(), String::new());
+        while !watch_stopped.load(Ordering::Relaxed) {
+            let current = player.current_song_now();
+            let status = playback_status(&current).to_string();
+            let name = current.song.map(|song| song.name).unwrap_or_default();
+            if (&status, &name) != (&last.0, &last.1) {
+                let ctxt = iface.signal_context();
+                let player_iface = iface.get();
+                let _ = zbus::block_on(player_iface.playback_status_changed(ctxt));
+                let _ = zbus::block_on(player_iface.metadata_changed(ctxt));
+                last = (status, name);
+            }
+            sleep(Duration::from_millis(500));
+        }
+    });
+    Ok(MprisServer {
+        connection,
+        stopped,
+    })
+}
@@ -0,0 +1,72 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{sleep, spawn, JoinHandle},
+    time::Duration,
+};
+
+use rodio::Source;
+
+use crate::bus::Port;
+
+/// Interval of each batch of samples consumed
+const TICK: Duration = Duration::from_millis(10);
+
+/// Output backend consuming sources without any device,
+/// for running players in CI and tests
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct NullOutput {
+    /// Playing speed relative to real time,
+    /// e.g. `100.0` plays a 10s song in 0.1s
+    pub speed: f32,
+}
+
+impl Default for NullOutput {
+    fn default() -> Self {
+        NullOutput { speed: 1.0 }
+    }
+}
+
+impl NullOutput {
+    pub fn new(speed: f32) -> NullOutput {
+        NullOutput { speed }
+    }
+
+    /// Drain the port by a helper thread, which
+    /// stops once the returned stream is dropped
+    pub(crate) fn open(&self, mut output: Port) -> NullStream {
+        let stopped = Arc::new(AtomicBool::new(false));
+        let speed = self.speed.max(0.0);
+        let thread_stopped = Arc::clone(&stopped);
+        let thread = spawn(move || {
+            while !thread_stopped.load(Ordering::Relaxed) {
+                let per_sec = output.sample_rate() as f32 * output.channels() as f32;
+                let batch = (per_sec * TICK.as_secs_f32() * speed).ceil() as usize;
+                output.by_ref().take(batch).for_each(drop);
+                sleep(TICK);
+            }
+        });
+        NullStream {
+            stopped,
+            thread: Some(thread),
+        }
+    }
+}
+
+/// Stream of a [`NullOutput`], which keeps consuming
+/// the port until dropped
+pub(crate) struct NullStream {
+    stopped: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for NullStream {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
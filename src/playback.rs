@@ -0,0 +1,641 @@
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::{self, sleep, spawn},
+    time::{Duration, Instant, SystemTime},
+};
+
+use rodio::{source::Zero, Sink, Source};
+
+use crate::{
+    asset::{PlaybackMode, PlayerAsset, SharedHot},
+    bus::Output,
+    channels::{Balance, Downmix},
+    decode::{decode_song, decode_uri},
+    ducking::with_ducking,
+    effect::BoxedSource,
+    equalizer::Equalizer,
+    event::PlayerEvent,
+    fade::FadeOut,
+    logging::log,
+    mirror::Mirrored,
+    mixer::with_master,
+    reply::Responder,
+    shared_player::SharedState,
+    silence::TrimSilence,
+    slice::Slice,
+    song::{ActiveSong, Song, SongState},
+    stall::Pulling,
+    uri::SongUri,
+};
+
+#[cfg(feature = "time-stretch")]
+use crate::stretch::{Resample, TimeStretch};
+
+/// Interval of checking the progress of the playing sink
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Interval of checking the progress when looping
+/// a region, for a more precise loop point
+const LOOP_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Songs no longer than this are never scrobbled
+const SCROBBLE_MIN_LENGTH: Duration = Duration::from_secs(30);
+
+/// Time heard to scrobble a song longer than 8 minutes
+const SCROBBLE_MAX_WAIT: Duration = Duration::from_secs(240);
+
+/// Time without any progress to consider the output lost
+const STALL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Open an output playing the mix of the player, from
+/// the device maker or the null output if it's used
+pub(crate) fn open_output(state: &SharedState) -> io::Result<Output> {
+    let (port, maker) = {
+        let state = state.read();
+        #[cfg(feature = "test-backend")]
+        if let Some(output) = state.null_output {
+            log!(debug, "opened the null output");
+            return Ok(Output::null(output, state.bus.port()));
+        }
+        (state.bus.port(), state.output_maker.clone())
+    };
+    let output = Output::device(maker, port)?;
+    log!(info, "opened the output device");
+    Ok(output)
+}
+
+/// Open a new output playing the mix in place of the former
+/// one, which is closed once the new one takes over; the
+/// sounds playing go on where they are
+pub(crate) fn attach_output(state: &SharedState) -> io::Result<()> {
+    let output = open_output(state)?;
+    let former = state.write().bus.attach(output);
+    drop(former);
+    Ok(())
+}
+
+/// Open a new output and place a new sink on the mix for the songs
+fn open_sink(state: &SharedState) -> io::Result<()> {
+    attach_output(state)?;
+    // acquire write lock to place a new sink
+    let state = state.read();
+    state.hot.write().sink = Some(state.bus.inputs.sink());
+    state.apply_speed();
+    Ok(())
+}
+
+/// Close the output once the play loop is over,
+/// unless the channels are still using it, and let
+/// another loop be started
+fn end_loop(state: &SharedState) {
+    let output = {
+        let mut state = state.write();
+        state.loop_running = false;
+        state.stop_after_current = false;
+        match state.mixer {
+            Some(_) => None,
+            None => state.bus.detach(),
+        }
+    };
+    drop(output);
+}
+
+/// Give up the output which can't be opened, so that the
+/// play loop ends once the song playing is over
+fn output_failed(state: &SharedState, error: io::Error) {
+    log!(error, "failed to open the output: {}", error);
+    let mut state = state.write();
+    state.hot.write().sink = None;
+    state.stop_after_current = true;
+    state.emit(PlayerEvent::OutputFailed {
+        error: error.to_string(),
+    });
+}
+
+/// Decode the song and pass it through the processing chain
+pub(crate) fn open_source(state: &SharedState, song: &Song) -> io::Result<BoxedSource> {
+    let (buffering, decoder, cache, fade_in, fade_out, ducking) = {
+        let state = state.read();
+        (
+            state.buffering,
+            state.decoder,
+            Arc::clone(&state.cache),
+            state.fade_in,
+            state.fade_out,
+            Arc::clone(&state.ducking),
+        )
+    };
+    // the path is resolved once, for decoding and loudness
+    let uri = match &song.source {
+        Some(_) => None,
+        None => Some(SongUri::parse(&song.path)?),
+    };
+    let file = match &uri {
+        Some(SongUri::FILE(path)) => Some(path.to_string()),
+        _ => None,
+    };
+    let source = match uri {
+        Some(uri) => decode_uri(uri, &buffering, song.decoder.unwrap_or(decoder), &cache)?,
+        None => decode_song(song, &buffering, decoder, &cache)?,
+    };
+    let (eq_bands, balance, mono, normalization, silence_trim, master) = {
+        let state = state.read();
+        (
+            Arc::clone(&state.eq_bands),
+            Arc::clone(&state.balance),
+            Arc::clone(&state.mono),
+            state.normalization,
+            state.silence_trim,
+            Arc::clone(&state.master_volume),
+        )
+    };
+    // loudness is measured without holding any lock, only
+    // of files
+    let gain = normalization
+        .zip(file)
+        .map(|(normalization, path)| normalization.gain_of(&path))
+        .unwrap_or(1.0);
+    let source = Slice::new(source, song.start_at, song.end_at);
+    let source = TrimSilence::new(source, silence_trim)
+        .amplify(gain)
+        .fade_in(fade_in);
+    let source = FadeOut::new(source, fade_out);
+    let source: BoxedSource = Box::new(Equalizer::new(source, eq_bands));
+    let source = state
+        .write()
+        .effects
+        .iter_mut()
+        .fold(source, |source, effect| effect.process(source));
+    let source = Balance::new(Downmix::new(source, mono), balance);
+    let source = with_master(source, master);
+    let priority = song.priority.unwrap_or_default();
+    Ok(Box::new(with_ducking(source, priority, ducking)))
+}
+
+/// Block until the sink is drained, returns `false` if the
+/// output stops consuming it in the middle, e.g. the device
+/// is unplugged, but not while `pulling` a slow source
+///
+/// The loop region of the player is also
+/// applied here, the title of a live stream
+/// is followed, the progress is added to `listen`
+/// and sent at the progress interval
+fn wait_until_end(
+    state: &SharedState,
+    sink: &Sink,
+    song: &Song,
+    listen: &mut Listen,
+    pulling: &AtomicBool,
+) -> bool {
+    let hot = Arc::clone(&state.read().hot);
+    let stretch = Arc::clone(&hot.read().stretch);
+    let mut last_pos = stretch.song_pos(sink.get_pos());
+    let mut stalled = Duration::ZERO;
+    let mut next_progress = Instant::now() + state.read().progress_interval;
+    let mut last_tick = Instant::now();
+    while !sink.empty() {
+        let (loop_region, progress_interval, pace) = {
+            let state = state.read();
+            (
+                state.loop_region,
+                state.progress_interval,
+                state.output_pace(),
+            )
+        };
+        let mut interval = match loop_region {
+            Some(_) => LOOP_INTERVAL,
+            None => WATCH_INTERVAL,
+        };
+        if !progress_interval.is_zero() {
+            let now = Instant::now();
+            if now >= next_progress {
+                if !sink.is_paused() {
+                    send_progress(state, &hot, sink);
+                }
+                next_progress = now + progress_interval;
+            }
+            interval = interval.min(next_progress - now);
+        }
+        sleep(interval);
+        update_stream_title(state, &hot, song);
+        let mut pos = stretch.song_pos(sink.get_pos());
+        let now = Instant::now();
+        // the time spent playing is heard, not the
+        // progress, so that seeking adds nothing
+        if !sink.is_paused() && pos != last_pos {
+            listen.heard += (now - last_tick).mul_f64(pace);
+        }
+        last_tick = now;
+        scrobble(state, hot.read().current.duration, song, listen);
+        if let Some((start, end)) = loop_region {
+            if pos >= end && sink.try_seek(start).is_ok() {
+                pos = start;
+            }
+        }
+        // a source still decoding keeps the output waiting
+        if sink.is_paused() || pos != last_pos || pulling.load(Ordering::Relaxed) {
+            last_pos = pos;
+            stalled = Duration::ZERO;
+        } else {
+            stalled += interval;
+            if stalled >= STALL_TIMEOUT {
+                log!(warn, "the output stalled at {:?} of {:?}", pos, song.name);
+                return false;
+            }
+        }
+    }
+    true
+}
+
+/// Send the position of the song playing
+fn send_progress(state: &SharedState, hot: &SharedHot, sink: &Sink) {
+    let (position, duration) = {
+        let hot = hot.read();
+        (hot.sink_pos(sink), hot.current.duration)
+    };
+    state
+        .write()
+        .emit(PlayerEvent::Progress { position, duration });
+}
+
+/// How a song is heard in a play of it
+struct Listen {
+    started_at: SystemTime,
+    /// Time the song is played, pauses and seeks excluded
+    heard: Duration,
+    scrobbled: bool,
+}
+
+impl Listen {
+    fn new() -> Listen {
+        Listen {
+            started_at: SystemTime::now(),
+            heard: Duration::ZERO,
+            scrobbled: false,
+        }
+    }
+}
+
+/// Emit a scrobble once the song is heard long enough by
+/// the rules of Last.fm: a song longer than 30 seconds,
+/// heard for half of it or 4 minutes
+fn scrobble(state: &SharedState, duration: Duration, song: &Song, listen: &mut Listen) {
+    let enough = (duration / 2).min(SCROBBLE_MAX_WAIT);
+    if listen.scrobbled || duration <= SCROBBLE_MIN_LENGTH || listen.heard < enough {
+        return;
+    }
+    listen.scrobbled = true;
+    state.write().emit(PlayerEvent::Scrobble {
+        song: song.clone(),
+        started_at: listen.started_at,
+        scrobbled_at: SystemTime::now(),
+        duration,
+    });
+}
+
+/// Publish the title of the live stream if it changed
+fn update_stream_title(state: &SharedState, hot: &SharedHot, song: &Song) {
+    let Some(title) = song
+        .source
+        .as_ref()
+        .and_then(|source| source.stream_title())
+    else {
+        return;
+    };
+    {
+        let mut hot = hot.write();
+        if hot.current.stream_title.as_ref() == Some(&title) {
+            return;
+        }
+        hot.current.stream_title = Some(title.clone());
+    }
+    state.write().emit(PlayerEvent::StreamTitle {
+        song: song.clone(),
+        title,
+    });
+}
+
+/// Open the song, retrying by the retry policy until
+/// it's stopped, skipped or the player shuts down
+fn open_with_retry(state: &SharedState, song: &Song) -> io::Result<BoxedSource> {
+    let retry = state.read().retry;
+    let mut delay = retry.backoff;
+    let mut attempt = 1;
+    loop {
+        match open_source(state, song) {
+            Err(_e) if attempt < retry.max_attempts => {
+                log!(
+                    warn,
+                    "attempt {} to open {:?} failed: {}",
+                    attempt,
+                    song.path,
+                    _e
+                );
+                if !wait_to_retry(state, delay) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::Interrupted,
+                        "the retry is given up",
+                    ));
+                }
+                delay = retry.next_delay(delay);
+                attempt += 1;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Wait for the delay before a retry, returns `false` at
+/// once if the song is stopped or skipped meanwhile
+fn wait_to_retry(state: &SharedState, delay: Duration) -> bool {
+    let given_up =
+        |state: &PlayerAsset| state.halting || state.stop_after_current || state.skipping;
+    let deadline = Instant::now() + delay;
+    let (queue_changed, mut generation) = {
+        let mut state = state.write();
+        state.retrying = true;
+        let queue_changed = Arc::clone(&state.queue_changed);
+        let generation = queue_changed.generation();
+        (queue_changed, generation)
+    };
+    loop {
+        let now = Instant::now();
+        if now >= deadline || given_up(&state.read()) {
+            break;
+        }
+        queue_changed.wait_timeout(generation, deadline - now);
+        generation = queue_changed.generation();
+    }
+    let mut state = state.write();
+    state.retrying = false;
+    !given_up(&state)
+}
+
+/// Mix the song into the output of the current song,
+/// so that both are heard at the same time
+pub fn play_overlapping(state: &SharedState, song: &Song) -> io::Result<()> {
+    let (inputs, volume) = {
+        let state = state.read();
+        let inputs = state.is_active().then(|| state.bus.inputs.clone());
+        (inputs, state.sink_volume(song.gain))
+    };
+    let Some(inputs) = inputs else {
+        return Err(io::Error::new(
+            io::ErrorKind::NotConnected,
+            "no song is playing",
+        ));
+    };
+    let source = open_source(state, song)?.amplify(volume);
+    inputs.play(source);
+    Ok(())
+}
+
+/// Clean up after a play loop panicked, e.g. in an effect,
+/// so that the player can play again
+struct LoopGuard(SharedState);
+
+impl Drop for LoopGuard {
+    fn drop(&mut self) {
+        if !thread::panicking() {
+            return;
+        }
+        log!(error, "the play loop panicked");
+        {
+            let mut state = self.0.write();
+            {
+                let mut hot = state.hot.write();
+                if let Some(sink) = hot.sink.take() {
+                    sink.stop();
+                }
+                hot.current = Default::default();
+            }
+            state.loop_region = None;
+            state.skipping = false;
+            state.stop_after_current = false;
+        }
+        end_loop(&self.0);
+    }
+}
+
+/// Wait for a song to be added in continuous mode, returns
+/// `false` at once if it's stopped or out of the mode
+fn wait_for_song(state: &SharedState) -> bool {
+    let (queue_changed, generation) = {
+        let mut state = state.write();
+        let stopping = std::mem::take(&mut state.stop_after_current);
+        if state.mode != PlaybackMode::CONTINUOUS || state.halting || stopping {
+            return false;
+        }
+        // added after the queue was found empty
+        if !state.waiting_q.is_empty() {
+            return true;
+        }
+        let queue_changed = Arc::clone(&state.queue_changed);
+        let generation = queue_changed.generation();
+        (queue_changed, generation)
+    };
+    queue_changed.wait(generation);
+    true
+}
+
+/// Play silence of the gap between two songs, which a
+/// stop or pause of the sink cuts or holds as a song
+fn play_gap(state: &SharedState, gap: Duration) {
+    if gap.is_zero() {
+        return;
+    }
+    let Some(sink) = state.read().hot.read().sink.clone() else {
+        return;
+    };
+    log!(debug, "gap of {:?} before the next song", gap);
+    sink.append(Zero::<f32>::new(2, 44100).take_duration(gap));
+    while !sink.empty() {
+        sleep(WATCH_INTERVAL);
+    }
+}
+
+/// Start the play loop on its own thread, the reply
+/// is resolved once the loop is over
+pub fn play(state: &SharedState, reply: Responder<()>) {
+    // set before the loop is spawned, so that a play
+    // right after this one can't start a second loop
+    let ended = {
+        let mut state = state.write();
+        match std::mem::replace(&mut state.loop_running, true) {
+            true => None,
+            false => Some(state.play_thread.take()),
+        }
+    };
+    let Some(ended) = ended else {
+        reply.send(());
+        return;
+    };
+    // the loop before is over and only has to exit, it's
+    // joined so that `shutdown` is left with the new one
+    if let Some(ended) = ended {
+        let _ = ended.join();
+    }
+    // acquire an arc for child thread
+    let thread_state = Arc::clone(state);
+    // create a new thread for loading and playing music
+    let handle = spawn(move || {
+        let state = thread_state;
+        let _guard = LoopGuard(Arc::clone(&state));
+        log!(info, "play loop started");
+        if let Err(e) = open_sink(&state) {
+            output_failed(&state, e);
+            end_loop(&state);
+            reply.send(());
+            return;
+        }
+        loop {
+            let (song, resume_at) = {
+                let mut state = state.write();
+                let song = state.next_song();
+                // a song finished to the end leaves no bookmark
+                let bookmark = song.as_ref().and_then(|song| state.take_bookmark(song));
+                (song, state.resume_at.take().or(bookmark))
+            };
+            let song = match song {
+                Some(song) if !state.read().halting => song,
+                None if wait_for_song(&state) => continue,
+                None => {
+                    log!(info, "play loop ended, no song left");
+                    break;
+                }
+                Some(_) => {
+                    log!(info, "play loop ended, shutting down");
+                    break;
+                }
+            };
+            log!(info, "playing {:?} from {:?}", song.name, song.path);
+            let mut failure = None;
+            let mut listen = Listen::new();
+            let pulling = Arc::new(AtomicBool::new(false));
+            let source = match open_with_retry(&state, &song) {
+                Ok(source) => Some(source),
+                Err(e) => {
+                    log!(warn, "failed to open {:?}: {}", song.path, e);
+                    failure = Some(e);
+                    None
+                }
+            };
+            if let Some(source) = source {
+                // clone the sink out so that no lock
+                // is held while the song is playing
+                let (sink, volume) = {
+                    let state = state.read();
+                    let sink = {
+                        // acquire write lock to prepare playing song
+                        let mut hot = state.hot.write();
+                        let duration = source.total_duration().unwrap_or_default();
+                        hot.current = ActiveSong::from(song.clone(), duration);
+                        hot.current.state = SongState::PLAY;
+                        hot.sink.clone().filter(|_| !state.halting)
+                    };
+                    if let Some(sink) = &sink {
+                        // stretched here, not in `open_source`, so
+                        // that overlapping songs keep their speed
+                        #[cfg(feature = "time-stretch")]
+                        let source = {
+                            let stretch = Arc::clone(&state.hot.read().stretch);
+                            Resample::new(TimeStretch::new(source, Arc::clone(&stretch)), stretch)
+                        };
+                        let feeds = state
+                            .mirrors
+                            .iter()
+                            .map(|mirror| {
+                                let (channels, rate) = (source.channels(), source.sample_rate());
+                                mirror.play(channels, rate, Arc::downgrade(sink))
+                            })
+                            .collect();
+                        let source = Mirrored::new(source, feeds);
+                        // assign current song, under the lock so
+                        // that a shutdown can't miss it
+                        sink.append(Pulling::new(source, Arc::clone(&pulling)));
+                    }
+                    (sink, state.sink_volume(song.gain))
+                };
+                if let Some(sink) = sink {
+                    if let Some(pos) = resume_at {
+                        let _ = sink.try_seek(pos);
+                    }
+                    sink.set_volume(volume);
+                    // the lost output is replaced under the
+                    // song, which goes on from where it was
+                    while !wait_until_end(&state, &sink, &song, &mut listen, &pulling) {
+                        state.write().metrics.underruns += 1;
+                        log!(info, "reopening the output for {:?}", song.name);
+                        if let Err(e) = attach_output(&state) {
+                            output_failed(&state, e);
+                            sink.stop();
+                            break;
+                        }
+                    }
+                }
+            }
+            {
+                // acquire write lock to finish end-of-play process
+                let mut state = state.write();
+                {
+                    let mut hot = state.hot.write();
+                    hot.current.progress = hot.current.duration;
+                    hot.current.state = SongState::STOP;
+                    hot.current.song = None;
+                    hot.current.id = None;
+                }
+                state.loop_region = None;
+                let going_back = std::mem::take(&mut state.going_back);
+                match failure {
+                    // a bad song doesn't stop the rest of the queue
+                    Some(e) => {
+                        state.metrics.decode_errors += 1;
+                        state.failed_q.push(song.clone());
+                        state.emit(PlayerEvent::SongFailed {
+                            song: song.clone(),
+                            error: e.to_string(),
+                        });
+                    }
+                    // it's queued again after the previous song
+                    None if going_back => {}
+                    None => {
+                        let skipped = state.skipping;
+                        log!(debug, "finished {:?}, heard {:?}", song.name, listen.heard);
+                        state.metrics.songs_played += 1;
+                        state.metrics.songs_skipped += skipped as u64;
+                        state.record_play(&song, listen.heard, skipped);
+                        state.played_q.push(song.clone());
+                    }
+                }
+            }
+            {
+                // auto play if flag is on or current song
+                // is skipped, otherwise breaks
+                let (to_auto_play, gap) = {
+                    let mut state = state.write();
+                    let skipped = std::mem::take(&mut state.skipping);
+                    let stopping = std::mem::take(&mut state.stop_after_current);
+                    let auto = matches!(state.mode, PlaybackMode::AUTO | PlaybackMode::CONTINUOUS);
+                    // no gap after a skip, or before no song
+                    let gap = match skipped || state.waiting_q.is_empty() {
+                        true => Duration::ZERO,
+                        false => state.track_gap,
+                    };
+                    ((auto || skipped) && !stopping, gap)
+                };
+                if !to_auto_play {
+                    log!(info, "play loop ended");
+                    break;
+                }
+                play_gap(&state, gap);
+            }
+        }
+        end_loop(&state);
+        reply.send(());
+    });
+    state.write().play_thread = Some(handle);
+}
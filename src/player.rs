@@ -1,36 +1,407 @@
-use std::thread::JoinHandle;
+use std::{io, sync::mpsc::Receiver, time::Duration};
 
 use rodio::{OutputStream, OutputStreamHandle};
 
-use crate::song::{ActiveSong, Song};
+use crate::{
+    asset::{PlaybackState, PlayerSnapshot, PlayerStatus, UiState},
+    config::{Buffering, RetryPolicy, SpeedMode},
+    device::{DeviceInfo, StreamConfig},
+    effect::{BoxedSource, Effect},
+    equalizer::{EqBand, EqPreset},
+    event::PlayerEvent,
+    focus::AudioFocus,
+    limiter::Limiter,
+    loudness::Normalization,
+    metrics::PlayerMetrics,
+    mixer::Channel,
+    playlist::Playlist,
+    reply::Reply,
+    silence::SilenceTrim,
+    song::{ActiveSong, Song, SongId},
+    spatial::SpatialHandle,
+    stats::PlayStats,
+};
+
+#[cfg(feature = "test-backend")]
+use crate::null_output::NullOutput;
 
 pub trait Player {
-    /// Add a song to the player
-    fn add(&self, song: Song) -> JoinHandle<()>;
+    /// Add a song to the player, resolved to the id
+    /// assigned to it
+    fn add(&self, song: Song) -> Reply<SongId>;
+    /// Add a source, e.g. synthesized audio, as a song which
+    /// can be played only once
+    fn add_source(&self, name: &str, source: BoxedSource) -> Reply<SongId>;
+    /// Add the playable audio files of the folder in natural
+    /// order, e.g. "2.mp3" before "10.mp3", and those of its
+    /// subfolders if `recursive`, resolved to their ids
+    fn add_folder(&self, path: &str, recursive: bool) -> Reply<io::Result<Vec<SongId>>>;
+    /// Watch the folder, and its subfolders if `recursive`,
+    /// adding the playable audio files appearing in it once
+    /// they're copied completely; `PlayerEvent::SongAutoAdded`
+    /// is sent for each, files already there are left alone
+    fn watch_folder(&self, path: &str, recursive: bool) -> Reply<io::Result<()>>;
+    /// Stop watching the folder, resolved to `false` if
+    /// it isn't watched
+    fn unwatch_folder(&self, path: &str) -> Reply<bool>;
+    /// Replace waiting list with the songs of the playlist,
+    /// which are queued again when all played if it's repeated
+    fn load_playlist(&self, playlist: &Playlist) -> Reply<()>;
+    /// Create an empty playlist managed by the player,
+    /// resolved to `false` if it already exists
+    fn create_playlist(&self, name: &str) -> Reply<bool>;
+    /// Append a song to the named playlist,
+    /// resolved to `false` if there's no such playlist
+    fn add_to_playlist(&self, name: &str, song: Song) -> Reply<bool>;
+    /// Replace waiting list with the named playlist,
+    /// resolved to `false` if there's no such playlist
+    fn queue_playlist(&self, name: &str) -> Reply<bool>;
+    /// Get a copy of the named playlist
+    fn playlist(&self, name: &str) -> Reply<Option<Playlist>>;
+    /// Remove the named playlist from the player
+    fn remove_playlist(&self, name: &str) -> Reply<Option<Playlist>>;
+    /// Get names of playlists managed by the player, sorted
+    fn playlist_names(&self) -> Reply<Vec<String>>;
+    /// Remove the song with the given id from waiting list
+    fn remove(&self, id: SongId) -> Reply<Option<Song>>;
+    /// Move the song with the given id to the index of waiting
+    /// list, resolved to `false` if there's no such song
+    fn move_song(&self, id: SongId, index: usize) -> Reply<bool>;
     /// Get current waiting list
-    fn waiting_list(&self) -> JoinHandle<Vec<Song>>;
+    fn waiting_list(&self) -> Reply<Vec<Song>>;
     /// Get current played history
-    fn played_list(&self) -> JoinHandle<Vec<Song>>;
+    fn played_list(&self) -> Reply<Vec<Song>>;
+    /// Get at most `len` songs of waiting list from `offset`,
+    /// e.g. the window of a huge queue shown by a UI
+    fn waiting_range(&self, offset: usize, len: usize) -> Reply<Vec<Song>>;
+    /// Get at most `len` songs of played history from `offset`
+    fn played_range(&self, offset: usize, len: usize) -> Reply<Vec<Song>>;
+    /// Find the songs whose names or paths contain the text,
+    /// ignoring case, in waiting list and then in played
+    /// history, with their indices in the lists
+    fn find(&self, text: &str) -> Reply<Vec<(usize, Song)>>;
+    /// Find the songs matching the predicate, like `find`
+    fn find_by(&self, predicate: Box<dyn Fn(&Song) -> bool + Send>) -> Reply<Vec<(usize, Song)>>;
     /// Get current active song
-    fn current_song(&self) -> JoinHandle<ActiveSong>;
+    fn current_song(&self) -> Reply<ActiveSong>;
+    /// Get songs failed to be opened or decoded,
+    /// which are skipped when playing
+    fn failed_list(&self) -> Reply<Vec<Song>>;
+    /// Receive events of the player from now on
+    fn subscribe(&self) -> Reply<Receiver<PlayerEvent>>;
+    /// Receive current status, then a new one whenever the
+    /// song, the state or the second of the position changes
+    fn watch(&self) -> Reply<Receiver<PlayerStatus>>;
+    /// Get current waiting list right away, without
+    /// waiting for the commands sent before
+    fn waiting_list_now(&self) -> Vec<Song>;
+    /// Get current played history right away
+    fn played_list_now(&self) -> Vec<Song>;
+    /// Get current active song right away
+    fn current_song_now(&self) -> ActiveSong;
+    /// Check whether the current song is playing right away
+    fn is_playing_now(&self) -> bool;
+    /// Get current waiting list right away, `None` if the
+    /// player stays busy for the lock timeout
+    fn try_waiting_list(&self) -> Option<Vec<Song>>;
+    /// Get current played history right away, or `None`
+    fn try_played_list(&self) -> Option<Vec<Song>>;
+    /// Get current active song right away, or `None`
+    fn try_current_song(&self) -> Option<ActiveSong>;
+    /// Check whether the current song is playing right away, or `None`
+    fn try_is_playing(&self) -> Option<bool>;
+    /// Get the playback state right away, or `None`
+    fn try_playback_state(&self) -> Option<PlaybackState>;
+    /// Get the position of the output in the current song right
+    /// away, exact even while paused or just after a seek
+    fn position(&self) -> Duration;
+    /// Get the state, current song and position at once,
+    /// a short read that never waits for the actor
+    fn snapshot(&self) -> PlayerStatus;
+    /// Get the status, the first `preview` songs of waiting
+    /// list and the output device at once, e.g. for each
+    /// frame of a GUI, a short read that never waits for the actor
+    fn ui_state(&self, preview: usize) -> UiState;
     /// Play the song in waiting list
-    fn play(&self) -> JoinHandle<()>;
+    fn play(&self) -> Reply<()>;
     /// Use normal play mode: playing a single song and stop
-    fn use_normal_play(&self) -> JoinHandle<()>;
+    fn use_normal_play(&self) -> Reply<()>;
     /// Use auto play mode: playing all the songs one-by-one in the playlist
-    fn use_auto_play(&self) -> JoinHandle<()>;
+    fn use_auto_play(&self) -> Reply<()>;
+    /// Use continuous play mode: playing all the songs one-by-one,
+    /// then waiting for more songs to be added until stopped
+    fn use_continuous_play(&self) -> Reply<()>;
     /// Toggle play/pause
-    fn toggle(&self) -> JoinHandle<()>;
+    fn toggle(&self) -> Reply<()>;
+    /// Pause the playing song, nothing happens if it's paused
+    fn pause(&self) -> Reply<()>;
+    /// Resume the paused song, nothing happens if it's playing
+    fn resume(&self) -> Reply<()>;
     /// Stop current music
-    fn stop(&self) -> JoinHandle<()>;
+    fn stop(&self) -> Reply<()>;
+    /// Skip current song and play the next one in waiting list
+    fn skip(&self) -> Reply<()>;
+    /// Go back to the last played song, or restart current
+    /// song if it's played over 3 seconds, like the previous
+    /// button of most players
+    fn previous(&self) -> Reply<()>;
+    /// Stop current song and play the song with the given id
+    /// in waiting list, resolved to `false` if there's no such
+    /// song; the songs before it go as `SkippedSongs` says
+    fn play_song(&self, id: SongId) -> Reply<bool>;
+    /// Stop current song and play the song at the index of
+    /// waiting list, resolved to `false` if there's none
+    fn play_index(&self, index: usize) -> Reply<bool>;
+    /// Set the volume, 1.0 for the original loudness, ramped
+    /// over `DEFAULT_RAMP` so that it doesn't click
+    fn set_volume(&self, volume: f32) -> Reply<()>;
+    /// Ramp the volume to the target over the duration,
+    /// e.g. to fade in or out
+    fn set_volume_smooth(&self, volume: f32, duration: Duration) -> Reply<()>;
+    /// Set the volume scaling the player and all its channels,
+    /// on top of their own volumes
+    fn set_master_volume(&self, volume: f32) -> Reply<()>;
+    /// Silence the player, keeping the volume to be restored
+    /// by `unmute`; the volume can still be set while muted
+    fn mute(&self) -> Reply<()>;
+    /// Restore the volume before `mute`
+    fn unmute(&self) -> Reply<()>;
+    /// Check whether the player is muted
+    fn is_muted(&self) -> Reply<bool>;
+    /// Report a change of the audio focus of the app, e.g.
+    /// a phone call on mobile; the player pauses, ducks or
+    /// resumes, and sends `PlayerEvent::AudioFocusChanged`
+    fn set_audio_focus(&self, focus: AudioFocus) -> Reply<()>;
+    /// Seek current song to the given position
+    fn seek(&self, pos: Duration) -> Reply<()>;
+    /// Seek current song forward by given seconds,
+    /// or backward if it's negative
+    fn seek_by(&self, secs: f32) -> Reply<()>;
+    /// Seek current song to the start of its next chapter
+    fn next_chapter(&self) -> Reply<()>;
+    /// Seek current song to the start of its chapter being
+    /// played, or the previous one within 3 seconds of it
+    fn prev_chapter(&self) -> Reply<()>;
+    /// Seek current song to the start of its chapter at
+    /// the index, nothing happens if there's none
+    fn goto_chapter(&self, index: usize) -> Reply<()>;
+    /// Play the current song between `start` and `end`
+    /// repeatedly, until it's cleared or the song is over
+    fn set_loop_region(&self, start: Duration, end: Duration) -> Reply<()>;
+    /// Stop looping and continue the current song normally
+    fn clear_loop_region(&self) -> Reply<()>;
+    /// Stop playing after the given time, fading out
+    /// during the last `fade_out` if it's given
+    fn stop_after(&self, after: Duration, fade_out: Option<Duration>) -> Reply<()>;
+    /// Stop once the current song is over, even in auto play mode
+    fn stop_after_current(&self) -> Reply<()>;
+    /// Cancel both `stop_after` and `stop_after_current`
+    fn cancel_stop_after(&self) -> Reply<()>;
+    /// Set bands of the equalizer applied to every song
+    fn set_eq(&self, bands: Vec<EqBand>) -> Reply<()>;
+    /// Set the equalizer to one of the presets
+    fn set_eq_preset(&self, preset: EqPreset) -> Reply<()>;
+    /// Normalize loudness of every song, or `None` to disable it
+    fn set_normalization(&self, normalization: Option<Normalization>) -> Reply<()>;
+    /// Set the soft limiter keeping the output from clipping,
+    /// applied to the final mix right away
+    fn set_limiter(&self, limiter: Limiter) -> Reply<()>;
+    /// Set how songs are read and decoded,
+    /// it takes effect from the next song
+    fn set_buffering(&self, buffering: Buffering) -> Reply<()>;
+    /// Set most bytes of decoded samples cached for short
+    /// sounds played again, 0 to disable the cache
+    fn set_cache_budget(&self, budget: usize) -> Reply<()>;
+    /// Set the interval of `PlayerEvent::Progress` while
+    /// a song plays, zero to stop sending it
+    fn set_progress_interval(&self, interval: Duration) -> Reply<()>;
+    /// Set the silence between songs played one after
+    /// another, e.g. in auto mode, zero for none
+    fn set_track_gap(&self, gap: Duration) -> Reply<()>;
+    /// Set the volume ratio, 0.2 by default, of the player
+    /// and the channels ducked while a sound of a higher
+    /// `Priority` plays in a channel
+    fn set_duck_level(&self, level: f32) -> Reply<()>;
+    /// Set the speed of the songs, 1.0 for the original
+    /// one, clamped to 0.25..=4.0
+    fn set_speed(&self, speed: f32) -> Reply<()>;
+    /// Set how the speed of the songs is changed, e.g.
+    /// keeping the pitch by `SpeedMode::STRETCH`
+    fn set_speed_mode(&self, mode: SpeedMode) -> Reply<()>;
+    /// Shift the pitch of the songs by the semitones, up
+    /// to two octaves either way, keeping their speed
+    #[cfg(feature = "time-stretch")]
+    fn set_pitch(&self, semitones: f32) -> Reply<()>;
+    /// Decode a short sound into the cache ahead of time,
+    /// so that it's heard right away when it's played
+    fn preload(&self, song: Song) -> Reply<io::Result<()>>;
+    /// Skip silence at both ends of every song to tighten
+    /// transitions, or `None` to disable it; it takes effect
+    /// from the next song
+    fn set_silence_trim(&self, silence_trim: Option<SilenceTrim>) -> Reply<()>;
+    /// Remember where a song is stopped or skipped, and continue
+    /// from there the next time it's played, e.g. for audiobooks;
+    /// disabling it forgets all the positions
+    fn remember_position(&self, enabled: bool) -> Reply<()>;
+    /// Record the final mix to a WAV file, until
+    /// `stop_recording`; only the time any sound
+    /// plays is recorded
+    #[cfg(feature = "recording")]
+    fn start_recording(&self, path: String) -> Reply<io::Result<()>>;
+    /// Stop recording and complete the WAV file
+    #[cfg(feature = "recording")]
+    fn stop_recording(&self) -> Reply<io::Result<()>>;
+    /// Set how to retry a song failed to be opened
+    /// before it's marked failed and skipped
+    fn set_retry_policy(&self, retry: RetryPolicy) -> Reply<()>;
+    /// Append an effect to the end of the effect chain,
+    /// it takes effect from the next song
+    fn add_effect(&self, effect: Box<dyn Effect + Send + Sync>) -> Reply<()>;
+    /// Remove all effects in the effect chain
+    fn clear_effects(&self) -> Reply<()>;
+    /// Set stereo balance, from -1.0 (left only) to 1.0 (right only)
+    fn set_balance(&self, balance: f32) -> Reply<()>;
+    /// Downmix songs to mono, for single-speaker devices
+    fn set_mono(&self, mono: bool) -> Reply<()>;
     /// Clear all songs in waiting/played list
-    fn clear(&self) -> JoinHandle<()>;
+    fn clear(&self) -> Reply<()>;
+    /// Clear songs in waiting list only
+    fn clear_waiting(&self) -> Reply<()>;
+    /// Clear played history only
+    fn clear_history(&self) -> Reply<()>;
+    /// Move all songs in played history back to
+    /// the end of waiting list, in the played order
+    fn replay_history(&self) -> Reply<()>;
+    /// Move the last `n` played songs back to
+    /// the end of waiting list, in the played order
+    fn replay_last(&self, n: usize) -> Reply<()>;
+    /// Get magnitudes of the audio just played in `bins`
+    /// bands evenly from 0 Hz to Nyquist frequency,
+    /// e.g. to draw a visualizer
+    fn spectrum(&self, bins: usize) -> Reply<Vec<f32>>;
+    /// Get peak and RMS of the audio just played on
+    /// its loudest channels, zero when nothing is playing
+    fn levels(&self) -> Reply<(f32, f32)>;
+    /// Get peak and RMS of the audio just played on
+    /// each channel, e.g. to draw VU meters
+    fn channel_levels(&self) -> Reply<Vec<(f32, f32)>>;
+    /// Get the number of songs in waiting list
+    fn queue_len(&self) -> Reply<usize>;
+    /// Get total length of the songs in waiting list,
+    /// songs of unknown length are not counted
+    fn queue_duration(&self) -> Reply<Duration>;
+    /// Get the time left of current song plus
+    /// the length of waiting list
+    fn remaining_duration(&self) -> Reply<Duration>;
     /// Check whether the current song is playing
-    fn is_playing(&self) -> JoinHandle<bool>;
+    fn is_playing(&self) -> Reply<bool>;
+    /// Check whether the current song is paused
+    fn is_paused(&self) -> Reply<bool>;
+    /// Get whether the player is playing, paused, stopped or idle
+    fn playback_state(&self) -> Reply<PlaybackState>;
+    /// Get the playback state right away
+    fn playback_state_now(&self) -> PlaybackState;
     /// Set output device generator, the default
-    /// generator is based on `OutputStream::try_default`
+    /// generator is based on `OutputStream::try_default`;
+    /// it's used from the next song, see `switch_device`
+    /// to move the song playing to the new device
     fn set_device_maker(
         &self,
         with_generator: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
-    ) -> JoinHandle<()>;
+    ) -> Reply<()>;
+    /// Open the default device in the format, e.g. with a
+    /// small buffer for low latency, instead of the device
+    /// maker; it's used from the next song
+    fn set_stream_config(&self, config: StreamConfig) -> Reply<()>;
+    /// Set output device generator and move the current
+    /// song to the new device right away, continuing
+    /// from its current position, without a stop and play;
+    /// `PlayerEvent::DeviceChanged` is sent
+    fn switch_device(
+        &self,
+        with_generator: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+    ) -> Reply<()>;
+    /// Switch to the output device with the given name,
+    /// resolved to `false` if there is no such device; if it
+    /// fails to open, `PlayerEvent::OutputFailed` is sent
+    fn select_device_by_name(&self, name: &str) -> Reply<bool>;
+    /// Play the songs on another output device along with
+    /// the main one, e.g. speakers and a network sink, from
+    /// the next song; a mirror of the same name is replaced
+    fn add_mirror(
+        &self,
+        name: &str,
+        with_generator: Box<dyn Fn() -> (OutputStream, OutputStreamHandle) + Send + Sync>,
+    ) -> Reply<io::Result<()>>;
+    /// Stop mirroring to the named output, resolved to
+    /// `false` if there is no such mirror
+    fn remove_mirror(&self, name: &str) -> Reply<bool>;
+    /// Set the volume of the named mirror on top of the
+    /// player volume, resolved to `false` if there is no
+    /// such mirror
+    fn set_mirror_volume(&self, name: &str, volume: f32) -> Reply<bool>;
+    /// List output devices of all available hosts
+    fn list_output_devices() -> Vec<DeviceInfo>
+    where
+        Self: Sized;
+    /// Play without any audio device, consuming songs at
+    /// the given speed, until another device is set
+    #[cfg(feature = "test-backend")]
+    fn use_null_output(&self, output: NullOutput) -> Reply<()>;
+    /// Get the named channel, e.g. "music" or "sfx", which
+    /// is created if it doesn't exist; channels play along
+    /// with each other on one output stream, failed if
+    /// the output can't be opened
+    fn channel(&self, name: &str) -> Reply<io::Result<Channel>>;
+    /// Duck the target channel by the amount, 1.0 to silence it,
+    /// while the source channel is audible, e.g. the "music"
+    /// under "sfx"; the volume glides down in `attack` and back
+    /// in `release`, and an amount of 0.0 stops the ducking
+    fn set_ducking(
+        &self,
+        source_channel: &str,
+        target_channel: &str,
+        amount: f32,
+        attack: Duration,
+        release: Duration,
+    ) -> Reply<io::Result<()>>;
+    /// Play a short sound right away, e.g. a UI click, without
+    /// touching the waiting list, current song or play mode
+    fn play_once(&self, song: Song) -> Reply<io::Result<()>>;
+    /// Mix the song into the output along with the current
+    /// song, failed if no song is playing
+    fn play_overlapping(&self, song: Song) -> Reply<io::Result<()>>;
+    /// Play the song at the emitter position in 3D space, heard
+    /// by the ears at the given positions, e.g. for games; it
+    /// plays along with the channels until the handle is dropped
+    fn play_spatial(
+        &self,
+        song: Song,
+        emitter: [f32; 3],
+        left_ear: [f32; 3],
+        right_ear: [f32; 3],
+    ) -> Reply<io::Result<SpatialHandle>>;
+    /// Stop playing, wait for every thread of the player
+    /// to exit and release the output device; the player
+    /// can't be used anymore, later calls fail
+    fn shutdown(&self) -> Reply<()>;
+    /// Take a snapshot of queues, current song and settings
+    fn save_state(&self) -> Reply<PlayerSnapshot>;
+    /// Replace queues, current song and settings with a snapshot
+    fn load_state(&self, snapshot: PlayerSnapshot) -> Reply<()>;
+    /// Get play counts, skip counts and listen time of the
+    /// songs played, e.g. for "most played" lists
+    fn stats(&self) -> Reply<PlayStats>;
+    /// Replace the statistics, e.g. with ones saved before
+    fn load_stats(&self, stats: PlayStats) -> Reply<()>;
+    /// Get the counters and gauges of the player, e.g. to
+    /// export them by `PlayerMetrics::to_prometheus`
+    fn metrics(&self) -> Reply<PlayerMetrics>;
+    /// Save current session as JSON to the given path
+    #[cfg(feature = "serde")]
+    fn save_session(&self, path: String) -> Reply<io::Result<()>>;
+    /// Restore the session saved by `save_session`,
+    /// resuming the interrupted song at its last position
+    #[cfg(feature = "serde")]
+    fn restore_session(&self, path: String) -> Reply<io::Result<()>>;
 }
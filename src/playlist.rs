@@ -0,0 +1,121 @@
+use std::{
+    slice,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::song::Song;
+
+/// Named and ordered list of songs, managed by the
+/// application above the waiting list of a player
+#[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Playlist {
+    pub name: String,
+    songs: Vec<Song>,
+    /// Queue the songs in random order
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub shuffle: bool,
+    /// Queue the songs again once they are all played
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub repeat: bool,
+}
+
+impl Playlist {
+    pub fn new(name: impl Into<String>) -> Playlist {
+        Playlist {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_shuffle(mut self, shuffle: bool) -> Playlist {
+        self.shuffle = shuffle;
+        self
+    }
+
+    pub fn with_repeat(mut self, repeat: bool) -> Playlist {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn songs(&self) -> &[Song] {
+        &self.songs
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Song> {
+        self.songs.get(index)
+    }
+
+    pub fn push(&mut self, song: Song) {
+        self.songs.push(song);
+    }
+
+    /// Insert the song at the index, or to the
+    /// back if the index is out of range
+    pub fn insert(&mut self, index: usize, song: Song) {
+        self.songs.insert(index.min(self.songs.len()), song);
+    }
+
+    /// Replace the song at the index, return the old one
+    pub fn replace(&mut self, index: usize, song: Song) -> Option<Song> {
+        self.songs
+            .get_mut(index)
+            .map(|old| std::mem::replace(old, song))
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Song> {
+        (index < self.songs.len()).then(|| self.songs.remove(index))
+    }
+
+    /// Move the song at `from` to `to`, return
+    /// `false` if `from` is out of range
+    pub fn move_song(&mut self, from: usize, to: usize) -> bool {
+        let Some(song) = self.remove(from) else {
+            return false;
+        };
+        self.insert(to, song);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.songs.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Song> {
+        self.songs.iter()
+    }
+
+    /// Songs in the order to be queued, which
+    /// is shuffled if `shuffle` is on
+    pub fn ordered(&self) -> Vec<Song> {
+        let mut songs = self.songs.clone();
+        if self.shuffle {
+            shuffle(&mut songs);
+        }
+        songs
+    }
+}
+
+/// Fisher-Yates shuffle driven by a xorshift generator,
+/// good enough for playing order
+fn shuffle<T>(items: &mut [T]) {
+    let mut seed = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default()
+        | 1;
+    for i in (1..items.len()).rev() {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+        items.swap(i, (seed % (i as u64 + 1)) as usize);
+    }
+}
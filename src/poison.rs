@@ -0,0 +1,14 @@
+use std::sync::{LockResult, PoisonError};
+
+/// Take the guard of a lock even if another holder
+/// panicked, so that one bad song or callback doesn't
+/// fail every later call of the player
+pub(crate) trait Recover<G> {
+    fn recover(self) -> G;
+}
+
+impl<G> Recover<G> for LockResult<G> {
+    fn recover(self) -> G {
+        self.unwrap_or_else(PoisonError::into_inner)
+    }
+}
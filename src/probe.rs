@@ -0,0 +1,70 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    time::Duration,
+};
+
+use rodio::{Decoder, Source};
+
+/// Codec of an audio file, told by its content
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Codec {
+    WAV,
+    FLAC,
+    VORBIS,
+    MP3,
+    /// Decodable but not recognized by its header
+    UNKNOWN,
+}
+
+/// Format of an audio file, read without playing it
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AudioInfo {
+    pub codec: Codec,
+    pub sample_rate: u32,
+    pub channels: u16,
+    /// Length of the audio, if the format tells it
+    pub duration: Option<Duration>,
+}
+
+/// Tell the codec by the magic bytes at the beginning
+fn sniff_codec(header: &[u8]) -> Codec {
+    match header {
+        [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'A', b'V', b'E', ..] => Codec::WAV,
+        [b'f', b'L', b'a', b'C', ..] => Codec::FLAC,
+        [b'O', b'g', b'g', b'S', ..] => Codec::VORBIS,
+        [b'I', b'D', b'3', ..] => Codec::MP3,
+        // frame sync of MPEG audio
+        [0xFF, b, ..] if b & 0xE0 == 0xE0 => Codec::MP3,
+        _ => Codec::UNKNOWN,
+    }
+}
+
+/// Read codec, sample rate, channels and duration of the
+/// audio file, failed if it can't be decoded
+pub fn probe(path: &str) -> io::Result<AudioInfo> {
+    let mut file = File::open(path)?;
+    let mut header = [0u8; 12];
+    let read = file.read(&mut header)?;
+    file.seek(SeekFrom::Start(0))?;
+    let decoder = Decoder::new(BufReader::new(file))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(AudioInfo {
+        codec: sniff_codec(&header[..read]),
+        sample_rate: decoder.sample_rate(),
+        channels: decoder.channels(),
+        duration: decoder.total_duration(),
+    })
+}
+
+/// Check whether the audio file can be played
+pub fn is_supported(path: &str) -> bool {
+    probe(path).is_ok()
+}
+
+/// Length of the audio file, if the format tells it
+pub fn duration_of(path: &str) -> Option<Duration> {
+    probe(path).ok()?.duration
+}
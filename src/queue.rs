@@ -0,0 +1,131 @@
+use std::collections::{vec_deque, VecDeque};
+
+use crate::song::{Song, SongId};
+
+/// FIFO queue of songs, either bounded or unbounded
+///
+/// A bounded queue drops its oldest song when
+/// pushing into a full queue
+#[derive(Clone, Debug)]
+pub struct SongQueue {
+    songs: VecDeque<Song>,
+    capacity: Option<usize>,
+}
+
+impl SongQueue {
+    /// Make a queue holding at most `cap` songs
+    pub fn with_capacity(cap: usize) -> SongQueue {
+        SongQueue {
+            // grown as needed, the cap may be huge
+            songs: VecDeque::new(),
+            capacity: Some(cap),
+        }
+    }
+
+    /// Make a queue that never drops songs
+    pub fn unbounded() -> SongQueue {
+        SongQueue {
+            songs: VecDeque::new(),
+            capacity: None,
+        }
+    }
+
+    /// Push a song to the back, return the dropped
+    /// song if the queue was full
+    pub fn push(&mut self, song: Song) -> Option<Song> {
+        match self.capacity {
+            Some(0) => Some(song),
+            Some(cap) if self.songs.len() >= cap => {
+                let dropped = self.songs.pop_front();
+                self.songs.push_back(song);
+                dropped
+            }
+            _ => {
+                self.songs.push_back(song);
+                None
+            }
+        }
+    }
+
+    /// Push a song to the front, return the song
+    /// dropped from the back if the queue was full
+    pub fn push_front(&mut self, song: Song) -> Option<Song> {
+        match self.capacity {
+            Some(0) => Some(song),
+            Some(cap) if self.songs.len() >= cap => {
+                let dropped = self.songs.pop_back();
+                self.songs.push_front(song);
+                dropped
+            }
+            _ => {
+                self.songs.push_front(song);
+                None
+            }
+        }
+    }
+
+    /// Pop the song in the front
+    pub fn pop(&mut self) -> Option<Song> {
+        self.songs.pop_front()
+    }
+
+    /// Remove at most `n` songs from the back,
+    /// returned in their order in the queue
+    pub fn take_back(&mut self, n: usize) -> Vec<Song> {
+        let at = self.songs.len().saturating_sub(n);
+        self.songs.split_off(at).into()
+    }
+
+    /// Remove at most `n` songs from the front,
+    /// returned in their order in the queue
+    pub fn take_front(&mut self, n: usize) -> Vec<Song> {
+        let rest = self.songs.split_off(n.min(self.songs.len()));
+        std::mem::replace(&mut self.songs, rest).into()
+    }
+
+    /// Index of the song with the given id
+    pub fn position(&self, id: SongId) -> Option<usize> {
+        self.songs.iter().position(|song| song.id == Some(id))
+    }
+
+    /// Remove the song with the given id
+    pub fn remove(&mut self, id: SongId) -> Option<Song> {
+        self.songs.remove(self.position(id)?)
+    }
+
+    /// Move the song with the given id to the index,
+    /// or to the back if the index is out of range
+    pub fn move_to(&mut self, id: SongId, index: usize) -> bool {
+        let Some(song) = self.remove(id) else {
+            return false;
+        };
+        self.songs.insert(index.min(self.songs.len()), song);
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.songs.clear();
+    }
+
+    pub fn iter(&self) -> vec_deque::Iter<'_, Song> {
+        self.songs.iter()
+    }
+
+    /// Copy of at most `len` songs from `offset`
+    pub fn range(&self, offset: usize, len: usize) -> Vec<Song> {
+        self.songs.iter().skip(offset).take(len).cloned().collect()
+    }
+
+    /// The songs in order, in two slices following each other
+    pub fn as_slices(&self) -> (&[Song], &[Song]) {
+        self.songs.as_slices()
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::Source;
+use symphonia::core::{
+    io::{MediaSourceStream, ReadOnlySource},
+    probe::Hint,
+};
+
+use crate::{
+    effect::BoxedSource, poison::Recover, source::IntoAudioSource,
+    symphonia_source::SymphoniaSource, uri::strip_scheme,
+};
+
+/// Time to wait for each address of the server to answer
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time without any data to consider the stream lost
+const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Time for the server to take the request
+const WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Most redirects followed to reach the stream
+const MAX_REDIRECTS: usize = 5;
+
+/// Endless stream of an internet radio, e.g. Icecast or
+/// SHOUTcast, played from its `http://` URL, or `https://`
+/// with the `tls` feature
+///
+/// The title on air is read from the ICY metadata of
+/// the stream, see `ActiveSong::stream_title`
+pub struct Radio {
+    url: String,
+    title: Arc<Mutex<Option<String>>>,
+}
+
+impl Radio {
+    pub fn new(url: impl Into<String>) -> Radio {
+        Radio {
+            url: url.into(),
+            title: Default::default(),
+        }
+    }
+}
+
+impl IntoAudioSource for Radio {
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+        Ok(Box::new(self.open_f32()?.convert_samples()))
+    }
+
+    fn open_f32(&self) -> io::Result<BoxedSource> {
+        *self.title.lock().recover() = None;
+        let (reader, headers) = connect(&self.url)?;
+        let metaint = headers
+            .get("icy-metaint")
+            .and_then(|metaint| metaint.parse().ok())
+            .unwrap_or(0);
+        let reader = IcyReader {
+            inner: reader,
+            metaint,
+            left: metaint,
+            title: Arc::clone(&self.title),
+        };
+        let mut hint = Hint::new();
+        if let Some(mime) = headers.get("content-type") {
+            hint.mime_type(mime);
+        }
+        let stream =
+            MediaSourceStream::new(Box::new(ReadOnlySource::new(reader)), Default::default());
+        Ok(Box::new(SymphoniaSource::new(stream, &hint)?))
+    }
+
+    fn stream_title(&self) -> Option<String> {
+        self.title.lock().recover().clone()
+    }
+}
+
+/// Connection to the server, wrapped in TLS for `https://`
+trait Connection: Read + Write + Send + Sync {}
+
+impl<T: Read + Write + Send + Sync> Connection for T {}
+
+/// Body of the response, after its headers
+type Body = BufReader<Box<dyn Connection>>;
+
+/// Parts of a stream URL
+#[derive(PartialEq, Debug)]
+pub(crate) struct Target<'a> {
+    pub tls: bool,
+    pub host: &'a str,
+    /// Host and port to connect
+    pub address: String,
+    pub path: &'a str,
+}
+
+/// Split the URL into the host, the address to connect and
+/// the path, the scheme matched ignoring its case
+pub(crate) fn split_url(url: &str) -> io::Result<Target<'_>> {
+    let (tls, rest) = match strip_scheme(url, "http://") {
+        Some(rest) => (false, rest),
+        None => strip_scheme(url, "https://")
+            .map(|rest| (true, rest))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::Unsupported,
+                    format!("only http(s):// streams are supported: {:.64}", url),
+                )
+            })?,
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, "/"),
+    };
+    let (host, address) = match authority.rsplit_once(':') {
+        Some((host, port)) if port.parse::<u16>().is_ok() => (host, authority.to_string()),
+        _ => (
+            authority,
+            format!("{}:{}", authority, if tls { 443 } else { 80 }),
+        ),
+    };
+    Ok(Target {
+        tls,
+        host,
+        address,
+        path,
+    })
+}
+
+/// URL a redirect points to, a relative `location`
+/// resolved against the URL requested
+pub(crate) fn resolve(url: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let scheme_end = url.find("://").map_or(0, |i| i + 3);
+    if let Some(rest) = location.strip_prefix("//") {
+        return format!("{}{}", &url[..scheme_end], rest);
+    }
+    let path_start = url[scheme_end..]
+        .find('/')
+        .map_or(url.len(), |i| scheme_end + i);
+    if location.starts_with('/') {
+        return format!("{}{}", &url[..path_start], location);
+    }
+    // relative to the folder of the path, leaving its query out
+    let path_end = url[path_start..]
+        .find(['?', '#'])
+        .map_or(url.len(), |i| path_start + i);
+    match url[path_start..path_end].rfind('/') {
+        Some(i) => format!("{}{}", &url[..path_start + i + 1], location),
+        None => format!("{}/{}", &url[..path_start], location),
+    }
+}
+
+/// Connect to the first address of the server answering in
+/// time, with timeouts on reads and writes
+fn open_tcp(address: &str) -> io::Result<TcpStream> {
+    let mut error = io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("no address found for {:.64}", address),
+    );
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+            Ok(stream) => {
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                stream.set_write_timeout(Some(WRITE_TIMEOUT))?;
+                return Ok(stream);
+            }
+            Err(e) => error = e,
+        }
+    }
+    Err(error)
+}
+
+/// Open the connection to the server of the URL
+fn open(target: &Target) -> io::Result<Box<dyn Connection>> {
+    let stream = open_tcp(&target.address)?;
+    match target.tls {
+        false => Ok(Box::new(stream)),
+        #[cfg(feature = "tls")]
+        true => wrap_tls(target.host, stream),
+        #[cfg(not(feature = "tls"))]
+        true => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("https streams need the tls feature: {:.64}", target.host),
+        )),
+    }
+}
+
+/// Start a TLS session with the host, checking its
+/// certificate against the Mozilla roots
+#[cfg(feature = "tls")]
+fn wrap_tls(host: &str, stream: TcpStream) -> io::Result<Box<dyn Connection>> {
+    use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore};
+    use std::sync::OnceLock;
+
+    static CONFIG: OnceLock<Arc<ClientConfig>> = OnceLock::new();
+    let config = CONFIG.get_or_init(|| {
+        let roots = RootCertStore::from_iter(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Arc::new(config)
+    });
+    let name = ServerName::try_from(host.to_string())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    let session = ClientConnection::new(Arc::clone(config), name).map_err(io::Error::other)?;
+    Ok(Box::new(rustls::StreamOwned::new(session, stream)))
+}
+
+/// Request the stream with ICY metadata, following redirects,
+/// returns the body and the response headers in lowercase
+fn connect(url: &str) -> io::Result<(Body, HashMap<String, String>)> {
+    let mut url = url.to_string();
+    for _ in 0..=MAX_REDIRECTS {
+        let target = split_url(&url)?;
+        let mut stream = open(&target)?;
+        // HTTP/1.0 so that the body is never chunked
+        write!(
+            stream,
+            "GET {} HTTP/1.0\r\nHost: {}\r\nUser-Agent: super-rodio\r\nIcy-MetaData: 1\r\n\r\n",
+            target.path, target.address
+        )?;
+        stream.flush()?;
+        let mut reader = BufReader::new(stream);
+        // "HTTP/1.1 200 OK" or "ICY 200 OK" of old servers
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        let code: u16 = status
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not an http response"))?;
+        let mut headers = HashMap::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                headers.insert(key.trim().to_ascii_lowercase(), value.trim().to_string());
+            }
+        }
+        match code {
+            200..=299 => return Ok((reader, headers)),
+            301 | 302 | 303 | 307 | 308 => {
+                let location = headers.remove("location").ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, "redirect without location")
+                })?;
+                url = resolve(&url, &location);
+            }
+            _ => {
+                return Err(io::Error::other(format!(
+                    "stream responded {}",
+                    status.trim()
+                )))
+            }
+        }
+    }
+    Err(io::Error::other("too many redirects"))
+}
+
+/// Get the title from ICY metadata like `StreamTitle='...';`
+pub(crate) fn parse_stream_title(metadata: &[u8]) -> Option<String> {
+    let metadata = String::from_utf8_lossy(metadata);
+    let start = metadata.find("StreamTitle='")? + "StreamTitle='".len();
+    let rest = &metadata[start..];
+    // the title itself may contain quotes
+    let title = &rest[..rest.find("';").unwrap_or(rest.trim_end_matches('\0').len())];
+    let title = title.trim_end_matches('\'').trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+/// Reader removing the metadata blocks interleaved in
+/// the audio data, keeping the latest title
+struct IcyReader<R> {
+    inner: R,
+    // bytes of audio between metadata blocks, 0 if none
+    metaint: usize,
+    left: usize,
+    title: Arc<Mutex<Option<String>>>,
+}
+
+impl<R: Read> IcyReader<R> {
+    /// Read a metadata block, returns `false` if the
+    /// stream ends before it
+    fn read_metadata(&mut self) -> io::Result<bool> {
+        let mut len = [0];
+        if self.inner.read(&mut len)? == 0 {
+            return Ok(false);
+        }
+        let mut metadata = vec![0; len[0] as usize * 16];
+        self.inner.read_exact(&mut metadata)?;
+        // most blocks are empty, meaning nothing changed
+        if let Some(title) = parse_stream_title(&metadata) {
+            *self.title.lock().recover() = Some(title);
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for IcyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.metaint == 0 {
+            return self.inner.read(buf);
+        }
+        if self.left == 0 {
+            if !self.read_metadata()? {
+                return Ok(0);
+            }
+            self.left = self.metaint;
+        }
+        let len = buf.len().min(self.left);
+        let read = self.inner.read(&mut buf[..len])?;
+        self.left -= read;
+        Ok(read)
+    }
+}
@@ -0,0 +1,86 @@
+use std::{
+    sync::{Arc, Condvar, Mutex, Weak},
+    thread::spawn,
+    time::Duration,
+};
+
+use rodio::Sink;
+
+use crate::poison::Recover;
+
+/// Interval between volume changes of a ramp
+const RAMP_STEP: Duration = Duration::from_millis(5);
+
+/// Time the worker of the ramps waits for the next
+/// one before checking whether the player is gone
+const RAMP_IDLE: Duration = Duration::from_secs(1);
+
+/// Ramp of `set_volume`, short enough to feel instant
+/// but long enough to avoid a click
+pub const DEFAULT_RAMP: Duration = Duration::from_millis(30);
+
+struct Ramp {
+    sink: Arc<Sink>,
+    from: f32,
+    target: f32,
+    steps: u32,
+    step: u32,
+}
+
+/// Volume ramps of a player, run one at a time by a
+/// worker thread started on the first ramp, instead
+/// of a thread for each change of the volume
+#[derive(Default)]
+pub(crate) struct VolumeRamp {
+    ramp: Mutex<Option<Ramp>>,
+    started: Mutex<bool>,
+    changed: Condvar,
+}
+
+pub(crate) type SharedRamp = Arc<VolumeRamp>;
+
+/// Change the volume of the sink to `target` linearly
+/// over `duration`, replacing the ramp going on
+pub(crate) fn ramp_volume(sink: Arc<Sink>, target: f32, duration: Duration, ramps: &SharedRamp) {
+    let mut ramp = ramps.ramp.lock().recover();
+    if duration.is_zero() {
+        *ramp = None;
+        sink.set_volume(target);
+        return;
+    }
+    *ramp = Some(Ramp {
+        from: sink.volume(),
+        sink,
+        target,
+        steps: (duration.as_secs_f32() / RAMP_STEP.as_secs_f32()).ceil() as u32,
+        step: 0,
+    });
+    ramps.changed.notify_one();
+    drop(ramp);
+    let mut started = ramps.started.lock().recover();
+    if !*started {
+        *started = true;
+        let ramps = Arc::downgrade(ramps);
+        spawn(move || run(ramps));
+    }
+}
+
+/// Step the ramps until the player is gone
+fn run(ramps: Weak<VolumeRamp>) {
+    while let Some(ramps) = ramps.upgrade() {
+        let mut ramp = ramps.ramp.lock().recover();
+        let Some(current) = ramp.as_mut() else {
+            let _ = ramps.changed.wait_timeout(ramp, RAMP_IDLE);
+            continue;
+        };
+        current.step += 1;
+        let progress = current.step as f32 / current.steps as f32;
+        let volume = current.from + (current.target - current.from) * progress;
+        current.sink.set_volume(volume);
+        if current.step >= current.steps {
+            *ramp = None;
+        }
+        // a new ramp may replace this one meanwhile
+        let _ = ramps.changed.wait_timeout(ramp, RAMP_STEP);
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    io,
+    sync::mpsc::{channel, Sender},
+    thread::{spawn, JoinHandle},
+};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::bus::{CHANNELS, SAMPLE_RATE};
+
+/// WAV file of the final mix, written by its own thread
+/// so that the audio thread never waits for the file
+pub(crate) struct Recording {
+    thread: JoinHandle<io::Result<()>>,
+}
+
+impl Recording {
+    /// Create the file and the thread writing the samples sent
+    /// to the returned sender to it, until the sender is dropped
+    pub fn create(path: &str) -> io::Result<(Recording, Sender<Vec<f32>>)> {
+        let spec = WavSpec {
+            channels: CHANNELS,
+            sample_rate: SAMPLE_RATE,
+            bits_per_sample: 32,
+            sample_format: SampleFormat::Float,
+        };
+        let mut writer = WavWriter::create(path, spec).map_err(io::Error::other)?;
+        let (samples, batches) = channel::<Vec<f32>>();
+        let thread = spawn(move || {
+            for batch in batches {
+                for sample in batch {
+                    writer.write_sample(sample).map_err(io::Error::other)?;
+                }
+            }
+            writer.finalize().map_err(io::Error::other)
+        });
+        Ok((Recording { thread }, samples))
+    }
+
+    /// Wait for the samples sent to be written and complete
+    /// the header of the file, once the sender is dropped
+    pub fn finish(self) -> io::Result<()> {
+        self.thread
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("the recording panicked")))
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    fmt,
+    future::{Future, IntoFuture},
+    panic::resume_unwind,
+    pin::Pin,
+    sync::{Arc, Condvar, Mutex},
+    task::{Context, Poll, Waker},
+    thread,
+};
+
+use crate::poison::Recover;
+
+struct Slot<T> {
+    value: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+struct Shared<T> {
+    slot: Mutex<Slot<T>>,
+    ready: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn fill(&self, value: thread::Result<T>) {
+        let mut slot = self.slot.lock().recover();
+        slot.value = Some(value);
+        if let Some(waker) = slot.waker.take() {
+            waker.wake();
+        }
+        self.ready.notify_all();
+    }
+}
+
+/// Handle of the result of a player operation
+///
+/// Works like a `JoinHandle` without spawning a thread:
+/// block on it with `join`, or `.await` it
+pub struct Reply<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Future of a [`Reply`], made by `.await` or `into_future`
+pub struct ReplyFuture<T> {
+    shared: Arc<Shared<T>>,
+}
+
+/// Sending half of a [`Reply`], owned by whoever does the work
+///
+/// Dropping it without sending fails the reply, just
+/// like a panicked thread fails its `JoinHandle`
+pub struct Responder<T> {
+    shared: Option<Arc<Shared<T>>>,
+}
+
+impl<T> Reply<T> {
+    /// Make a pending reply and its responder
+    pub fn pending() -> (Reply<T>, Responder<T>) {
+        let shared = Arc::new(Shared {
+            slot: Mutex::new(Slot {
+                value: None,
+                waker: None,
+            }),
+            ready: Condvar::new(),
+        });
+        (
+            Reply {
+                shared: Arc::clone(&shared),
+            },
+            Responder {
+                shared: Some(shared),
+            },
+        )
+    }
+
+    /// Make a reply that is already resolved
+    pub fn ready(value: T) -> Reply<T> {
+        let (reply, responder) = Reply::pending();
+        responder.send(value);
+        reply
+    }
+
+    /// Wait for the result
+    pub fn join(self) -> thread::Result<T> {
+        let mut slot = self.shared.slot.lock().recover();
+        loop {
+            if let Some(value) = slot.value.take() {
+                return value;
+            }
+            slot = self.shared.ready.wait(slot).recover();
+        }
+    }
+
+    /// Check whether the result is available
+    pub fn is_finished(&self) -> bool {
+        self.shared.slot.lock().recover().value.is_some()
+    }
+}
+
+impl<T> fmt::Debug for Reply<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reply")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+impl<T> IntoFuture for Reply<T> {
+    type Output = T;
+    type IntoFuture = ReplyFuture<T>;
+
+    fn into_future(self) -> ReplyFuture<T> {
+        ReplyFuture {
+            shared: self.shared,
+        }
+    }
+}
+
+impl<T> Future for ReplyFuture<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.shared.slot.lock().recover();
+        match slot.value.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            // propagate the failure, like `join().unwrap()`
+            Some(Err(e)) => resume_unwind(e),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Responder<T> {
+    /// Resolve the reply with the given value
+    pub fn send(mut self, value: T) {
+        if let Some(shared) = self.shared.take() {
+            shared.fill(Ok(value));
+        }
+    }
+}
+
+impl<T> Drop for Responder<T> {
+    fn drop(&mut self) {
+        if let Some(shared) = self.shared.take() {
+            shared.fill(Err(Box::new("player dropped the reply")));
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! JSON-RPC 2.0 calls of the `Player` API, served by
+//! `serve_ipc` and `serve_ws`
+
+use std::{io, time::Duration};
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+
+use crate::{player::Player, reply::Reply, shared_player::SharedPlayer};
+
+// error codes of JSON-RPC 2.0
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+/// The player is shut down, or an I/O operation failed
+const SERVER_ERROR: i64 = -32000;
+
+type RpcResult = Result<Value, (i64, String)>;
+
+/// Methods naming files or URLs for the player to open,
+/// directly or as the paths of songs, and shutting it down,
+/// which only local callers are trusted with
+const LOCAL_METHODS: [&str; 14] = [
+    "add",
+    "add_folder",
+    "watch_folder",
+    "unwatch_folder",
+    "load_playlist",
+    "add_to_playlist",
+    "preload",
+    "play_once",
+    "play_overlapping",
+    "start_recording",
+    "save_session",
+    "restore_session",
+    "load_state",
+    "shutdown",
+];
+
+/// Where the requests come from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Caller {
+    /// Another process of the same machine
+    #[cfg_attr(not(all(feature = "ipc", unix)), allow(dead_code))]
+    Local,
+    /// A client over the network
+    #[cfg_attr(not(feature = "remote"), allow(dead_code))]
+    Remote,
+}
+
+/// Answer a request in JSON, `None` for a notification,
+/// i.e. a request without id
+pub(crate) fn handle(player: &SharedPlayer, request: &str, caller: Caller) -> Option<String> {
+    let response = match serde_json::from_str::<Value>(request) {
+        Ok(request) => respond(player, &request, caller),
+        Err(e) => Some(error(Value::Null, PARSE_ERROR, e.to_string())),
+    };
+    response.map(|response| response.to_string())
+}
+
+/// Notification of the server to a remote client, e.g. an
+/// event, without the paths of the songs
+#[cfg(feature = "remote")]
+pub(crate) fn notification<T: Serialize>(method: &str, params: T) -> String {
+    let mut params = serde_json::to_value(params).unwrap_or_default();
+    hide_paths(&mut params);
+    json!({"jsonrpc": "2.0", "method": method, "params": params}).to_string()
+}
+
+/// Blank the paths of the songs in the value, i.e. of the
+/// objects with a name and a path, which name local files
+/// and are kept from remote callers
+pub(crate) fn hide_paths(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            if map.contains_key("name") && map.contains_key("path") {
+                map.insert("path".into(), Value::String(String::new()));
+                if let Some(cover) = map.get_mut("cover") {
+                    *cover = Value::Null;
+                }
+            }
+            map.values_mut().for_each(hide_paths);
+        }
+        Value::Array(values) => values.iter_mut().for_each(hide_paths),
+        _ => {}
+    }
+}
+
+fn error(id: Value, code: i64, message: String) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+fn respond(player: &SharedPlayer, request: &Value, caller: Caller) -> Option<Value> {
+    let id = request.get("id").cloned();
+    let Some(method) = request.get("method").and_then(Value::as_str) else {
+        let message = "no method in the request".to_string();
+        return Some(error(id.unwrap_or(Value::Null), INVALID_REQUEST, message));
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    let remote = caller == Caller::Remote;
+    let mut result = match remote && LOCAL_METHODS.contains(&method) {
+        true => Err((METHOD_NOT_FOUND, format!("not served remotely: {}", method))),
+        false => call(player, method, &params),
+    };
+    if let (true, Ok(result)) = (remote, &mut result) {
+        hide_paths(result);
+    }
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => error(id, code, message),
+    })
+}
+
+/// Get the named param, missing ones are taken as null
+fn param<T: DeserializeOwned>(params: &Value, name: &str) -> Result<T, (i64, String)> {
+    let value = params.get(name).cloned().unwrap_or(Value::Null);
+    serde_json::from_value(value).map_err(|e| (INVALID_PARAMS, format!("{}: {}", name, e)))
+}
+
+/// Get the named param in seconds
+fn secs(params: &Value, name: &str) -> Result<Duration, (i64, String)> {
+    Duration::try_from_secs_f64(param(params, name)?)
+        .map_err(|e| (INVALID_PARAMS, format!("{}: {}", name, e)))
+}
+
+fn value<T: Serialize>(value: T) -> RpcResult {
+    serde_json::to_value(value).map_err(|e| (INTERNAL_ERROR, e.to_string()))
+}
+
+/// Wait for the reply of the player
+fn reply<T: Serialize>(reply: Reply<T>) -> RpcResult {
+    let result = reply.join();
+    value(result.map_err(|_| (SERVER_ERROR, "the player is shut down".to_string()))?)
+}
+
+/// Wait for a duration replied by the player, in seconds
+fn secs_reply(reply: Reply<Duration>) -> RpcResult {
+    let result = reply.join();
+    value(
+        result
+            .map_err(|_| (SERVER_ERROR, "the player is shut down".to_string()))?
+            .as_secs_f64(),
+    )
+}
+
+/// Wait for the reply of an I/O operation of the player
+fn io_reply<T: Serialize>(reply: Reply<io::Result<T>>) -> RpcResult {
+    match reply.join() {
+        Ok(result) => value(result.map_err(|e| (SERVER_ERROR, e.to_string()))?),
+        Err(_) => Err((SERVER_ERROR, "the player is shut down".to_string())),
+    }
+}
+
+/// Call the method of the player with the params
+fn call(player: &SharedPlayer, method: &str, params: &Value) -> RpcResult {
+    let p = params;
+    match method {
+        "add" => reply(player.add(param(p, "song")?)),
+        "add_folder" => io_reply(player.add_folder(
+            &param::<String>(p, "path")?,
+            param::<Option<bool>>(p, "recursive")?.unwrap_or(false),
+        )),
+        "watch_folder" => io_reply(player.watch_folder(
+            &param::<String>(p, "path")?,
+            param::<Option<bool>>(p, "recursive")?.unwrap_or(false),
+        )),
+        "unwatch_folder" => reply(player.unwatch_folder(&param::<String>(p, "path")?)),
+        "load_playlist" => reply(player.load_playlist(&param(p, "playlist")?)),
+        "create_playlist" => reply(player.create_playlist(&param::<String>(p, "name")?)),
+        "add_to_playlist" => {
+            reply(player.add_to_playlist(&param::<String>(p, "name")?, param(p, "song")?))
+        }
+        "queue_playlist" => reply(player.queue_playlist(&param::<String>(p, "name")?)),
+        "playlist" => reply(player.playlist(&param::<String>(p, "name")?)),
+        "remove_playlist" => reply(player.remove_playlist(&param::<String>(p, "name")?)),
+        "playlist_names" => reply(player.playlist_names()),
+        "remove" => reply(player.remove(param(p, "id")?)),
+        "move_song" => reply(player.move_song(param(p, "id")?, param(p, "index")?)),
+        "waiting_list" => reply(player.waiting_list()),
+        "played_list" => reply(player.played_list()),
+        "waiting_range" => reply(player.waiting_range(param(p, "offset")?, param(p, "len")?)),
+        "played_range" => reply(player.played_range(param(p, "offset")?, param(p, "len")?)),
+        "find" => reply(player.find(&param::<String>(p, "text")?)),
+        "current_song" => reply(player.current_song()),
+        "failed_list" => reply(player.failed_list()),
+        "snapshot" => value(player.snapshot()),
+        "ui_state" => value(player.ui_state(param(p, "preview")?)),
+        "position" => value(player.position().as_secs_f64()),
+        "metrics" => reply(player.metrics()),
+        "play" => {
+            // the reply comes once the songs end
+            player.play();
+            Ok(Value::Null)
+        }
+        "use_normal_play" => reply(player.use_normal_play()),
+        "use_auto_play" => reply(player.use_auto_play()),
+        "use_continuous_play" => reply(player.use_continuous_play()),
+        "toggle" => reply(player.toggle()),
+        "pause" => reply(player.pause()),
+        "resume" => reply(player.resume()),
+        "stop" => reply(player.stop()),
+        "skip" => reply(player.skip()),
+        "previous" => reply(player.previous()),
+        "play_song" => reply(player.play_song(param(p, "id")?)),
+        "play_index" => reply(player.play_index(param(p, "index")?)),
+        "set_volume" => reply(player.set_volume(param(p, "volume")?)),
+        "set_volume_smooth" => {
+            reply(player.set_volume_smooth(param(p, "volume")?, secs(p, "duration")?))
+        }
+        "set_master_volume" => reply(player.set_master_volume(param(p, "volume")?)),
+        "mute" => reply(player.mute()),
+        "unmute" => reply(player.unmute()),
+        "is_muted" => reply(player.is_muted()),
+        "set_audio_focus" => reply(player.set_audio_focus(param(p, "focus")?)),
+        "seek" => reply(player.seek(secs(p, "pos")?)),
+        "set_track_gap" => reply(player.set_track_gap(secs(p, "gap")?)),
+        "seek_by" => reply(player.seek_by(param(p, "secs")?)),
+        "next_chapter" => reply(player.next_chapter()),
+        "prev_chapter" => reply(player.prev_chapter()),
+        "goto_chapter" => reply(player.goto_chapter(param(p, "index")?)),
+        "set_loop_region" => reply(player.set_loop_region(secs(p, "start")?, secs(p, "end")?)),
+        "clear_loop_region" => reply(player.clear_loop_region()),
+        "stop_after" => {
+            let fade_out = match p.get("fade_out") {
+                None | Some(Value::Null) => None,
+                Some(_) => Some(secs(p, "fade_out")?),
+            };
+            reply(player.stop_after(secs(p, "after")?, fade_out))
+        }
+        "stop_after_current" => reply(player.stop_after_current()),
+        "cancel_stop_after" => reply(player.cancel_stop_after()),
+        "set_eq" => reply(player.set_eq(param(p, "bands")?)),
+        "set_eq_preset" => reply(player.set_eq_preset(param(p, "preset")?)),
+        "set_normalization" => reply(player.set_normalization(param(p, "normalization")?)),
+        "set_limiter" => reply(player.set_limiter(param(p, "limiter")?)),
+        "set_cache_budget" => reply(player.set_cache_budget(param(p, "budget")?)),
+        "set_duck_level" => reply(player.set_duck_level(param(p, "level")?)),
+        "set_speed" => reply(player.set_speed(param(p, "speed")?)),
+        "set_speed_mode" => reply(player.set_speed_mode(param(p, "mode")?)),
+        #[cfg(feature = "time-stretch")]
+        "set_pitch" => reply(player.set_pitch(param(p, "semitones")?)),
+        "set_progress_interval" => reply(player.set_progress_interval(secs(p, "interval")?)),
+        "preload" => io_reply(player.preload(param(p, "song")?)),
+        "set_silence_trim" => reply(player.set_silence_trim(param(p, "silence_trim")?)),
+        "remember_position" => reply(player.remember_position(param(p, "enabled")?)),
+        #[cfg(feature = "recording")]
+        "start_recording" => io_reply(player.start_recording(param(p, "path")?)),
+        #[cfg(feature = "recording")]
+        "stop_recording" => io_reply(player.stop_recording()),
+        "set_balance" => reply(player.set_balance(param(p, "balance")?)),
+        "set_mono" => reply(player.set_mono(param(p, "mono")?)),
+        "clear" => reply(player.clear()),
+        "clear_waiting" => reply(player.clear_waiting()),
+        "clear_history" => reply(player.clear_history()),
+        "replay_history" => reply(player.replay_history()),
+        "replay_last" => reply(player.replay_last(param(p, "n")?)),
+        "spectrum" => reply(player.spectrum(param(p, "bins")?)),
+        "levels" => reply(player.levels()),
+        "channel_levels" => reply(player.channel_levels()),
+        "queue_len" => reply(player.queue_len()),
+        "queue_duration" => secs_reply(player.queue_duration()),
+        "remaining_duration" => secs_reply(player.remaining_duration()),
+        "is_playing" => reply(player.is_playing()),
+        "is_paused" => reply(player.is_paused()),
+        "playback_state" => reply(player.playback_state()),
+        "remove_mirror" => reply(player.remove_mirror(&param::<String>(p, "name")?)),
+        "set_mirror_volume" => {
+            reply(player.set_mirror_volume(&param::<String>(p, "name")?, param(p, "volume")?))
+        }
+        "select_device_by_name" => {
+            reply(player.select_device_by_name(&param::<String>(p, "name")?))
+        }
+        "set_ducking" => io_reply(player.set_ducking(
+            &param::<String>(p, "source_channel")?,
+            &param::<String>(p, "target_channel")?,
+            param(p, "amount")?,
+            secs(p, "attack")?,
+            secs(p, "release")?,
+        )),
+        "play_once" => io_reply(player.play_once(param(p, "song")?)),
+        "play_overlapping" => io_reply(player.play_overlapping(param(p, "song")?)),
+        "shutdown" => reply(player.shutdown()),
+        "save_state" => reply(player.save_state()),
+        "load_state" => reply(player.load_state(param(p, "snapshot")?)),
+        "save_session" => io_reply(player.save_session(param(p, "path")?)),
+        "restore_session" => io_reply(player.restore_session(param(p, "path")?)),
+        _ => Err((METHOD_NOT_FOUND, format!("no such method: {}", method))),
+    }
+}
@@ -0,0 +1,37 @@
+//! Tokens given by the clients over the network, checked
+//! by `serve_ws` and `serve_http`
+
+use std::{io, sync::Arc};
+
+/// Token shared with the clients, which can't be empty
+pub(crate) fn server_token(token: String) -> io::Result<Arc<str>> {
+    if token.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the token is empty",
+        ));
+    }
+    Ok(token.into())
+}
+
+/// Token of a client, given as a bearer token of the
+/// `Authorization` header or in the query of the URL
+pub(crate) fn given_token<'a>(authorization: Option<&'a str>, query: &'a str) -> Option<&'a str> {
+    authorization
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| {
+            query
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("token="))
+        })
+}
+
+/// Compare the secrets in time independent of where they differ
+pub(crate) fn same_secret(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
@@ -1,190 +1,643 @@
 use std::{
-    fs::File,
-    io::BufReader,
-    sync::{Arc, RwLock},
-    thread::{spawn, JoinHandle},
+    fmt, io,
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Weak,
+    },
+    thread::spawn,
+    time::Duration,
 };
 
-use rodio::{Decoder, Sink, Source};
+use parking_lot::{RwLock, RwLockReadGuard};
 
 use crate::{
-    asset::{PlaybackMode, PlayerAsset},
-    make::Make,
+    actor::{self, PlayerCommand},
+    asset::{
+        HotState, PlaybackMode, PlaybackState, PlayerAsset, PlayerSnapshot, PlayerStatus,
+        SharedHot, UiState,
+    },
+    builder::PlayerBuilder,
+    config::{Buffering, PlayerConfig, RetryPolicy, SpeedMode},
+    device::{self, DeviceInfo, StreamConfig},
+    effect::{BoxedSource, Effect},
+    equalizer::{EqBand, EqPreset},
+    event::PlayerEvent,
+    focus::AudioFocus,
+    limiter::Limiter,
+    loudness::Normalization,
+    make::{Make, MakeWith},
+    metrics::PlayerMetrics,
+    mixer::Channel,
     player::Player,
-    song::{ActiveSong, Song, SongState},
+    playlist::Playlist,
+    ramp::DEFAULT_RAMP,
+    reply::{Reply, Responder},
+    silence::SilenceTrim,
+    song::{ActiveSong, Song, SongId},
+    source::OnceSource,
+    spatial::SpatialHandle,
+    stats::PlayStats,
 };
 
-pub type SharedPlayer = Arc<RwLock<PlayerAsset>>;
+#[cfg(feature = "test-backend")]
+use crate::null_output::NullOutput;
+
+/// Lock of the queues and settings of a player, fair so that
+/// a stream of readers can't starve the commands changing it
+pub(crate) type StateLock = RwLock<PlayerAsset>;
+
+/// State of a player, shared by its handles and threads
+pub(crate) type SharedState = Arc<StateLock>;
+
+/// Handle of a player, cheap to clone and send across threads
+///
+/// The player shuts down once every handle is dropped
+#[derive(Clone)]
+pub struct SharedPlayer {
+    owner: Arc<Owner>,
+}
+
+/// Handle of a player which doesn't keep it from
+/// shutting down, e.g. for the threads of the player
+pub(crate) struct WeakPlayer {
+    owner: Weak<Owner>,
+}
+
+impl WeakPlayer {
+    pub fn upgrade(&self) -> Option<SharedPlayer> {
+        let owner = self.owner.upgrade()?;
+        Some(SharedPlayer { owner })
+    }
+}
+
+/// Shared by all handles of a player, never by its threads
+struct Owner {
+    state: SharedState,
+    /// Hot state of `state`, read without locking the rest
+    hot: SharedHot,
+    lock_timeout: Duration,
+}
+
+impl Drop for Owner {
+    fn drop(&mut self) {
+        let _ = send(&self.state, PlayerCommand::Shutdown).join();
+    }
+}
+
+impl SharedPlayer {
+    /// Start building a player with custom settings
+    pub fn builder() -> PlayerBuilder {
+        PlayerBuilder::default()
+    }
+
+    /// Look at waiting list right away without copying it,
+    /// given as two slices in order, read-locking the player
+    /// while `f` runs
+    pub fn with_waiting<T>(&self, f: impl FnOnce(&[Song], &[Song]) -> T) -> T {
+        let state = self.state().read();
+        let (front, back) = state.waiting_q.as_slices();
+        f(front, back)
+    }
+
+    /// Look at played history right away without copying
+    /// it, given as two slices in order, read-locking the
+    /// player while `f` runs
+    pub fn with_played<T>(&self, f: impl FnOnce(&[Song], &[Song]) -> T) -> T {
+        let state = self.state().read();
+        let (front, back) = state.played_q.as_slices();
+        f(front, back)
+    }
+
+    pub(crate) fn downgrade(&self) -> WeakPlayer {
+        WeakPlayer {
+            owner: Arc::downgrade(&self.owner),
+        }
+    }
+
+    pub(crate) fn state(&self) -> &SharedState {
+        &self.owner.state
+    }
+
+    pub(crate) fn hot(&self) -> &SharedHot {
+        &self.owner.hot
+    }
+
+    /// Read the state, `None` if it's still locked
+    /// after the lock timeout
+    fn try_state(&self) -> Option<RwLockReadGuard<'_, PlayerAsset>> {
+        self.state().try_read_for(self.owner.lock_timeout)
+    }
+
+    /// Read the hot state, `None` if it's still locked
+    /// after the lock timeout
+    fn try_hot(&self) -> Option<RwLockReadGuard<'_, HotState>> {
+        self.hot().try_read_for(self.owner.lock_timeout)
+    }
+}
+
+impl fmt::Debug for SharedPlayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedPlayer")
+            .field("state", &self.playback_state_now())
+            .finish()
+    }
+}
 
 impl Make<Self> for SharedPlayer {
     fn make() -> SharedPlayer {
-        Arc::new(RwLock::new(PlayerAsset::make()))
+        SharedPlayer::make_with(PlayerConfig::default())
     }
 }
 
+impl MakeWith<Self, PlayerConfig> for SharedPlayer {
+    fn make_with(config: PlayerConfig) -> SharedPlayer {
+        let lock_timeout = config.lock_timeout;
+        let state = Arc::new_cyclic(|state| {
+            let (commands, inbox) = channel();
+            // the actor only keeps a weak reference, so it
+            // exits as soon as the last thread is done
+            let state = state.clone();
+            spawn(move || actor::run(state, inbox));
+            StateLock::new(PlayerAsset::new(config, commands))
+        });
+        let hot = Arc::clone(&state.read().hot);
+        SharedPlayer {
+            owner: Arc::new(Owner {
+                state,
+                hot,
+                lock_timeout,
+            }),
+        }
+    }
+}
+
+/// Send a command to the actor of the player
+fn request<T>(
+    player: &SharedPlayer,
+    command: impl FnOnce(Responder<T>) -> PlayerCommand,
+) -> Reply<T> {
+    send(player.state(), command)
+}
+
+fn send<T>(state: &SharedState, command: impl FnOnce(Responder<T>) -> PlayerCommand) -> Reply<T> {
+    let (reply, responder) = Reply::pending();
+    let commands = state.read().commands.clone();
+    // if the actor is gone, the command along with its
+    // responder is dropped and the reply fails instead of hanging
+    let _ = commands.send(command(responder));
+    reply
+}
+
 impl Player for SharedPlayer {
-    fn add(&self, song: Song) -> JoinHandle<()> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || {
-            state.write().unwrap().waiting_q.push(song);
-        })
-    }
-
-    fn waiting_list(&self) -> JoinHandle<Vec<Song>> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || {
-            state
-                .read()
-                .unwrap()
-                .waiting_q
-                .iter()
-                .map(Clone::clone)
-                .collect()
-        })
-    }
-
-    fn played_list(&self) -> JoinHandle<Vec<Song>> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || {
-            state
-                .read()
-                .unwrap()
-                .played_q
-                .iter()
-                .map(Clone::clone)
-                .collect()
-        })
-    }
-
-    fn current_song(&self) -> JoinHandle<ActiveSong> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || state.read().unwrap().current.clone())
-    }
-
-    fn play(&self) -> JoinHandle<()> {
-        if self.is_playing().join().unwrap() {
-            return spawn(|| {});
-        };
-        // acquire an arc for child thread
-        let state = Arc::clone(&self);
-        // create a new thread for loading and playing music
-        spawn(move || {
-            // The life cycle of "_stream" should >= source
-            // so we should make a new sink each time before playing some source
-            let (_stream, stream_handle) = { (state.read().unwrap().gen_out)() };
-            {
-                // acquire write lock to place a new sink
-                let mut state = state.write().unwrap();
-                state.sink = Some(Sink::try_new(&stream_handle).unwrap());
-            }
-            loop {
-                let song = { state.write().unwrap().waiting_q.pop() };
-                if song.is_none() {
-                    break;
-                }
-                let song = song.unwrap();
-                let file = BufReader::new(File::open(song.path.clone()).unwrap());
-                let source = Decoder::new(file).unwrap();
-                {
-                    // acquire write lock to prepare playing song
-                    let mut state = state.write().unwrap();
-                    state.current =
-                        ActiveSong::from(song.clone(), source.total_duration().unwrap_or_default());
-                    state.current.state = SongState::PLAY;
-                }
-                {
-                    // acquire read lock to play music
-                    let state = state.read().unwrap();
-                    // acquiring a read lock to play the music
-                    if let Some(sink) = state.sink.as_ref() {
-                        // assign current song
-                        sink.append(source);
-                        sink.set_volume(state.volume);
-                        sink.sleep_until_end();
-                    };
-                }
-                {
-                    // acquire write lock to finish end-of-play process
-                    let mut state = state.write().unwrap();
-                    state.current.progress = state.current.duration;
-                    state.current.state = SongState::STOP;
-                    state.current.song = None;
-                    state.played_q.push(song.clone());
-                }
-                {
-                    // auto play if flag is on, otherwise breaks
-                    let to_auto_play = { state.read().unwrap().mode == PlaybackMode::AUTO };
-                    if !to_auto_play {
-                        break;
-                    }
-                }
-            }
-        })
-    }
-
-    fn toggle(&self) -> JoinHandle<()> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || {
-            // check if old sink exists and
-            // play/pause it by acquiring read lock
-            if let Some(sink) = &state.read().unwrap().sink {
-                if sink.is_paused() {
-                    sink.play();
-                } else {
-                    sink.pause();
-                }
-            };
-        })
-    }
-
-    fn stop(&self) -> JoinHandle<()> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || {
-            // check if old sink exists and
-            // stop it by acquiring read lock
-            if let Some(sink) = &state.read().unwrap().sink {
-                sink.stop();
-            };
+    fn add(&self, song: Song) -> Reply<SongId> {
+        request(self, |reply| PlayerCommand::Add(song, reply))
+    }
+
+    fn add_source(&self, name: &str, source: BoxedSource) -> Reply<SongId> {
+        self.add(Song::from_source(name, OnceSource::new(source)))
+    }
+
+    fn add_folder(&self, path: &str, recursive: bool) -> Reply<io::Result<Vec<SongId>>> {
+        let path = path.to_string();
+        request(self, |reply| {
+            PlayerCommand::AddFolder(path, recursive, reply)
         })
     }
 
-    /// Clear the playlist
-    fn clear(&self) -> JoinHandle<()> {
-        // acquire an arc for this thread
-        let state = Arc::clone(&self);
-        spawn(move || {
-            let mut state = state.write().unwrap();
-            state.waiting_q.clear();
-            state.played_q.clear();
+    fn watch_folder(&self, path: &str, recursive: bool) -> Reply<io::Result<()>> {
+        let path = path.to_string();
+        request(self, |reply| {
+            PlayerCommand::WatchFolder(path, recursive, reply)
         })
     }
 
-    fn is_playing(&self) -> JoinHandle<bool> {
-        let state = Arc::clone(&self);
-        spawn(move || {
-            // acquire an arc for this thread
-            let res = state.read().unwrap().current.state == SongState::PLAY;
-            res
+    fn unwatch_folder(&self, path: &str) -> Reply<bool> {
+        let path = path.to_string();
+        request(self, |reply| PlayerCommand::UnwatchFolder(path, reply))
+    }
+
+    fn load_playlist(&self, playlist: &Playlist) -> Reply<()> {
+        let playlist = playlist.clone();
+        request(self, |reply| PlayerCommand::LoadPlaylist(playlist, reply))
+    }
+
+    fn create_playlist(&self, name: &str) -> Reply<bool> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::CreatePlaylist(name, reply))
+    }
+
+    fn add_to_playlist(&self, name: &str, song: Song) -> Reply<bool> {
+        let name = name.to_string();
+        request(self, |reply| {
+            PlayerCommand::AddToPlaylist(name, song, reply)
+        })
+    }
+
+    fn queue_playlist(&self, name: &str) -> Reply<bool> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::QueuePlaylist(name, reply))
+    }
+
+    fn playlist(&self, name: &str) -> Reply<Option<Playlist>> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::GetPlaylist(name, reply))
+    }
+
+    fn remove_playlist(&self, name: &str) -> Reply<Option<Playlist>> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::RemovePlaylist(name, reply))
+    }
+
+    fn playlist_names(&self) -> Reply<Vec<String>> {
+        request(self, PlayerCommand::PlaylistNames)
+    }
+
+    fn remove(&self, id: SongId) -> Reply<Option<Song>> {
+        request(self, |reply| PlayerCommand::Remove(id, reply))
+    }
+
+    fn move_song(&self, id: SongId, index: usize) -> Reply<bool> {
+        request(self, |reply| PlayerCommand::MoveSong(id, index, reply))
+    }
+
+    fn waiting_list(&self) -> Reply<Vec<Song>> {
+        request(self, PlayerCommand::WaitingList)
+    }
+
+    fn waiting_range(&self, offset: usize, len: usize) -> Reply<Vec<Song>> {
+        request(self, |reply| {
+            PlayerCommand::WaitingRange(offset, len, reply)
         })
     }
 
-    fn use_normal_play(&self) -> JoinHandle<()> {
-        let state = Arc::clone(&self);
-        spawn(move || {
-            state.write().unwrap().mode = PlaybackMode::NORMAL;
+    fn played_range(&self, offset: usize, len: usize) -> Reply<Vec<Song>> {
+        request(self, |reply| PlayerCommand::PlayedRange(offset, len, reply))
+    }
+
+    fn find(&self, text: &str) -> Reply<Vec<(usize, Song)>> {
+        let text = text.to_lowercase();
+        self.find_by(Box::new(move |song| {
+            song.name.to_lowercase().contains(&text) || song.path.to_lowercase().contains(&text)
+        }))
+    }
+
+    fn find_by(&self, predicate: Box<dyn Fn(&Song) -> bool + Send>) -> Reply<Vec<(usize, Song)>> {
+        request(self, |reply| PlayerCommand::Find(predicate, reply))
+    }
+
+    fn played_list(&self) -> Reply<Vec<Song>> {
+        request(self, PlayerCommand::PlayedList)
+    }
+
+    fn current_song(&self) -> Reply<ActiveSong> {
+        request(self, PlayerCommand::CurrentSong)
+    }
+
+    fn failed_list(&self) -> Reply<Vec<Song>> {
+        request(self, PlayerCommand::FailedList)
+    }
+
+    fn subscribe(&self) -> Reply<Receiver<PlayerEvent>> {
+        request(self, PlayerCommand::Subscribe)
+    }
+
+    fn watch(&self) -> Reply<Receiver<PlayerStatus>> {
+        request(self, PlayerCommand::Watch)
+    }
+
+    fn waiting_list_now(&self) -> Vec<Song> {
+        self.state().read().waiting_list()
+    }
+
+    fn played_list_now(&self) -> Vec<Song> {
+        self.state().read().played_list()
+    }
+
+    fn current_song_now(&self) -> ActiveSong {
+        self.hot().read().current_song()
+    }
+
+    fn is_playing_now(&self) -> bool {
+        self.hot().read().is_playing()
+    }
+
+    fn try_waiting_list(&self) -> Option<Vec<Song>> {
+        self.try_state().map(|state| state.waiting_list())
+    }
+
+    fn try_played_list(&self) -> Option<Vec<Song>> {
+        self.try_state().map(|state| state.played_list())
+    }
+
+    fn try_current_song(&self) -> Option<ActiveSong> {
+        self.try_hot().map(|hot| hot.current_song())
+    }
+
+    fn try_is_playing(&self) -> Option<bool> {
+        self.try_hot().map(|hot| hot.is_playing())
+    }
+
+    fn try_playback_state(&self) -> Option<PlaybackState> {
+        self.try_hot().map(|hot| hot.playback_state())
+    }
+
+    fn position(&self) -> Duration {
+        self.hot().read().position()
+    }
+
+    fn snapshot(&self) -> PlayerStatus {
+        self.state().read().status()
+    }
+
+    fn ui_state(&self, preview: usize) -> UiState {
+        self.state().read().ui_state(preview)
+    }
+
+    fn play(&self) -> Reply<()> {
+        request(self, PlayerCommand::Play)
+    }
+
+    fn toggle(&self) -> Reply<()> {
+        request(self, PlayerCommand::Toggle)
+    }
+
+    fn pause(&self) -> Reply<()> {
+        request(self, PlayerCommand::Pause)
+    }
+
+    fn resume(&self) -> Reply<()> {
+        request(self, PlayerCommand::Resume)
+    }
+
+    fn stop(&self) -> Reply<()> {
+        request(self, PlayerCommand::Stop)
+    }
+
+    fn skip(&self) -> Reply<()> {
+        request(self, PlayerCommand::Skip)
+    }
+
+    fn previous(&self) -> Reply<()> {
+        request(self, PlayerCommand::Previous)
+    }
+
+    fn play_song(&self, id: SongId) -> Reply<bool> {
+        request(self, |reply| PlayerCommand::PlaySong(id, reply))
+    }
+
+    fn play_index(&self, index: usize) -> Reply<bool> {
+        request(self, |reply| PlayerCommand::PlayIndex(index, reply))
+    }
+
+    fn set_volume(&self, volume: f32) -> Reply<()> {
+        self.set_volume_smooth(volume, DEFAULT_RAMP)
+    }
+
+    fn set_volume_smooth(&self, volume: f32, duration: Duration) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetVolume(volume, duration, reply)
         })
     }
 
-    fn use_auto_play(&self) -> JoinHandle<()> {
-        let state = Arc::clone(&self);
-        spawn(move || {
-            state.write().unwrap().mode = PlaybackMode::AUTO;
+    fn set_master_volume(&self, volume: f32) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetMasterVolume(volume, reply))
+    }
+
+    fn mute(&self) -> Reply<()> {
+        request(self, PlayerCommand::Mute)
+    }
+
+    fn unmute(&self) -> Reply<()> {
+        request(self, PlayerCommand::Unmute)
+    }
+
+    fn is_muted(&self) -> Reply<bool> {
+        request(self, PlayerCommand::IsMuted)
+    }
+
+    fn set_audio_focus(&self, focus: AudioFocus) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetAudioFocus(focus, reply))
+    }
+
+    fn seek(&self, pos: Duration) -> Reply<()> {
+        request(self, |reply| PlayerCommand::Seek(pos, reply))
+    }
+
+    fn seek_by(&self, secs: f32) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SeekBy(secs, reply))
+    }
+
+    fn next_chapter(&self) -> Reply<()> {
+        request(self, PlayerCommand::NextChapter)
+    }
+
+    fn prev_chapter(&self) -> Reply<()> {
+        request(self, PlayerCommand::PrevChapter)
+    }
+
+    fn goto_chapter(&self, index: usize) -> Reply<()> {
+        request(self, |reply| PlayerCommand::GotoChapter(index, reply))
+    }
+
+    fn set_loop_region(&self, start: Duration, end: Duration) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetLoopRegion(Some((start, end)), reply)
+        })
+    }
+
+    fn clear_loop_region(&self) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetLoopRegion(None, reply))
+    }
+
+    fn stop_after(&self, after: Duration, fade_out: Option<Duration>) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::StopAfter(after, fade_out, reply)
+        })
+    }
+
+    fn stop_after_current(&self) -> Reply<()> {
+        request(self, PlayerCommand::StopAfterCurrent)
+    }
+
+    fn cancel_stop_after(&self) -> Reply<()> {
+        request(self, PlayerCommand::CancelStopAfter)
+    }
+
+    fn set_eq(&self, bands: Vec<EqBand>) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetEq(bands, reply))
+    }
+
+    fn set_eq_preset(&self, preset: EqPreset) -> Reply<()> {
+        self.set_eq(preset.bands())
+    }
+
+    fn set_normalization(&self, normalization: Option<Normalization>) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetNormalization(normalization, reply)
+        })
+    }
+
+    fn set_limiter(&self, limiter: Limiter) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetLimiter(limiter, reply))
+    }
+
+    fn set_buffering(&self, buffering: Buffering) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetBuffering(buffering, reply))
+    }
+
+    fn set_cache_budget(&self, budget: usize) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetCacheBudget(budget, reply))
+    }
+
+    fn set_progress_interval(&self, interval: Duration) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetProgressInterval(interval, reply)
+        })
+    }
+
+    fn set_track_gap(&self, gap: Duration) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetTrackGap(gap, reply))
+    }
+
+    fn set_duck_level(&self, level: f32) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetDuckLevel(level, reply))
+    }
+
+    fn set_speed(&self, speed: f32) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetSpeed(speed, reply))
+    }
+
+    fn set_speed_mode(&self, mode: SpeedMode) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetSpeedMode(mode, reply))
+    }
+
+    #[cfg(feature = "time-stretch")]
+    fn set_pitch(&self, semitones: f32) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetPitch(semitones, reply))
+    }
+
+    fn preload(&self, song: Song) -> Reply<io::Result<()>> {
+        request(self, |reply| PlayerCommand::Preload(song, reply))
+    }
+
+    fn set_silence_trim(&self, silence_trim: Option<SilenceTrim>) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetSilenceTrim(silence_trim, reply)
+        })
+    }
+
+    fn remember_position(&self, enabled: bool) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::RememberPosition(enabled, reply)
+        })
+    }
+
+    #[cfg(feature = "recording")]
+    fn start_recording(&self, path: String) -> Reply<io::Result<()>> {
+        request(self, |reply| PlayerCommand::StartRecording(path, reply))
+    }
+
+    #[cfg(feature = "recording")]
+    fn stop_recording(&self) -> Reply<io::Result<()>> {
+        request(self, PlayerCommand::StopRecording)
+    }
+
+    fn set_retry_policy(&self, retry: RetryPolicy) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetRetryPolicy(retry, reply))
+    }
+
+    fn add_effect(&self, effect: Box<dyn Effect + Send + Sync>) -> Reply<()> {
+        request(self, |reply| PlayerCommand::AddEffect(effect, reply))
+    }
+
+    fn clear_effects(&self) -> Reply<()> {
+        request(self, PlayerCommand::ClearEffects)
+    }
+
+    fn set_balance(&self, balance: f32) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetBalance(balance, reply))
+    }
+
+    fn set_mono(&self, mono: bool) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetMono(mono, reply))
+    }
+
+    /// Clear the playlist
+    fn clear(&self) -> Reply<()> {
+        request(self, PlayerCommand::Clear)
+    }
+
+    fn clear_waiting(&self) -> Reply<()> {
+        request(self, PlayerCommand::ClearWaiting)
+    }
+
+    fn clear_history(&self) -> Reply<()> {
+        request(self, PlayerCommand::ClearHistory)
+    }
+
+    fn replay_history(&self) -> Reply<()> {
+        request(self, |reply| PlayerCommand::ReplayLast(None, reply))
+    }
+
+    fn replay_last(&self, n: usize) -> Reply<()> {
+        request(self, |reply| PlayerCommand::ReplayLast(Some(n), reply))
+    }
+
+    fn queue_len(&self) -> Reply<usize> {
+        request(self, PlayerCommand::QueueLen)
+    }
+
+    fn spectrum(&self, bins: usize) -> Reply<Vec<f32>> {
+        request(self, |reply| PlayerCommand::Spectrum(bins, reply))
+    }
+
+    fn levels(&self) -> Reply<(f32, f32)> {
+        request(self, PlayerCommand::Levels)
+    }
+
+    fn channel_levels(&self) -> Reply<Vec<(f32, f32)>> {
+        request(self, PlayerCommand::ChannelLevels)
+    }
+
+    fn queue_duration(&self) -> Reply<Duration> {
+        request(self, PlayerCommand::QueueDuration)
+    }
+
+    fn remaining_duration(&self) -> Reply<Duration> {
+        request(self, PlayerCommand::RemainingDuration)
+    }
+
+    fn is_playing(&self) -> Reply<bool> {
+        request(self, PlayerCommand::IsPlaying)
+    }
+
+    fn is_paused(&self) -> Reply<bool> {
+        request(self, PlayerCommand::IsPaused)
+    }
+
+    fn playback_state(&self) -> Reply<PlaybackState> {
+        request(self, PlayerCommand::GetPlaybackState)
+    }
+
+    fn playback_state_now(&self) -> PlaybackState {
+        self.hot().read().playback_state()
+    }
+
+    fn use_normal_play(&self) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetMode(PlaybackMode::NORMAL, reply)
+        })
+    }
+
+    fn use_auto_play(&self) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetMode(PlaybackMode::AUTO, reply)
+        })
+    }
+
+    fn use_continuous_play(&self) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetMode(PlaybackMode::CONTINUOUS, reply)
         })
     }
 
@@ -205,10 +658,136 @@ impl Player for SharedPlayer {
         with_generator: Box<
             dyn Fn() -> (rodio::OutputStream, rodio::OutputStreamHandle) + Send + Sync,
         >,
-    ) -> JoinHandle<()> {
-        let state = Arc::clone(&self);
-        spawn(move || {
-            state.write().unwrap().gen_out = with_generator;
+    ) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SetDeviceMaker(with_generator, reply)
+        })
+    }
+
+    fn set_stream_config(&self, config: StreamConfig) -> Reply<()> {
+        request(self, |reply| PlayerCommand::SetStreamConfig(config, reply))
+    }
+
+    fn switch_device(
+        &self,
+        with_generator: Box<
+            dyn Fn() -> (rodio::OutputStream, rodio::OutputStreamHandle) + Send + Sync,
+        >,
+    ) -> Reply<()> {
+        request(self, |reply| {
+            PlayerCommand::SwitchDevice(with_generator, reply)
         })
     }
+
+    fn select_device_by_name(&self, name: &str) -> Reply<bool> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::SelectDevice(name, reply))
+    }
+
+    fn add_mirror(
+        &self,
+        name: &str,
+        with_generator: Box<
+            dyn Fn() -> (rodio::OutputStream, rodio::OutputStreamHandle) + Send + Sync,
+        >,
+    ) -> Reply<io::Result<()>> {
+        let name = name.to_string();
+        request(self, |reply| {
+            PlayerCommand::AddMirror(name, with_generator, reply)
+        })
+    }
+
+    fn remove_mirror(&self, name: &str) -> Reply<bool> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::RemoveMirror(name, reply))
+    }
+
+    fn set_mirror_volume(&self, name: &str, volume: f32) -> Reply<bool> {
+        let name = name.to_string();
+        request(self, |reply| {
+            PlayerCommand::SetMirrorVolume(name, volume, reply)
+        })
+    }
+
+    fn list_output_devices() -> Vec<DeviceInfo> {
+        device::list_output_devices()
+    }
+
+    #[cfg(feature = "test-backend")]
+    fn use_null_output(&self, output: NullOutput) -> Reply<()> {
+        request(self, |reply| PlayerCommand::UseNullOutput(output, reply))
+    }
+
+    fn channel(&self, name: &str) -> Reply<io::Result<Channel>> {
+        let name = name.to_string();
+        request(self, |reply| PlayerCommand::Channel(name, reply))
+    }
+
+    fn set_ducking(
+        &self,
+        source_channel: &str,
+        target_channel: &str,
+        amount: f32,
+        attack: Duration,
+        release: Duration,
+    ) -> Reply<io::Result<()>> {
+        let channels = [source_channel.to_string(), target_channel.to_string()];
+        request(self, |reply| {
+            PlayerCommand::SetDucking(channels, amount, [attack, release], reply)
+        })
+    }
+
+    fn play_once(&self, song: Song) -> Reply<io::Result<()>> {
+        request(self, |reply| PlayerCommand::PlayOnce(song, reply))
+    }
+
+    fn play_overlapping(&self, song: Song) -> Reply<io::Result<()>> {
+        request(self, |reply| PlayerCommand::PlayOverlapping(song, reply))
+    }
+
+    fn play_spatial(
+        &self,
+        song: Song,
+        emitter: [f32; 3],
+        left_ear: [f32; 3],
+        right_ear: [f32; 3],
+    ) -> Reply<io::Result<SpatialHandle>> {
+        request(self, |reply| {
+            PlayerCommand::PlaySpatial(song, [emitter, left_ear, right_ear], reply)
+        })
+    }
+
+    fn shutdown(&self) -> Reply<()> {
+        request(self, PlayerCommand::Shutdown)
+    }
+
+    fn save_state(&self) -> Reply<PlayerSnapshot> {
+        request(self, PlayerCommand::SaveState)
+    }
+
+    fn load_state(&self, snapshot: PlayerSnapshot) -> Reply<()> {
+        request(self, |reply| PlayerCommand::LoadState(snapshot, reply))
+    }
+
+    fn stats(&self) -> Reply<PlayStats> {
+        request(self, PlayerCommand::Stats)
+    }
+
+    fn load_stats(&self, stats: PlayStats) -> Reply<()> {
+        request(self, |reply| PlayerCommand::LoadStats(stats, reply))
+    }
+
+    fn metrics(&self) -> Reply<PlayerMetrics> {
+        request(self, PlayerCommand::Metrics)
+    }
+
+    #[cfg(feature = "serde")]
+    fn save_session(&self, path: String) -> Reply<io::Result<()>> {
+        request(self, |reply| PlayerCommand::SaveSession(path, reply))
+    }
+
+    #[cfg(feature = "serde")]
+    fn restore_session(&self, path: String) -> Reply<io::Result<()>> {
+        request(self, |reply| PlayerCommand::RestoreSession(path, reply))
+    }
 }
@@ -0,0 +1,181 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Source};
+
+use crate::channels::SharedF32;
+
+/// Interval of measuring the level of a channel, and
+/// of following the levels of the channels ducking it
+const SIDECHAIN_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Level above which the output of a channel is audible, -60 dB
+const AUDIBLE: f32 = 0.001;
+
+/// Latest level and the volume of a channel
+pub(crate) struct ChannelLevel {
+    pub peak: SharedF32,
+    pub volume: SharedF32,
+}
+
+impl Default for ChannelLevel {
+    fn default() -> Self {
+        ChannelLevel {
+            peak: SharedF32::new(0.0),
+            volume: SharedF32::new(1.0),
+        }
+    }
+}
+
+impl ChannelLevel {
+    fn is_audible(&self) -> bool {
+        self.peak.get() * self.volume.get() > AUDIBLE
+    }
+}
+
+/// Ducking of the target channel while the source
+/// channel is audible
+pub(crate) struct Sidechain {
+    pub source: String,
+    pub target: String,
+    pub level: Arc<ChannelLevel>,
+    /// Ratio of the volume taken away, 1.0 to silence it
+    pub amount: f32,
+    pub attack: Duration,
+    pub release: Duration,
+}
+
+pub(crate) type SharedSidechains = Arc<Mutex<Vec<Sidechain>>>;
+
+/// Source adapter measuring the peak of its samples
+/// into the level of its channel
+pub(crate) struct Meter<S> {
+    input: S,
+    level: Arc<ChannelLevel>,
+    peak: f32,
+    left: usize,
+}
+
+impl<S> Meter<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, level: Arc<ChannelLevel>) -> Meter<S> {
+        Meter {
+            input,
+            level,
+            peak: 0.0,
+            left: 0,
+        }
+    }
+}
+
+impl<S> Iterator for Meter<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.input.next()?;
+        self.peak = self.peak.max(sample.abs());
+        if self.left == 0 {
+            self.level.peak.set(self.peak);
+            self.peak = 0.0;
+            let frames = self.input.sample_rate() as f32 * SIDECHAIN_INTERVAL.as_secs_f32();
+            self.left = (frames as usize).max(1) * self.input.channels() as usize;
+        }
+        self.left -= 1;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Meter<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
+
+impl<S> Drop for Meter<S> {
+    fn drop(&mut self) {
+        // a finished song ducks nothing
+        self.level.peak.set(0.0);
+    }
+}
+
+/// Move the gain toward the target, taking `time` for
+/// a change from silence to full volume
+fn glide(gain: f32, target: f32, time: Duration) -> f32 {
+    if time.is_zero() {
+        return target;
+    }
+    let step = SIDECHAIN_INTERVAL.as_secs_f32() / time.as_secs_f32();
+    gain + (target - gain).clamp(-step, step)
+}
+
+/// Duck the source of the channel while the channels
+/// ducking it are audible
+pub(crate) fn with_sidechain<S>(
+    source: S,
+    channel: String,
+    sidechains: SharedSidechains,
+) -> impl Source<Item = f32> + Send
+where
+    S: Source<Item = f32> + Send,
+{
+    // gain under each channel ducking this one
+    let mut gains: HashMap<String, f32> = HashMap::new();
+    source
+        .amplify(1.0)
+        .periodic_access(SIDECHAIN_INTERVAL, move |source| {
+            // never block the audio thread, try again later
+            let Ok(sidechains) = sidechains.try_lock() else {
+                return;
+            };
+            gains.retain(|name, _| {
+                sidechains
+                    .iter()
+                    .any(|sidechain| sidechain.target == channel && &sidechain.source == name)
+            });
+            for sidechain in sidechains.iter().filter(|s| s.target == channel) {
+                let gain = gains.get(&sidechain.source).copied().unwrap_or(1.0);
+                let gain = match sidechain.level.is_audible() {
+                    true => glide(gain, 1.0 - sidechain.amount, sidechain.attack),
+                    false => glide(gain, 1.0, sidechain.release),
+                };
+                match gains.get_mut(&sidechain.source) {
+                    Some(old) => *old = gain,
+                    None => {
+                        gains.insert(sidechain.source.clone(), gain);
+                    }
+                }
+            }
+            source.set_factor(gains.values().product());
+        })
+}
@@ -0,0 +1,46 @@
+use std::{
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+use crate::poison::Recover;
+
+/// Wake-up of threads waiting for a change, e.g. the
+/// play loop waiting for songs to be added
+#[derive(Default)]
+pub(crate) struct Signal {
+    // number of notifications so far
+    generation: Mutex<u64>,
+    changed: Condvar,
+}
+
+impl Signal {
+    /// Take the generation before checking for the change,
+    /// then wait for it, so that no notification is missed
+    pub fn generation(&self) -> u64 {
+        *self.generation.lock().recover()
+    }
+
+    pub fn notify(&self) {
+        *self.generation.lock().recover() += 1;
+        self.changed.notify_all();
+    }
+
+    /// Wait until notified after the generation was taken
+    pub fn wait(&self, generation: u64) {
+        let current = self.generation.lock().recover();
+        let _current = self
+            .changed
+            .wait_while(current, |current| *current == generation)
+            .recover();
+    }
+
+    /// Wait as `wait` does, but no longer than `timeout`
+    pub fn wait_timeout(&self, generation: u64, timeout: Duration) {
+        let current = self.generation.lock().recover();
+        let _current = self
+            .changed
+            .wait_timeout_while(current, timeout, |current| *current == generation)
+            .recover();
+    }
+}
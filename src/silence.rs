@@ -0,0 +1,174 @@
+use std::{collections::VecDeque, time::Duration};
+
+use rodio::{source::SeekError, Source};
+
+/// Settings to skip silence at both ends of each song
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SilenceTrim {
+    /// Amplitude below which a sample is silent, from 0.0 to 1.0
+    pub threshold: f32,
+    /// Most silence to skip at each end of the song
+    pub max_trim: Duration,
+}
+
+impl Default for SilenceTrim {
+    /// Skip up to 5 seconds below -60 dBFS
+    fn default() -> Self {
+        SilenceTrim {
+            threshold: 0.001,
+            max_trim: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Frames read ahead at most on each sample played,
+/// faster than played until far enough ahead
+const READ_AHEAD: usize = 2;
+
+/// Source adapter skipping leading and trailing silence,
+/// passing the input through when trimming is disabled
+///
+/// The leading silence is skipped once it's made, on the
+/// thread opening the song; the input is then read ahead by
+/// `max_trim` to drop the silence left once it ends
+pub struct TrimSilence<S> {
+    input: S,
+    trim: Option<SilenceTrim>,
+    // leading silence skipped
+    lead: Duration,
+    // samples read ahead of the ones played
+    ahead: VecDeque<f32>,
+    // silent samples at the back of `ahead`
+    silent_back: usize,
+    ended: bool,
+}
+
+impl<S> TrimSilence<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, trim: Option<SilenceTrim>) -> TrimSilence<S> {
+        let mut source = TrimSilence {
+            input,
+            trim,
+            lead: Duration::ZERO,
+            ahead: VecDeque::new(),
+            silent_back: 0,
+            ended: false,
+        };
+        if let Some(trim) = trim {
+            source.skip_lead(trim);
+        }
+        source
+    }
+
+    /// Samples in `max_trim` of the current format
+    fn max_samples(&self, trim: &SilenceTrim) -> usize {
+        let rate = self.input.sample_rate() as f64 * self.input.channels() as f64;
+        (trim.max_trim.as_secs_f64() * rate) as usize
+    }
+
+    /// Read a frame ahead, returns `false` if the input is over
+    fn read_frame(&mut self, trim: &SilenceTrim) -> bool {
+        let channels = self.input.channels().max(1) as usize;
+        let len = self.ahead.len();
+        self.ahead.extend(self.input.by_ref().take(channels));
+        let read = self.ahead.len() - len;
+        if read == 0 {
+            self.ended = true;
+            return false;
+        }
+        let silent = self
+            .ahead
+            .range(len..)
+            .all(|sample| sample.abs() < trim.threshold);
+        self.silent_back = match silent {
+            true => self.silent_back + read,
+            false => 0,
+        };
+        true
+    }
+
+    /// Skip the leading silence, the rest of a
+    /// long one is played
+    fn skip_lead(&mut self, trim: SilenceTrim) {
+        let max_samples = self.max_samples(&trim);
+        let mut skipped = 0;
+        while skipped < max_samples && self.read_frame(&trim) {
+            if self.silent_back == 0 {
+                break;
+            }
+            skipped += self.ahead.len();
+            self.ahead.clear();
+            self.silent_back = 0;
+        }
+        let rate = self.input.sample_rate() as f64 * self.input.channels().max(1) as f64;
+        self.lead = Duration::from_secs_f64(skipped as f64 / rate);
+    }
+}
+
+impl<S> Iterator for TrimSilence<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let Some(trim) = self.trim else {
+            return self.input.next();
+        };
+        let max_samples = self.max_samples(&trim);
+        for _ in 0..READ_AHEAD {
+            if self.ended || self.ahead.len() > max_samples || !self.read_frame(&trim) {
+                break;
+            }
+        }
+        // the silence left at the end is dropped here
+        if self.ended && self.ahead.len() <= self.silent_back.min(max_samples) {
+            self.ahead.clear();
+            self.silent_back = 0;
+            return None;
+        }
+        let sample = self.ahead.pop_front()?;
+        self.silent_back = self.silent_back.min(self.ahead.len());
+        Some(sample)
+    }
+}
+
+impl<S> Source for TrimSilence<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.trim {
+            Some(_) => None,
+            None => self.input.current_frame_len(),
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    /// Length without the leading silence, the trailing
+    /// one is unknown until the end is read
+    fn total_duration(&self) -> Option<Duration> {
+        let total = self.input.total_duration()?;
+        Some(total.saturating_sub(self.lead))
+    }
+
+    /// Seek in the song without the leading silence,
+    /// silence after a seek is on purpose
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos + self.lead)?;
+        self.ahead.clear();
+        self.silent_back = 0;
+        self.ended = false;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use std::{
+    sync::Arc,
+    thread::{sleep, spawn},
+    time::{Duration, Instant},
+};
+
+use crate::{logging::log, shared_player::SharedState};
+
+/// Interval of checking the sleep timer and
+/// stepping its fade out
+const TIMER_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Run the sleep timer of the player, fading out the song
+/// playing toward the deadline and stopping it there; the
+/// timer is over once it's cancelled or replaced
+pub(crate) fn start(state: &SharedState, deadline: Instant, fade_out: Duration) {
+    let timer = Some((deadline, fade_out));
+    state.write().sleep_timer = timer;
+    let state = Arc::downgrade(state);
+    spawn(move || loop {
+        let left = deadline.saturating_duration_since(Instant::now());
+        sleep(left.min(TIMER_INTERVAL));
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        let now = Instant::now();
+        if now < deadline {
+            let state = state.read();
+            if state.sleep_timer != timer {
+                return;
+            }
+            // anchored to the deadline, so that a song starting
+            // in the middle of the fade goes on from its level
+            let hot = state.hot.read();
+            if let (Some(sink), true) = (hot.active_sink(), now + fade_out >= deadline) {
+                let gain = hot.current.song.as_ref().and_then(|song| song.gain);
+                let left = (deadline - now).as_secs_f32() / fade_out.as_secs_f32();
+                sink.set_volume(state.sink_volume(gain) * left);
+            }
+            continue;
+        }
+        let mut state = state.write();
+        if state.sleep_timer != timer {
+            return;
+        }
+        // nothing playing now is left alone, the
+        // timer doesn't carry over to a later play
+        state.sleep_timer = None;
+        if state.loop_running {
+            log!(info, "the sleep timer is up, stopping");
+            state.stop_after_current = true;
+            state.queue_changed.notify();
+            if let Some(sink) = &state.hot.read().sink {
+                sink.stop();
+            }
+        }
+        return;
+    });
+}
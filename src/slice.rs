@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use rodio::{source::SeekError, Source};
+
+/// Source adapter playing only a part of its input,
+/// from `start` until `end` if it's given
+///
+/// Positions of seeking and total duration are relative
+/// to `start`, so the slice looks like a whole song
+pub struct Slice<S> {
+    input: S,
+    start: Duration,
+    length: Option<Duration>,
+    // samples played since `start`, in seconds
+    elapsed: f64,
+}
+
+impl<S> Slice<S>
+where
+    S: Source,
+    S::Item: rodio::Sample,
+{
+    pub fn new(mut input: S, start: Option<Duration>, end: Option<Duration>) -> Slice<S> {
+        let start = start.unwrap_or_default();
+        if !start.is_zero() && input.try_seek(start).is_err() {
+            // decode and drop the samples before start instead
+            let samples =
+                start.as_secs_f64() * input.sample_rate() as f64 * input.channels() as f64;
+            input.by_ref().take(samples as usize).for_each(drop);
+        }
+        Slice {
+            input,
+            start,
+            length: end.map(|end| end.saturating_sub(start)),
+            elapsed: 0.0,
+        }
+    }
+}
+
+impl<S> Iterator for Slice<S>
+where
+    S: Source,
+    S::Item: rodio::Sample,
+{
+    type Item = S::Item;
+
+    fn next(&mut self) -> Option<S::Item> {
+        if let Some(length) = self.length {
+            if self.elapsed >= length.as_secs_f64() {
+                return None;
+            }
+        }
+        let sample = self.input.next()?;
+        let per_sec = self.input.sample_rate() as f64 * self.input.channels().max(1) as f64;
+        self.elapsed += 1.0 / per_sec;
+        Some(sample)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.input.size_hint().1)
+    }
+}
+
+impl<S> Source for Slice<S>
+where
+    S: Source,
+    S::Item: rodio::Sample,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        let rest = self
+            .input
+            .total_duration()
+            .map(|total| total.saturating_sub(self.start));
+        match (rest, self.length) {
+            (Some(rest), Some(length)) => Some(rest.min(length)),
+            (rest, length) => rest.or(length),
+        }
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(self.start + pos)?;
+        self.elapsed = pos.as_secs_f64();
+        Ok(())
+    }
+}
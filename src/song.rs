@@ -1,18 +1,327 @@
-use std::time::Duration;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::Source;
+
+use crate::{
+    config::DecoderBackend,
+    ducking::Priority,
+    generator::{Generator, Waveform},
+    slice::Slice,
+    source::IntoAudioSource,
+    uri::{file_url, SongUri},
+};
+
+/// Frames summarized at once while computing a waveform
+const WAVEFORM_BLOCK: usize = 256;
+
+/// Identity of a song added to a player, unique in the
+/// process, so that duplicated songs can be told apart
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SongId(u64);
+
+impl SongId {
+    pub(crate) fn next() -> SongId {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        SongId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl From<SongId> for u64 {
+    fn from(id: SongId) -> u64 {
+        id.0
+    }
+}
+
+/// Named part of a song, e.g. a chapter of an audiobook
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Chapter {
+    pub name: String,
+    /// Position in the song where the chapter starts
+    pub start: Duration,
+}
 
 #[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Song {
+    /// Assigned when the song is added to a player
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub id: Option<SongId>,
     pub name: String,
+    /// File path, or a `file://`, `http://` or `data:`
+    /// URI of the audio, see [`Song::file`]
     pub path: String,
+    /// Volume factor of this song on top of the player volume
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub gain: Option<f32>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub artist: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub album: Option<String>,
+    /// Path of the cover image
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cover: Option<String>,
+    /// Position in the file where the song starts
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub start_at: Option<Duration>,
+    /// Position in the file where the song ends
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub end_at: Option<Duration>,
+    /// Custom tags of the application
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: HashMap<String, String>,
+    /// Chapters of the song, in order of their start
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub chapters: Vec<Chapter>,
+    /// Decoder of the file instead of the one of the player
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub decoder: Option<DecoderBackend>,
+    /// Priority instead of the one of its channel
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: Option<Priority>,
+    /// Where the song is played from instead of `path`,
+    /// which can't be saved along with the song
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub source: Option<Arc<dyn IntoAudioSource>>,
 }
 
 impl Song {
     pub fn from(name: String, path: String) -> Self {
-        Song { name, path }
+        Song {
+            name,
+            path,
+            ..Default::default()
+        }
+    }
+
+    /// Song played from the given source instead of a file
+    pub fn from_source(name: impl Into<String>, source: impl IntoAudioSource + 'static) -> Self {
+        Song {
+            name: name.into(),
+            source: Some(Arc::new(source)),
+            ..Default::default()
+        }
+    }
+
+    /// Sine wave of the frequency in Hz, e.g. for alarms
+    /// or testing an output device
+    pub fn sine(freq: f32, duration: Duration) -> Self {
+        Song::generated(format!("Sine {} Hz", freq), Waveform::SINE(freq), duration)
+    }
+
+    /// Silence of the given length, e.g. a gap between songs
+    pub fn silence(duration: Duration) -> Self {
+        Song::generated("Silence".into(), Waveform::SILENCE, duration)
+    }
+
+    /// White noise of the given length
+    pub fn white_noise(duration: Duration) -> Self {
+        Song::generated("White noise".into(), Waveform::WHITENOISE, duration)
+    }
+
+    /// Endless stream of an internet radio at the `http://` URL,
+    /// whose title on air is updated while it's played
+    #[cfg(feature = "radio")]
+    pub fn radio(name: impl Into<String>, url: impl Into<String>) -> Self {
+        Song::from_source(name, crate::radio::Radio::new(url))
+    }
+
+    /// Check whether the song can be played again once played
+    pub(crate) fn replayable(&self) -> bool {
+        self.source
+            .as_ref()
+            .is_none_or(|source| source.replayable())
+    }
+
+    /// Path of the file of the song, a `file://` URI resolved,
+    /// `None` for a stream, data carried by the URI, a bad
+    /// URI or a song played from a source
+    pub fn file(&self) -> Option<Cow<'_, str>> {
+        if self.source.is_some() {
+            return None;
+        }
+        match SongUri::parse(&self.path).ok()? {
+            SongUri::FILE(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// URL of the song for other apps to open, a `file://`
+    /// one for a file, `None` for data carried by the URI,
+    /// a bad URI or a song played from a source
+    pub fn url(&self) -> Option<String> {
+        if self.source.is_some() {
+            return None;
+        }
+        match SongUri::parse(&self.path).ok()? {
+            SongUri::FILE(path) => Some(file_url(&path)),
+            SongUri::HTTP(url) => Some(url.to_string()),
+            SongUri::DATA(_) => None,
+        }
+    }
+
+    fn generated(name: String, waveform: Waveform, duration: Duration) -> Self {
+        Song::from_source(name, Generator { waveform, duration })
+    }
+
+    /// Index of the chapter at the position of the song,
+    /// `None` if it's before the first chapter
+    pub fn chapter_at(&self, pos: Duration) -> Option<usize> {
+        self.chapters
+            .iter()
+            .rposition(|chapter| chapter.start <= pos)
+    }
+
+    /// Length of this song in a file of the given
+    /// length, considering its start and end
+    pub fn length_in(&self, file_length: Duration) -> Duration {
+        let end = self.end_at.map_or(file_length, |end| end.min(file_length));
+        end.saturating_sub(self.start_at.unwrap_or_default())
+    }
+
+    /// Decode the song and get its peak amplitude in each
+    /// of `buckets` equal parts, e.g. to draw a seekbar;
+    /// empty if the file can't be decoded
+    pub fn compute_waveform(&self, buckets: usize) -> Vec<f32> {
+        let decoded = match &self.source {
+            Some(source) => source.open_f32(),
+            None => SongUri::parse(&self.path).and_then(SongUri::open_f32),
+        };
+        let Ok(decoded) = decoded else {
+            return vec![];
+        };
+        let source = Slice::new(decoded, self.start_at, self.end_at);
+        // summarize blocks first so that the whole song
+        // needn't be kept in memory
+        let block = WAVEFORM_BLOCK * source.channels().max(1) as usize;
+        let mut peaks = vec![];
+        let (mut peak, mut count) = (0f32, 0);
+        for sample in source {
+            peak = peak.max(sample.abs());
+            count += 1;
+            if count == block {
+                peaks.push(peak);
+                (peak, count) = (0.0, 0);
+            }
+        }
+        if count > 0 {
+            peaks.push(peak);
+        }
+        if peaks.is_empty() {
+            return vec![];
+        }
+        (0..buckets)
+            .map(|bucket| {
+                let from = bucket * peaks.len() / buckets;
+                let to = ((bucket + 1) * peaks.len() / buckets).max(from + 1);
+                peaks[from..to].iter().copied().fold(0.0, f32::max)
+            })
+            .collect()
+    }
+
+    /// Start building a song with optional details
+    pub fn builder(name: impl Into<String>, path: impl Into<String>) -> SongBuilder {
+        SongBuilder {
+            song: Song::from(name.into(), path.into()),
+        }
+    }
+
+    /// Play this song louder (> 1.0) or quieter (< 1.0)
+    /// than the player volume
+    pub fn with_gain(mut self, gain: f32) -> Self {
+        self.gain = Some(gain);
+        self
+    }
+}
+
+/// Builder of [`Song`], made by `Song::builder`
+#[derive(Clone, Debug)]
+pub struct SongBuilder {
+    song: Song,
+}
+
+impl SongBuilder {
+    pub fn artist(mut self, artist: impl Into<String>) -> Self {
+        self.song.artist = Some(artist.into());
+        self
+    }
+
+    pub fn album(mut self, album: impl Into<String>) -> Self {
+        self.song.album = Some(album.into());
+        self
+    }
+
+    /// Path of the cover image
+    pub fn cover(mut self, cover: impl Into<String>) -> Self {
+        self.song.cover = Some(cover.into());
+        self
+    }
+
+    /// Volume factor on top of the player volume
+    pub fn gain(mut self, gain: f32) -> Self {
+        self.song.gain = Some(gain);
+        self
+    }
+
+    /// Start the song from the position in the file
+    pub fn start_at(mut self, pos: Duration) -> Self {
+        self.song.start_at = Some(pos);
+        self
+    }
+
+    /// End the song at the position in the file
+    pub fn end_at(mut self, pos: Duration) -> Self {
+        self.song.end_at = Some(pos);
+        self
+    }
+
+    /// Decode the file with the backend, whatever
+    /// the player uses
+    pub fn decoder(mut self, decoder: DecoderBackend) -> Self {
+        self.song.decoder = Some(decoder);
+        self
+    }
+
+    /// Priority of the song, e.g. `HIGH` for a voice
+    /// prompt ducking the music
+    pub fn priority(mut self, priority: Priority) -> Self {
+        self.song.priority = Some(priority);
+        self
+    }
+
+    /// Attach a custom tag
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.song.tags.insert(key.into(), value.into());
+        self
+    }
+
+    /// Add a chapter starting at the position in the song
+    pub fn chapter(mut self, name: impl Into<String>, start: Duration) -> Self {
+        let chapter = Chapter {
+            name: name.into(),
+            start,
+        };
+        let index = self.song.chapters.partition_point(|c| c.start <= start);
+        self.song.chapters.insert(index, chapter);
+        self
+    }
+
+    pub fn build(self) -> Song {
+        self.song
     }
 }
 
 #[derive(Clone, Copy, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum SongState {
     #[default]
     NONE,
@@ -23,20 +332,28 @@ pub enum SongState {
 }
 
 #[derive(Clone, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ActiveSong {
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub id: Option<SongId>,
     pub song: Option<Song>,
     pub state: SongState,
     pub progress: Duration,
     pub duration: Duration,
+    /// Title on air of a live stream, e.g. a radio
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub stream_title: Option<String>,
 }
 
 impl ActiveSong {
     pub fn from(song: Song, duration: Duration) -> ActiveSong {
         ActiveSong {
+            id: song.id,
             song: Some(song),
             state: SongState::NONE,
             progress: Duration::from_secs(0),
             duration,
+            stream_title: None,
         }
     }
 }
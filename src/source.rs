@@ -0,0 +1,118 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufReader, Cursor},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use rodio::{Decoder, Source};
+
+use crate::{effect::BoxedSource, poison::Recover};
+
+/// Something a song can be played from, opened each time
+/// the song is played, e.g. a file, an in-memory buffer,
+/// a network stream or generated audio
+///
+/// ```
+/// use std::io;
+/// use rodio::{source::SineWave, Source};
+/// use super_rodio::{IntoAudioSource, Song};
+///
+/// struct Beep;
+///
+/// impl IntoAudioSource for Beep {
+///     fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+///         let beep = SineWave::new(880.0).take_duration(std::time::Duration::from_millis(200));
+///         Ok(Box::new(beep.convert_samples()))
+///     }
+/// }
+///
+/// let song = Song::from_source("Beep", Beep);
+/// ```
+pub trait IntoAudioSource: Send + Sync {
+    /// Open a new source playing from the beginning
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>>;
+
+    /// Open it with samples in `f32`, which the player plays
+    fn open_f32(&self) -> io::Result<BoxedSource> {
+        Ok(Box::new(self.open()?.convert_samples()))
+    }
+
+    /// Title announced by a live stream while it's played,
+    /// e.g. the song on air of a radio
+    fn stream_title(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether it can be opened again once played, a song
+    /// which can't is never queued again, e.g. by `previous`
+    fn replayable(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for dyn IntoAudioSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("IntoAudioSource")
+    }
+}
+
+fn invalid_data(e: rodio::decoder::DecoderError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Audio file at the path
+impl IntoAudioSource for PathBuf {
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+        let file = BufReader::new(File::open(self)?);
+        Ok(Box::new(Decoder::new(file).map_err(invalid_data)?))
+    }
+}
+
+/// Encoded audio file in memory, e.g. downloaded or embedded
+/// with `include_bytes!`
+impl IntoAudioSource for Arc<[u8]> {
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+        let bytes = Cursor::new(Arc::clone(self));
+        Ok(Box::new(Decoder::new(bytes).map_err(invalid_data)?))
+    }
+}
+
+/// Function making the source
+impl<F> IntoAudioSource for F
+where
+    F: Fn() -> io::Result<Box<dyn Source<Item = i16> + Send>> + Send + Sync,
+{
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+        self()
+    }
+}
+
+/// Source which can be played only once, e.g. synthesized
+/// audio queued by `add_source`
+pub(crate) struct OnceSource(Mutex<Option<BoxedSource>>);
+
+impl OnceSource {
+    pub fn new(source: BoxedSource) -> OnceSource {
+        OnceSource(Mutex::new(Some(source)))
+    }
+}
+
+impl IntoAudioSource for OnceSource {
+    fn open(&self) -> io::Result<Box<dyn Source<Item = i16> + Send>> {
+        Ok(Box::new(self.open_f32()?.convert_samples()))
+    }
+
+    fn open_f32(&self) -> io::Result<BoxedSource> {
+        self.0
+            .lock()
+            .recover()
+            .take()
+            .ok_or_else(|| io::Error::other("the source has been played"))
+    }
+
+    fn replayable(&self) -> bool {
+        false
+    }
+}
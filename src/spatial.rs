@@ -0,0 +1,223 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Sink, Source};
+
+use crate::poison::Recover;
+
+/// Interval of reading the positions while playing
+const UPDATE_INTERVAL: Duration = Duration::from_millis(5);
+
+/// Time the gains glide to those of new positions
+const GLIDE: Duration = Duration::from_millis(20);
+
+/// Positions of a sound and the ears of its listener
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) struct Positions {
+    pub emitter: [f32; 3],
+    pub left_ear: [f32; 3],
+    pub right_ear: [f32; 3],
+}
+
+fn dist_sq(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a.iter().zip(b).map(|(a, b)| (a - b) * (a - b)).sum()
+}
+
+impl Positions {
+    /// Gains of the left and right channel, by the model of
+    /// `rodio::source::Spatial` but louder on the nearer ear
+    pub fn gains(&self) -> [f32; 2] {
+        let left_sq = dist_sq(self.left_ear, self.emitter);
+        let right_sq = dist_sq(self.right_ear, self.emitter);
+        let ears = dist_sq(self.left_ear, self.right_ear)
+            .sqrt()
+            .max(f32::EPSILON);
+        let (left, right) = (left_sq.sqrt(), right_sq.sqrt());
+        let diff = |ear: f32, other: f32| (((other - ear) / ears + 1.0) / 4.0 + 0.5).min(1.0);
+        [
+            diff(left, right) * (1.0 / left_sq).min(1.0),
+            diff(right, left) * (1.0 / right_sq).min(1.0),
+        ]
+    }
+}
+
+type SharedPositions = Arc<Mutex<Positions>>;
+
+/// Source adapter playing the input downmixed to mono on
+/// two channels by the positions, gliding to the gains
+/// of new positions instead of stepping to them
+pub(crate) struct Spatializer<S> {
+    input: S,
+    positions: SharedPositions,
+    gains: [f32; 2],
+    target: [f32; 2],
+    steps: [f32; 2],
+    // frames left of the glide, and until the next update
+    gliding: u32,
+    until_update: u32,
+    mono: f32,
+    channel: usize,
+}
+
+impl<S> Spatializer<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, positions: SharedPositions) -> Spatializer<S> {
+        let gains = positions.lock().recover().gains();
+        Spatializer {
+            input,
+            positions,
+            gains,
+            target: gains,
+            steps: [0.0; 2],
+            gliding: 0,
+            until_update: 0,
+            mono: 0.0,
+            channel: 0,
+        }
+    }
+
+    fn frames_in(&self, duration: Duration) -> u32 {
+        (self.input.sample_rate() as f32 * duration.as_secs_f32()).max(1.0) as u32
+    }
+
+    /// Glide to the gains of the latest positions
+    fn update(&mut self) {
+        self.until_update = self.frames_in(UPDATE_INTERVAL);
+        // never block the audio thread, try again later
+        let Ok(positions) = self.positions.try_lock() else {
+            return;
+        };
+        let target = positions.gains();
+        drop(positions);
+        if target == self.target {
+            return;
+        }
+        self.target = target;
+        let frames = self.frames_in(GLIDE);
+        for ((step, gain), target) in self.steps.iter_mut().zip(self.gains).zip(target) {
+            *step = (target - gain) / frames as f32;
+        }
+        self.gliding = frames;
+    }
+
+    /// Read the next frame of the input as mono
+    fn next_frame(&mut self) -> Option<f32> {
+        let channels = self.input.channels().max(1);
+        let mut sum = 0.0;
+        for _ in 0..channels {
+            sum += self.input.next()?;
+        }
+        if self.until_update == 0 {
+            self.update();
+        }
+        self.until_update -= 1;
+        if self.gliding > 0 {
+            self.gliding -= 1;
+            for (gain, step) in self.gains.iter_mut().zip(self.steps) {
+                *gain += step;
+            }
+            if self.gliding == 0 {
+                self.gains = self.target;
+            }
+        }
+        Some(sum / channels as f32)
+    }
+}
+
+impl<S> Iterator for Spatializer<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.channel == 0 {
+            self.mono = self.next_frame()?;
+        }
+        let sample = self.mono * self.gains[self.channel];
+        self.channel ^= 1;
+        Some(sample)
+    }
+}
+
+impl<S> Source for Spatializer<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        let channels = self.input.channels().max(1) as usize;
+        self.input.current_frame_len().map(|len| len / channels * 2)
+    }
+
+    fn channels(&self) -> u16 {
+        2
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.channel = 0;
+        Ok(())
+    }
+}
+
+/// Handle of a sound played at a position in 3D space,
+/// made by `play_spatial`; the sound stops once the
+/// handle is dropped
+pub struct SpatialHandle {
+    sink: Arc<Sink>,
+    positions: SharedPositions,
+}
+
+impl SpatialHandle {
+    /// Play the source on the sink at the positions
+    pub(crate) fn new<S>(sink: Arc<Sink>, source: S, positions: Positions) -> SpatialHandle
+    where
+        S: Source<Item = f32> + Send + 'static,
+    {
+        let positions = Arc::new(Mutex::new(positions));
+        sink.append(Spatializer::new(source, Arc::clone(&positions)));
+        SpatialHandle { sink, positions }
+    }
+
+    /// Move the sound to the position, e.g. on every frame
+    /// of a game; the sound glides there smoothly
+    pub fn set_position(&self, emitter: [f32; 3]) {
+        self.positions.lock().recover().emitter = emitter;
+    }
+
+    /// Move the ears of the listener to the positions
+    pub fn set_ear_positions(&self, left_ear: [f32; 3], right_ear: [f32; 3]) {
+        let mut positions = self.positions.lock().recover();
+        positions.left_ear = left_ear;
+        positions.right_ear = right_ear;
+    }
+
+    pub fn pause(&self) {
+        self.sink.pause();
+    }
+
+    pub fn resume(&self) {
+        self.sink.play();
+    }
+
+    pub fn stop(&self) {
+        self.sink.stop();
+    }
+
+    /// Check whether the sound is over
+    pub fn is_done(&self) -> bool {
+        self.sink.empty()
+    }
+}
@@ -0,0 +1,69 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use rodio::{source::SeekError, Source};
+
+/// Source adapter telling whether the output is waiting
+/// for its next sample, e.g. from a slow network stream,
+/// which isn't a stall of the output
+pub(crate) struct Pulling<S> {
+    input: S,
+    pulling: Arc<AtomicBool>,
+}
+
+impl<S> Pulling<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, pulling: Arc<AtomicBool>) -> Pulling<S> {
+        Pulling { input, pulling }
+    }
+}
+
+impl<S> Iterator for Pulling<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.pulling.store(true, Ordering::Relaxed);
+        let sample = self.input.next();
+        self.pulling.store(false, Ordering::Relaxed);
+        sample
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.input.size_hint()
+    }
+}
+
+impl<S> Source for Pulling<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        self.input.current_frame_len()
+    }
+
+    fn channels(&self) -> u16 {
+        self.input.channels()
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)
+    }
+}
@@ -0,0 +1,34 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    time::{Duration, SystemTime},
+};
+
+use crate::song::Song;
+
+/// Listening statistics of a song
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SongStats {
+    /// Times the song is played, to the end or not
+    pub plays: u32,
+    /// Times the song is skipped in the middle
+    pub skips: u32,
+    /// Time the song is played, pauses and seeks excluded
+    pub listen_time: Duration,
+    /// When the song was played last time
+    pub last_played: Option<SystemTime>,
+}
+
+/// Statistics of the songs played, by their path, with
+/// `file://` URIs resolved, or their name if they're
+/// played from a source
+pub type PlayStats = HashMap<String, SongStats>;
+
+/// Key of the song in [`PlayStats`]
+pub fn stats_key(song: &Song) -> Cow<'_, str> {
+    match song.source {
+        Some(_) => Cow::Borrowed(&song.name),
+        None => song.file().unwrap_or(Cow::Borrowed(&song.path)),
+    }
+}
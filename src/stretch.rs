@@ -0,0 +1,471 @@
+#[cfg(feature = "time-stretch")]
+use std::{collections::VecDeque, f32::consts::PI};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+#[cfg(feature = "time-stretch")]
+use rodio::{source::SeekError, Source};
+
+use crate::channels::SharedF32;
+
+/// Length of a segment overlapped with the next ones
+#[cfg(feature = "time-stretch")]
+const SEGMENT: Duration = Duration::from_millis(20);
+
+/// Farthest a segment is moved to match the last one
+#[cfg(feature = "time-stretch")]
+const TOLERANCE: Duration = Duration::from_millis(5);
+
+/// Samples skipped when matching segments, trading
+/// precision for time
+#[cfg(feature = "time-stretch")]
+const MATCH_STRIDE: usize = 4;
+
+/// Range of the factor of a time stretch
+#[cfg(feature = "time-stretch")]
+const MIN_FACTOR: f32 = 1.0 / 16.0;
+#[cfg(feature = "time-stretch")]
+const MAX_FACTOR: f32 = 16.0;
+
+/// Farthest the pitch is shifted, two octaves
+#[cfg(feature = "time-stretch")]
+const MAX_SEMITONES: f32 = 24.0;
+
+/// Speed and pitch of the time stretch of the player, and
+/// how far the song is ahead of the output of the sink by it
+pub(crate) struct Stretch {
+    factor: SharedF32,
+    // ratio of the frequencies
+    #[cfg(feature = "time-stretch")]
+    pitch: SharedF32,
+    // seconds, as the bits of a f64
+    drift: AtomicU64,
+}
+
+pub(crate) type SharedStretch = Arc<Stretch>;
+
+impl Default for Stretch {
+    fn default() -> Self {
+        Stretch {
+            factor: SharedF32::new(1.0),
+            #[cfg(feature = "time-stretch")]
+            pitch: SharedF32::new(1.0),
+            drift: AtomicU64::new(0.0f64.to_bits()),
+        }
+    }
+}
+
+impl Stretch {
+    pub fn set_factor(&self, factor: f32) {
+        self.factor.set(factor);
+    }
+
+    /// Shift the pitch by the semitones, resampling the
+    /// songs stretched to keep their speed
+    #[cfg(feature = "time-stretch")]
+    pub fn set_pitch(&self, semitones: f32) {
+        let semitones = semitones.clamp(-MAX_SEMITONES, MAX_SEMITONES);
+        self.pitch.set(2f32.powf(semitones / 12.0));
+    }
+
+    /// Factor of the time stretch, slowed down as much
+    /// as the resampling after it speeds up
+    #[cfg(feature = "time-stretch")]
+    fn stretch_factor(&self) -> f32 {
+        (self.factor.get() / self.pitch.get()).clamp(MIN_FACTOR, MAX_FACTOR)
+    }
+
+    /// Position in the song of the output at `pos`
+    pub fn song_pos(&self, pos: Duration) -> Duration {
+        let drift = f64::from_bits(self.drift.load(Ordering::Relaxed));
+        Duration::from_secs_f64((pos.as_secs_f64() + drift).max(0.0))
+    }
+
+    #[cfg(feature = "time-stretch")]
+    fn add_drift(&self, secs: f64) {
+        let drift = f64::from_bits(self.drift.load(Ordering::Relaxed));
+        self.drift
+            .store((drift + secs).to_bits(), Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "time-stretch")]
+    fn reset_drift(&self) {
+        self.drift.store(0.0f64.to_bits(), Ordering::Relaxed);
+    }
+}
+
+/// Source adapter changing the speed of the input by the
+/// factor, keeping its pitch, by WSOLA: segments of the input
+/// are overlapped at a fixed hop, each taken a hop times the
+/// factor after the last one, moved a bit to match it best
+///
+/// The input passes through untouched at the factor of 1.0
+#[cfg(feature = "time-stretch")]
+pub(crate) struct TimeStretch<S> {
+    input: S,
+    stretch: SharedStretch,
+    channels: usize,
+    // frames of a segment and of a hop, half a segment
+    len: usize,
+    hop: usize,
+    tolerance: usize,
+    window: Vec<f32>,
+    // frames read from the input, interleaved
+    read: VecDeque<f32>,
+    // overlapped segments, of one segment long
+    overlap: Vec<f32>,
+    ready: VecDeque<f32>,
+    // where the next segment is taken nominally, and the
+    // start of the last one, in frames of `read`
+    next: f64,
+    last: Option<usize>,
+    // frames of `read` before the input ended
+    end: Option<usize>,
+    finished: bool,
+}
+
+#[cfg(feature = "time-stretch")]
+impl<S> TimeStretch<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, stretch: SharedStretch) -> TimeStretch<S> {
+        let mut stretch = TimeStretch {
+            input,
+            stretch,
+            channels: 1,
+            len: 2,
+            hop: 1,
+            tolerance: 0,
+            window: vec![],
+            read: VecDeque::new(),
+            overlap: vec![],
+            ready: VecDeque::new(),
+            next: 0.0,
+            last: None,
+            end: None,
+            finished: false,
+        };
+        stretch.reset();
+        stretch
+    }
+
+    /// Start over by the format of the input
+    fn reset(&mut self) {
+        let rate = self.input.sample_rate() as f32;
+        self.channels = self.input.channels().max(1) as usize;
+        self.hop = ((rate * SEGMENT.as_secs_f32()) as usize / 2).max(1);
+        self.len = self.hop * 2;
+        self.tolerance = (rate * TOLERANCE.as_secs_f32()) as usize;
+        // periodic Hann, summing to 1 overlapped by half
+        self.window = (0..self.len)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / self.len as f32).cos())
+            .collect();
+        self.read.clear();
+        self.overlap = vec![0.0; self.len * self.channels];
+        self.ready.clear();
+        self.next = 0.0;
+        self.last = None;
+        self.end = None;
+        self.finished = false;
+        self.stretch.reset_drift();
+    }
+
+    fn frames(&self) -> usize {
+        self.read.len() / self.channels
+    }
+
+    /// Read the input until there are the frames,
+    /// padded with silence once it ends
+    fn fill(&mut self, frames: usize) {
+        while self.frames() < frames {
+            match self.end {
+                Some(_) => self.read.push_back(0.0),
+                None => match self.input.next() {
+                    Some(sample) => self.read.push_back(sample),
+                    None => self.end = Some(self.frames()),
+                },
+            }
+        }
+    }
+
+    /// Sum of the channels of the frame
+    fn mono(&self, frame: usize) -> f32 {
+        let start = frame * self.channels;
+        (start..start + self.channels).map(|i| self.read[i]).sum()
+    }
+
+    /// Start of the segment near `next` matching the
+    /// continuation of the last segment best
+    fn best_start(&self, last: usize) -> usize {
+        let nominal = self.next.round() as usize;
+        let (from, to) = (
+            nominal.saturating_sub(self.tolerance),
+            nominal + self.tolerance,
+        );
+        let template = last + self.hop;
+        let mut best = (f32::MIN, nominal);
+        for start in from..=to {
+            let score: f32 = (0..self.len)
+                .step_by(MATCH_STRIDE)
+                .map(|i| self.mono(template + i) * self.mono(start + i))
+                .sum();
+            if score > best.0 {
+                best = (score, start);
+            }
+        }
+        best.1
+    }
+
+    /// Overlap the next segment, making a hop of output
+    fn step(&mut self, factor: f32) {
+        let nominal = self.next.round() as usize;
+        let needed = match self.last {
+            Some(last) => (nominal + self.tolerance).max(last + self.hop) + self.len,
+            None => nominal + self.len,
+        };
+        self.fill(needed);
+        let start = match self.last {
+            Some(last) => self.best_start(last),
+            None => nominal,
+        };
+        for i in 0..self.len {
+            // nothing to fade in from at first
+            let gain = match self.last {
+                None if i < self.hop => 1.0,
+                _ => self.window[i],
+            };
+            for c in 0..self.channels {
+                self.overlap[i * self.channels + c] +=
+                    gain * self.read[(start + i) * self.channels + c];
+            }
+        }
+        let hop = self.hop * self.channels;
+        self.ready.extend(self.overlap.drain(..hop));
+        self.overlap.resize(self.len * self.channels, 0.0);
+        let taken = self.hop as f64 * factor as f64;
+        self.next += taken;
+        let rate = self.input.sample_rate().max(1) as f64;
+        self.stretch.add_drift((taken - self.hop as f64) / rate);
+        // drop the frames no segment can take any more
+        let drop = start.min(self.next as usize).saturating_sub(self.tolerance);
+        self.read.drain(..drop * self.channels);
+        self.next -= drop as f64;
+        self.last = Some(start - drop);
+        self.end = self.end.map(|end| end.saturating_sub(drop));
+    }
+
+    /// Fade out of the overlapped segments into the input
+    /// right after the last one, to pass the input through
+    fn pass_through(&mut self, last: usize) {
+        self.fill(last + self.len);
+        for i in 0..self.hop {
+            let gain = 1.0 - self.window[self.hop + i];
+            for c in 0..self.channels {
+                let sample = self.read[(last + self.hop + i) * self.channels + c];
+                self.ready
+                    .push_back(self.overlap[i * self.channels + c] + gain * sample);
+            }
+        }
+        let end = self.end.unwrap_or(self.frames()).max(last + self.len);
+        let rest = (last + self.len) * self.channels..end * self.channels;
+        self.ready.extend(self.read.range(rest));
+        self.read.clear();
+        self.overlap.fill(0.0);
+        self.next = 0.0;
+        self.last = None;
+        self.finished = self.end.is_some();
+    }
+
+    /// Let the last segment fade out once the input is over
+    fn finish(&mut self) {
+        if self.last.is_some() {
+            self.ready.extend(&self.overlap[..self.hop * self.channels]);
+        }
+        self.finished = true;
+    }
+}
+
+#[cfg(feature = "time-stretch")]
+impl<S> Iterator for TimeStretch<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        loop {
+            if let Some(sample) = self.ready.pop_front() {
+                return Some(sample);
+            }
+            if self.finished {
+                return None;
+            }
+            let factor = self.stretch.stretch_factor();
+            match self.last {
+                None if factor == 1.0 && self.read.is_empty() => return self.input.next(),
+                Some(last) if factor == 1.0 => self.pass_through(last),
+                _ if self.end.is_some_and(|end| self.next as usize >= end) => self.finish(),
+                _ => self.step(factor),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "time-stretch")]
+impl<S> Source for TimeStretch<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.last {
+            None if self.ready.is_empty() && self.read.is_empty() && !self.finished => {
+                self.input.current_frame_len()
+            }
+            _ => None,
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.reset();
+        Ok(())
+    }
+}
+
+/// Source adapter resampling the input by the pitch ratio
+/// of the stretch, by linear interpolation, raising the pitch
+/// and the speed together
+///
+/// The input passes through untouched at the ratio of 1.0
+#[cfg(feature = "time-stretch")]
+pub(crate) struct Resample<S> {
+    input: S,
+    stretch: SharedStretch,
+    channels: usize,
+    // the two frames around the position, interleaved
+    frames: VecDeque<f32>,
+    // position between the two frames
+    pos: f64,
+    ready: VecDeque<f32>,
+}
+
+#[cfg(feature = "time-stretch")]
+impl<S> Resample<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(input: S, stretch: SharedStretch) -> Resample<S> {
+        Resample {
+            channels: input.channels().max(1) as usize,
+            input,
+            stretch,
+            frames: VecDeque::new(),
+            pos: 0.0,
+            ready: VecDeque::new(),
+        }
+    }
+
+    /// Read the input until there are the frames,
+    /// returns `false` if it ends before
+    fn fill(&mut self, frames: usize) -> bool {
+        while self.frames.len() < frames * self.channels {
+            match self.input.next() {
+                Some(sample) => self.frames.push_back(sample),
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(feature = "time-stretch")]
+impl<S> Iterator for Resample<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if let Some(sample) = self.ready.pop_front() {
+            return Some(sample);
+        }
+        let ratio = self.stretch.pitch.get();
+        if self.frames.is_empty() && ratio == 1.0 {
+            return self.input.next();
+        }
+        if !self.fill(2) {
+            // the input is over, so is the last frame
+            self.ready.extend(self.frames.drain(..));
+            return self.ready.pop_front();
+        }
+        if ratio == 1.0 && self.pos == 0.0 {
+            // right on a frame, back to passing through
+            self.ready.extend(self.frames.drain(..));
+            return self.ready.pop_front();
+        }
+        let t = self.pos as f32;
+        for c in 0..self.channels {
+            let (from, to) = (self.frames[c], self.frames[self.channels + c]);
+            self.ready.push_back(from + (to - from) * t);
+        }
+        self.pos += ratio as f64;
+        let rate = self.input.sample_rate().max(1) as f64;
+        self.stretch.add_drift((ratio as f64 - 1.0) / rate);
+        while self.pos >= 1.0 && self.fill(2) {
+            self.frames.drain(..self.channels);
+            self.pos -= 1.0;
+        }
+        self.ready.pop_front()
+    }
+}
+
+#[cfg(feature = "time-stretch")]
+impl<S> Source for Resample<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_frame_len(&self) -> Option<usize> {
+        match self.frames.is_empty() && self.ready.is_empty() {
+            true => self.input.current_frame_len(),
+            false => None,
+        }
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels as u16
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.input.sample_rate()
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.input.total_duration()
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        self.input.try_seek(pos)?;
+        self.frames.clear();
+        self.pos = 0.0;
+        self.ready.clear();
+        Ok(())
+    }
+}
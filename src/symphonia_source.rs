@@ -0,0 +1,180 @@
+use std::{io, time::Duration};
+
+use rodio::{source::SeekError, Source};
+use symphonia::core::{
+    audio::SampleBuffer,
+    codecs::{Decoder, DecoderOptions},
+    errors::Error as DecodeError,
+    formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+    io::{MediaSource, MediaSourceStream},
+    meta::MetadataOptions,
+    probe::Hint,
+    units::TimeBase,
+};
+
+/// Source decoding a file or stream with Symphonia,
+/// packet by packet
+pub(crate) struct SymphoniaSource {
+    format: Box<dyn FormatReader>,
+    decoder: Box<dyn Decoder>,
+    track_id: u32,
+    time_base: Option<TimeBase>,
+    total_duration: Option<Duration>,
+    seekable: bool,
+    buffer: Vec<f32>,
+    pos: usize,
+    // samples decoded before the position seeked to
+    skip: usize,
+    channels: u16,
+    sample_rate: u32,
+}
+
+impl SymphoniaSource {
+    pub fn new(stream: MediaSourceStream, hint: &Hint) -> io::Result<SymphoniaSource> {
+        let seekable = stream.is_seekable();
+        let format = symphonia::default::get_probe()
+            .format(
+                hint,
+                stream,
+                &FormatOptions::default(),
+                &MetadataOptions::default(),
+            )
+            .map_err(invalid_data)?
+            .format;
+        let track = format
+            .default_track()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no audio in the stream"))?;
+        let decoder = symphonia::default::get_codecs()
+            .make(&track.codec_params, &DecoderOptions::default())
+            .map_err(invalid_data)?;
+        let params = &track.codec_params;
+        let total_duration = params
+            .n_frames
+            .zip(params.sample_rate)
+            .map(|(frames, rate)| Duration::from_secs_f64(frames as f64 / rate as f64));
+        let mut source = SymphoniaSource {
+            track_id: track.id,
+            time_base: params.time_base,
+            total_duration,
+            seekable,
+            format,
+            decoder,
+            buffer: vec![],
+            pos: 0,
+            skip: 0,
+            channels: 0,
+            sample_rate: 0,
+        };
+        // the format is known once a packet is decoded
+        if !source.decode_next() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no audio in the stream",
+            ));
+        }
+        Ok(source)
+    }
+
+    /// Decode the next packet into the buffer, returns
+    /// `false` if the stream ends or fails
+    fn decode_next(&mut self) -> bool {
+        loop {
+            let Ok(packet) = self.format.next_packet() else {
+                return false;
+            };
+            if packet.track_id() != self.track_id {
+                continue;
+            }
+            match self.decoder.decode(&packet) {
+                Ok(decoded) => {
+                    let spec = *decoded.spec();
+                    let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+                    samples.copy_interleaved_ref(decoded);
+                    self.buffer.clear();
+                    self.buffer.extend_from_slice(samples.samples());
+                    self.channels = spec.channels.count() as u16;
+                    self.sample_rate = spec.rate;
+                    self.pos = self.skip.min(self.buffer.len());
+                    self.skip -= self.pos;
+                    if self.pos < self.buffer.len() {
+                        return true;
+                    }
+                }
+                // a corrupted packet is skipped
+                Err(DecodeError::DecodeError(_)) => continue,
+                Err(_) => return false,
+            }
+        }
+    }
+
+    /// Samples in the time between two timestamps of the track
+    fn samples_between(&self, from: u64, to: u64) -> usize {
+        let frames = to.saturating_sub(from);
+        let frames = match self.time_base {
+            Some(time_base) => {
+                let time = time_base.calc_time(frames);
+                (time.seconds as f64 + time.frac) * self.sample_rate as f64
+            }
+            None => frames as f64,
+        };
+        frames as usize * self.channels as usize
+    }
+}
+
+fn invalid_data(e: DecodeError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+impl Iterator for SymphoniaSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.pos == self.buffer.len() && !self.decode_next() {
+            return None;
+        }
+        self.pos += 1;
+        Some(self.buffer[self.pos - 1])
+    }
+}
+
+impl Source for SymphoniaSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.buffer.len())
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        self.total_duration
+    }
+
+    fn try_seek(&mut self, pos: Duration) -> Result<(), SeekError> {
+        if !self.seekable {
+            return Err(SeekError::NotSupported {
+                underlying_source: "a live stream",
+            });
+        }
+        let seeked = self
+            .format
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: pos.as_secs_f64().into(),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .map_err(|e| SeekError::Other(Box::new(e)))?;
+        self.decoder.reset();
+        // the packet seeked to may start before the position
+        self.skip = self.samples_between(seeked.actual_ts, seeked.required_ts);
+        self.buffer.clear();
+        self.pos = 0;
+        Ok(())
+    }
+}
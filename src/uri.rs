@@ -0,0 +1,144 @@
+use std::{borrow::Cow, io, path::PathBuf, sync::Arc};
+
+#[cfg(feature = "radio")]
+use crate::radio::Radio;
+use crate::{effect::BoxedSource, source::IntoAudioSource};
+
+/// Path of a song read as a URI: `file://`, `http://` or
+/// `data:` ones, or a bare path; `https://` ones need the
+/// `tls` feature
+#[derive(PartialEq, Debug)]
+pub(crate) enum SongUri<'a> {
+    FILE(Cow<'a, str>),
+    HTTP(&'a str),
+    /// Encoded audio carried by the URI itself
+    DATA(Vec<u8>),
+}
+
+fn invalid_uri(message: &str, path: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("{}: {:.64}", message, path),
+    )
+}
+
+/// Strip the scheme from the path, ignoring its case
+pub(crate) fn strip_scheme<'a>(path: &'a str, scheme: &str) -> Option<&'a str> {
+    let head = path.get(..scheme.len())?;
+    head.eq_ignore_ascii_case(scheme)
+        .then(|| &path[scheme.len()..])
+}
+
+impl SongUri<'_> {
+    pub fn parse(path: &str) -> io::Result<SongUri<'_>> {
+        if let Some(rest) = strip_scheme(path, "file://") {
+            // the host, if any, is always the local one
+            let rest = rest.strip_prefix("localhost").unwrap_or(rest);
+            let decoded = percent_decode(rest).ok_or_else(|| invalid_uri("bad file URI", path))?;
+            let decoded =
+                String::from_utf8(decoded).map_err(|_| invalid_uri("bad file URI", path))?;
+            return Ok(SongUri::FILE(Cow::Owned(decoded)));
+        }
+        if strip_scheme(path, "http://").is_some() {
+            return Ok(SongUri::HTTP(path));
+        }
+        if strip_scheme(path, "https://").is_some() {
+            #[cfg(feature = "tls")]
+            return Ok(SongUri::HTTP(path));
+            #[cfg(not(feature = "tls"))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("https streams need the tls feature: {:.64}", path),
+            ));
+        }
+        if let Some(rest) = strip_scheme(path, "data:") {
+            let (header, data) = rest
+                .split_once(',')
+                .ok_or_else(|| invalid_uri("bad data URI", path))?;
+            let bytes = match header.ends_with(";base64") {
+                true => base64_decode(data),
+                false => percent_decode(data),
+            };
+            return bytes
+                .map(SongUri::DATA)
+                .ok_or_else(|| invalid_uri("bad data URI", path));
+        }
+        Ok(SongUri::FILE(Cow::Borrowed(path)))
+    }
+
+    /// Decode the audio the URI points to or carries
+    pub fn open_f32(self) -> io::Result<BoxedSource> {
+        match self {
+            SongUri::FILE(path) => PathBuf::from(path.as_ref()).open_f32(),
+            #[cfg(feature = "radio")]
+            SongUri::HTTP(url) => Radio::new(url).open_f32(),
+            #[cfg(not(feature = "radio"))]
+            SongUri::HTTP(url) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("streams need the radio feature: {:.64}", url),
+            )),
+            SongUri::DATA(bytes) => Arc::<[u8]>::from(bytes).open_f32(),
+        }
+    }
+}
+
+/// `file://` URL of the path made absolute, with the
+/// bytes not allowed in a URL escaped as `%xx`
+pub(crate) fn file_url(path: &str) -> String {
+    let path = std::path::absolute(path)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| path.to_string());
+    let mut url = String::from("file://");
+    for &byte in path.as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    url
+}
+
+/// Decode `%xx` escapes, `None` if one is malformed
+fn percent_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    Some(bytes)
+}
+
+/// Decode standard or URL-safe base64, skipping
+/// whitespace, `None` if it's malformed
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut bits, mut count) = (0u32, 0);
+    for c in text.bytes() {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            _ => return None,
+        };
+        bits = bits << 6 | value as u32;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,84 @@
+use std::{
+    sync::{
+        mpsc::{channel, Receiver},
+        Arc, Weak,
+    },
+    thread::{sleep, spawn},
+    time::Duration,
+};
+
+use crate::{
+    asset::{PlaybackState, PlayerStatus},
+    shared_player::{SharedState, StateLock},
+    song::SongId,
+};
+
+/// Interval of checking the status for the watchers
+const WATCH_INTERVAL: Duration = Duration::from_millis(50);
+
+/// What a new status is pushed for: the song, the state
+/// and the position in whole seconds
+#[derive(PartialEq)]
+struct WatchKey {
+    song: Option<(Option<SongId>, String)>,
+    state: PlaybackState,
+    second: u64,
+}
+
+impl WatchKey {
+    fn of(status: &PlayerStatus) -> WatchKey {
+        WatchKey {
+            song: status
+                .song
+                .as_ref()
+                .map(|song| (song.id, song.path.clone())),
+            state: status.state,
+            second: status.position.as_secs(),
+        }
+    }
+}
+
+/// Receive current status, then a new one whenever the
+/// song, the state or the second of the position changes
+pub(crate) fn watch(state: &SharedState) -> Receiver<PlayerStatus> {
+    let (sender, receiver) = channel();
+    let mut asset = state.write();
+    let status = asset.status();
+    let _ = sender.send(status.clone());
+    asset.watchers.push(sender);
+    if !asset.watching {
+        asset.watching = true;
+        let state = Arc::downgrade(state);
+        spawn(move || run(state, WatchKey::of(&status)));
+    }
+    receiver
+}
+
+/// Push the changed statuses until every watcher or
+/// the player is gone
+fn run(state: Weak<StateLock>, mut last: WatchKey) {
+    loop {
+        sleep(WATCH_INTERVAL);
+        let Some(state) = state.upgrade() else {
+            return;
+        };
+        // only a change or the last watcher gone is written
+        let (status, unwatched) = {
+            let state = state.read();
+            (state.status(), state.watchers.is_empty())
+        };
+        let key = WatchKey::of(&status);
+        if key == last && !unwatched {
+            continue;
+        }
+        let mut state = state.write();
+        state
+            .watchers
+            .retain(|watcher| watcher.send(status.clone()).is_ok());
+        last = key;
+        if state.watchers.is_empty() {
+            state.watching = false;
+            return;
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{
+    io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread::spawn,
+    time::{Duration, Instant},
+};
+
+use serde_json::Value;
+use tungstenite::{
+    handshake::server::{ErrorResponse, Request, Response},
+    http::StatusCode,
+    Error, Message,
+};
+
+use crate::{
+    player::Player,
+    rpc::{self, Caller},
+    secret::{given_token, same_secret, server_token},
+    shared_player::SharedPlayer,
+};
+
+/// Interval of checking for requests and events of a client
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Least interval between status updates sent to a client
+const STATUS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Running WebSocket service, stopped when dropped,
+/// closing the connections of its clients
+pub struct WsServer {
+    addr: SocketAddr,
+    stopped: Arc<AtomicBool>,
+}
+
+impl WsServer {
+    /// Address served, e.g. to find the port given by the OS
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+impl Drop for WsServer {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+        // wake the listener up to see it's stopped
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+/// Serve the player to WebSocket clients at the address, e.g.
+/// a phone used as the remote of a desktop player
+///
+/// Clients send requests as `serve_ipc` takes them, one per text
+/// message, and get their responses. The server also sends every
+/// `PlayerEvent` as an `event` notification, and `PlayerStatus`
+/// as a `status` notification whenever it changes:
+///
+/// ```text
+/// <-- {"jsonrpc": "2.0", "method": "status", "params": {"state": "PLAYING", ...}}
+/// ```
+///
+/// Clients give the `token` shared with them in the URL, e.g.
+/// `ws://192.168.1.2:8765/?token=...`, or as a bearer token of
+/// the `Authorization` header; pages of browsers are only
+/// accepted from the origin of the server itself. Methods
+/// taking paths of files or songs, e.g. `save_session` or
+/// `add`, and `shutdown` aren't served, and the paths of the
+/// songs sent are blanked
+pub fn serve_ws(
+    player: SharedPlayer,
+    addr: impl ToSocketAddrs,
+    token: impl Into<String>,
+) -> io::Result<WsServer> {
+    let token = server_token(token.into())?;
+    let listener = TcpListener::bind(addr)?;
+    let addr = listener.local_addr()?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let accept_stopped = Arc::clone(&stopped);
+    spawn(move || {
+        for stream in listener.incoming() {
+            if accept_stopped.load(Ordering::Relaxed) {
+                break;
+            }
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let player = player.clone();
+            let stopped = Arc::clone(&accept_stopped);
+            let token = Arc::clone(&token);
+            spawn(move || {
+                let _ = serve_client(&player, stream, &token, &stopped);
+            });
+        }
+    });
+    Ok(WsServer { addr, stopped })
+}
+
+/// Accept the handshake of a client giving the token, from
+/// no browser or a page of the same origin as the server
+fn check_handshake(request: &Request, token: &str) -> Result<(), (StatusCode, &'static str)> {
+    let header = |name: &str| {
+        request
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    let query = request.uri().query().unwrap_or_default();
+    let given = given_token(header("authorization"), query);
+    if !given.is_some_and(|given| same_secret(given, token)) {
+        return Err((StatusCode::UNAUTHORIZED, "the token is wrong"));
+    }
+    // sent by browsers, e.g. "http://192.168.1.2:8765"
+    if let Some(origin) = header("origin") {
+        let origin_host = origin.split_once("://").map(|(_, host)| host);
+        if origin_host.is_none() || origin_host != header("host") {
+            return Err((StatusCode::FORBIDDEN, "the origin is foreign"));
+        }
+    }
+    Ok(())
+}
+
+/// Answer the requests of a client and send it the events
+/// and status updates, until it disconnects
+fn serve_client(
+    player: &SharedPlayer,
+    stream: TcpStream,
+    token: &str,
+    stopped: &AtomicBool,
+) -> io::Result<()> {
+    // the error response is the one tungstenite takes
+    #[allow(clippy::result_large_err)]
+    let callback = |request: &Request, response: Response| match check_handshake(request, token) {
+        Ok(()) => Ok(response),
+        Err((status, reason)) => {
+            let mut rejected = ErrorResponse::new(Some(reason.to_string()));
+            *rejected.status_mut() = status;
+            Err(rejected)
+        }
+    };
+    let mut socket =
+        tungstenite::accept_hdr(stream, callback).map_err(|e| io::Error::other(e.to_string()))?;
+    // read in turns with sending
+    socket.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+    let Ok(events) = player.subscribe().join() else {
+        return Ok(());
+    };
+    let mut status = Value::Null;
+    let mut status_sent = Instant::now() - STATUS_INTERVAL;
+    let send = |socket: &mut tungstenite::WebSocket<TcpStream>, text: String| {
+        socket
+            .send(Message::Text(text))
+            .map_err(|e| io::Error::other(e.to_string()))
+    };
+    while !stopped.load(Ordering::Relaxed) {
+        match socket.read() {
+            Ok(Message::Text(request)) => {
+                if let Some(response) = rpc::handle(player, &request, Caller::Remote) {
+                    send(&mut socket, response)?;
+                }
+            }
+            // pings are answered by tungstenite itself
+            Ok(_) => {}
+            Err(Error::Io(e))
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) => {}
+            Err(Error::ConnectionClosed | Error::AlreadyClosed) => return Ok(()),
+            Err(e) => return Err(io::Error::other(e.to_string())),
+        }
+        for event in events.try_iter() {
+            send(&mut socket, rpc::notification("event", event))?;
+        }
+        if status_sent.elapsed() >= STATUS_INTERVAL {
+            let latest = serde_json::to_value(player.snapshot()).unwrap_or_default();
+            if latest != status {
+                send(&mut socket, rpc::notification("status", &latest))?;
+                status = latest;
+                status_sent = Instant::now();
+            }
+        }
+    }
+    let _ = socket.close(None);
+    Ok(())
+}